@@ -0,0 +1,59 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use serde_json::Value;
+use snafu::ResultExt;
+
+use crate::{CanvasDecodeSnafu, ExportError};
+
+type Result<T, E = ExportError> = std::result::Result<T, E>;
+
+/// Convert the contents of the Obsidian canvas (JSON Canvas) file at `path` into Obsidian-flavored
+/// markdown, which can then be parsed like a regular note.
+///
+/// Each card (node) on the canvas becomes a paragraph, ordered from top to bottom and left to
+/// right: text cards are included as-is, cards showing a file become a wikilink to that file (or
+/// an embed, for attachments for which `is_note` returns false) and web page cards become links.
+/// Groups and the connections between cards are left out.
+pub fn to_markdown(path: &Path, contents: &str, is_note: &dyn Fn(&Path) -> bool) -> Result<String> {
+    let canvas: Value = serde_json::from_str(contents).context(CanvasDecodeSnafu { path })?;
+    let mut nodes: Vec<&Value> = canvas
+        .get("nodes")
+        .and_then(Value::as_array)
+        .map(|nodes| nodes.iter().collect())
+        .unwrap_or_default();
+    nodes.sort_by(|a, b| {
+        let position =
+            |node: &Value, axis: &str| node.get(axis).and_then(Value::as_f64).unwrap_or_default();
+        match position(a, "y").total_cmp(&position(b, "y")) {
+            Ordering::Equal => position(a, "x").total_cmp(&position(b, "x")),
+            ordering => ordering,
+        }
+    });
+
+    let cards: Vec<String> = nodes
+        .into_iter()
+        .filter_map(|node| {
+            let field = |name: &str| node.get(name).and_then(Value::as_str);
+            match field("type")? {
+                "text" => field("text").map(str::to_owned),
+                "file" => {
+                    let file = field("file")?;
+                    let subpath = field("subpath").unwrap_or_default();
+                    let label = Path::new(file).file_stem()?.to_string_lossy();
+                    Some(match is_note(Path::new(file)) {
+                        true => format!("[[{file}{subpath}|{label}]]"),
+                        false => format!("![[{file}{subpath}]]"),
+                    })
+                }
+                "link" => field("url").map(|url| format!("<{url}>")),
+                _ => None,
+            }
+        })
+        .filter(|card| !card.trim().is_empty())
+        .collect();
+
+    let mut markdown = cards.join("\n\n");
+    markdown.push('\n');
+    Ok(markdown)
+}
@@ -1,6 +1,19 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use crate::Frontmatter;
+use pulldown_cmark::HeadingLevel;
+use serde::de::DeserializeOwned;
+use serde::ser::Error as _;
+use serde::Serialize;
+use serde_yaml::Value;
+use snafu::ResultExt;
+
+use crate::headings::NoteAnchors;
+use crate::{ExportError, FrontMatterDecodeSnafu, FrontMatterEncodeSnafu, Frontmatter, LinkGraph};
+
+type EmbeddedNotes = HashSet<(PathBuf, Option<String>)>;
 
 #[derive(Debug, Clone)]
 /// Context holds metadata about a note which is being parsed.
@@ -13,6 +26,39 @@ use crate::Frontmatter;
 pub struct Context {
     file_tree: Vec<PathBuf>,
 
+    // Notes (and sections thereof) which have been embedded into the root note so far. This is
+    // shared between a context and all of the child contexts derived from it.
+    embedded_notes: Arc<Mutex<EmbeddedNotes>>,
+
+    // The cumulative size (in bytes) of the content embedded into the root note so far. Like
+    // embedded_notes, this is shared with all child contexts.
+    embedded_bytes: Arc<AtomicUsize>,
+
+    // The number of notes embedded into the root note so far, which is shared with all child
+    // contexts as well.
+    embed_count: Arc<AtomicUsize>,
+
+    // Links found in the root note and the notes embedded into it. This is shared with all child
+    // contexts as well.
+    links: Arc<Mutex<LinkGraph>>,
+
+    // The raw contents of the note which is currently being parsed.
+    source_content: Arc<str>,
+
+    // The root directory of the vault which is being exported.
+    vault_root: PathBuf,
+
+    // Raw output which replaces the rendered note, if set by a postprocessor.
+    output_bytes: Option<Vec<u8>>,
+
+    // The level of the heading which the content currently being parsed falls under, if any.
+    heading_level: Option<HeadingLevel>,
+
+    // The anchors of the headings of the note which is currently being parsed, used to resolve
+    // links to sections within the same note. Unlike most other fields, this isn't inherited by
+    // child contexts.
+    note_anchors: Option<Arc<NoteAnchors>>,
+
     /// The path where this note will be written to when exported.
     ///
     /// Changing this path will result in the note being written to that new path instead, but
@@ -49,8 +95,18 @@ impl Context {
     #[inline]
     #[must_use]
     pub fn new(src: PathBuf, dest: PathBuf) -> Self {
+        let vault_root = src.parent().map(Path::to_path_buf).unwrap_or_default();
         Self {
+            vault_root,
             file_tree: vec![src],
+            embedded_notes: Arc::default(),
+            embedded_bytes: Arc::default(),
+            embed_count: Arc::default(),
+            links: Arc::default(),
+            source_content: Arc::from(""),
+            output_bytes: None,
+            heading_level: None,
+            note_anchors: None,
             destination: dest,
             frontmatter: Frontmatter::new(),
         }
@@ -62,6 +118,7 @@ impl Context {
     pub fn from_parent(context: &Self, child: &Path) -> Self {
         let mut context = context.clone();
         context.file_tree.push(child.to_path_buf());
+        context.note_anchors = None;
         context
     }
 
@@ -74,6 +131,21 @@ impl Context {
             .expect("Context not initialized properly, file_tree is empty")
     }
 
+    /// Return the path of the file which embedded the file currently being parsed.
+    ///
+    /// This returns `None` for root notes, which aren't embedded in any other note. For embedded
+    /// notes, this returns the note which directly embeds `current_file`. This is the same as
+    /// `root_file` when a note is embedded in the root note, but differs for notes which are
+    /// embedded through other embedded notes.
+    #[inline]
+    #[must_use]
+    pub fn parent_file(&self) -> Option<&PathBuf> {
+        self.file_tree
+            .len()
+            .checked_sub(2)
+            .and_then(|idx| self.file_tree.get(idx))
+    }
+
     /// Return the path of the root file.
     ///
     /// Typically this will yield the same element as `current_file`, but when a note is embedded
@@ -86,6 +158,30 @@ impl Context {
             .expect("Context not initialized properly, file_tree is empty")
     }
 
+    /// Return the root directory of the vault which is being exported.
+    ///
+    /// This may be used to determine the location of notes within the vault, for example by
+    /// stripping it from [`Context::current_file`]. When the `Context` isn't created by an
+    /// [`Exporter`][crate::Exporter], this is the directory containing the root file.
+    #[inline]
+    #[must_use]
+    pub fn vault_root(&self) -> &Path {
+        &self.vault_root
+    }
+
+    pub(crate) fn set_vault_root(&mut self, vault_root: &Path) {
+        vault_root.clone_into(&mut self.vault_root);
+    }
+
+    /// Return the level of the heading which the content currently being parsed falls under.
+    pub(crate) const fn heading_level(&self) -> Option<HeadingLevel> {
+        self.heading_level
+    }
+
+    pub(crate) fn set_heading_level(&mut self, level: Option<HeadingLevel>) {
+        self.heading_level = level;
+    }
+
     /// Return the note depth (nesting level) for this context.
     #[inline]
     #[must_use]
@@ -102,4 +198,178 @@ impl Context {
     pub fn file_tree(&self) -> Vec<PathBuf> {
         self.file_tree.clone()
     }
+
+    /// Return the original, unparsed contents of the note currently being parsed.
+    ///
+    /// This is the full text of `current_file` as it was read from disk (after decoding it to
+    /// UTF-8), including its frontmatter. It is empty until the note has been read.
+    ///
+    /// Note that this keeps the contents of the note in memory for as long as this context (or
+    /// any of its clones) lives, in addition to the parsed
+    /// [`MarkdownEvents`][crate::MarkdownEvents].
+    #[inline]
+    #[must_use]
+    pub fn source_content(&self) -> &str {
+        &self.source_content
+    }
+
+    pub(crate) fn set_source_content(&mut self, content: String) {
+        self.source_content = Arc::from(content);
+    }
+
+    /// Replace the exported note with `bytes`.
+    ///
+    /// When this is set by a [postprocessor][crate::Postprocessor], `bytes` are written to
+    /// [`Context::destination`] verbatim instead of the rendered markdown events. Frontmatter isn't
+    /// written either, regardless of the [`FrontmatterStrategy`][crate::FrontmatterStrategy]. This
+    /// allows postprocessors to convert notes to an entirely different format.
+    ///
+    /// This only has an effect on the root note: setting it from an
+    /// [embed postprocessor][crate::Exporter::add_embed_postprocessor] does nothing.
+    #[inline]
+    pub fn set_output_bytes(&mut self, bytes: Vec<u8>) {
+        self.output_bytes = Some(bytes);
+    }
+
+    /// Return the raw output set through [`Context::set_output_bytes`], if any.
+    #[inline]
+    #[must_use]
+    pub fn output_bytes(&self) -> Option<&[u8]> {
+        self.output_bytes.as_deref()
+    }
+
+    /// Discard the raw output set through [`Context::set_output_bytes`], so that the note's
+    /// markdown events are rendered as usual.
+    #[inline]
+    pub fn clear_output_bytes(&mut self) {
+        self.output_bytes = None;
+    }
+
+    pub(crate) fn take_output_bytes(&mut self) -> Option<Vec<u8>> {
+        self.output_bytes.take()
+    }
+
+    /// Deserialize the note's [Frontmatter] into `T`.
+    ///
+    /// This, together with [`Context::set_frontmatter_from`], allows working with frontmatter
+    /// through a custom type rather than through [`serde_yaml::Mapping`] directly.
+    ///
+    /// # Example
+    ///
+    /// Increment a counter in the frontmatter of a note, leaving other keys untouched:
+    ///
+    /// ```
+    /// # use std::path::PathBuf;
+    /// # use obsidian_export::Context;
+    /// use obsidian_export::serde_yaml::{Mapping, Value};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Meta {
+    ///     title: String,
+    ///     #[serde(default)]
+    ///     revision: u32,
+    ///     #[serde(flatten)]
+    ///     other: Mapping,
+    /// }
+    ///
+    /// # let mut context = Context::new(PathBuf::from("source"), PathBuf::from("destination"));
+    /// # context.frontmatter.insert("title".into(), "Note".into());
+    /// # context.frontmatter.insert("tags".into(), vec!["foo"].into());
+    /// let mut meta: Meta = context.frontmatter_as().unwrap();
+    /// meta.revision += 1;
+    /// context.set_frontmatter_from(&meta).unwrap();
+    ///
+    /// assert_eq!(context.frontmatter.get("revision"), Some(&Value::from(1)));
+    /// assert_eq!(context.frontmatter.get("title"), Some(&Value::from("Note")));
+    /// assert_eq!(
+    ///     context.frontmatter.get("tags"),
+    ///     Some(&Value::from(vec!["foo"]))
+    /// );
+    /// ```
+    pub fn frontmatter_as<T: DeserializeOwned>(&self) -> Result<T, ExportError> {
+        serde_yaml::from_value(Value::Mapping(self.frontmatter.clone())).context(
+            FrontMatterDecodeSnafu {
+                path: self.current_file(),
+            },
+        )
+    }
+
+    /// Replace the note's [Frontmatter] with `value`, which must serialize to a mapping (or to
+    /// nothing at all, which results in empty frontmatter).
+    ///
+    /// See [`Context::frontmatter_as`] for an example.
+    pub fn set_frontmatter_from<T: Serialize>(&mut self, value: &T) -> Result<(), ExportError> {
+        let context = FrontMatterEncodeSnafu {
+            path: self.current_file(),
+        };
+        self.frontmatter = match serde_yaml::to_value(value).context(context)? {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => Frontmatter::new(),
+            _ => {
+                return Err(serde_yaml::Error::custom("frontmatter must be a mapping"))
+                    .context(context)
+            }
+        };
+        Ok(())
+    }
+
+    /// Record that (a section of) `note` is being embedded into the root note.
+    ///
+    /// Returns `false` if the same note and section were already embedded before.
+    pub(crate) fn record_embedded_note(&self, note: &Path, section: Option<&str>) -> bool {
+        self.embedded_notes
+            .lock()
+            .expect("embedded_notes mutex should not be poisoned")
+            .insert((note.to_path_buf(), section.map(str::to_owned)))
+    }
+
+    /// Record a link from the current file to `target`, ignoring links from a file to itself.
+    pub(crate) fn record_link(&self, target: &Path) {
+        let source = self.current_file();
+        if source != target {
+            self.links
+                .lock()
+                .expect("links mutex should not be poisoned")
+                .insert(source, target);
+        }
+    }
+
+    /// Take the links recorded through [`Context::record_link`], leaving an empty graph behind.
+    pub(crate) fn take_links(&self) -> LinkGraph {
+        std::mem::take(
+            &mut *self
+                .links
+                .lock()
+                .expect("links mutex should not be poisoned"),
+        )
+    }
+
+    /// Return the cumulative size (in bytes) of the content embedded into the root note so far.
+    pub(crate) fn embedded_bytes(&self) -> usize {
+        self.embedded_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_embedded_bytes(&self, bytes: usize) {
+        self.embedded_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Return the anchors of the headings of the current note, if set through
+    /// [`Context::set_note_anchors`].
+    pub(crate) fn note_anchors(&self) -> Option<Arc<NoteAnchors>> {
+        self.note_anchors.clone()
+    }
+
+    pub(crate) fn set_note_anchors(&mut self, anchors: NoteAnchors) {
+        self.note_anchors = Some(Arc::new(anchors));
+    }
+
+    /// Return the number of notes embedded into the root note so far.
+    pub(crate) fn embed_count(&self) -> usize {
+        self.embed_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_embed_count(&self, count: usize) {
+        self.embed_count.store(count, Ordering::Relaxed);
+    }
 }
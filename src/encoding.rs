@@ -0,0 +1,79 @@
+use std::path::Path;
+use std::{fs, str};
+
+use encoding_rs::{Encoding, UTF_8};
+use snafu::ResultExt;
+
+use crate::{CharacterEncodingSnafu, ExportError, ReadSnafu, Utf16EncodingSnafu};
+
+type Result<T, E = ExportError> = std::result::Result<T, E>;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Available strategies for dealing with notes which are not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+pub enum EncodingFallback {
+    /// Fail with [`ExportError::CharacterEncodingError`] when a note contains invalid UTF-8.
+    #[default]
+    Error,
+    /// Replace any invalid UTF-8 sequences with the Unicode replacement character (`U+FFFD`).
+    Lossy,
+    /// Decode notes which are not valid UTF-8 using the given legacy encoding instead.
+    ///
+    /// Encodings may be looked up by name using [`Encoding::for_label`].
+    Encoding(&'static Encoding),
+}
+
+/// Read the contents of the file at `path` into a string, applying the given `fallback` when the
+/// file isn't valid UTF-8.
+///
+/// Files starting with a UTF-16 (little or big endian) byte order mark are decoded as UTF-16
+/// instead. A leading byte order mark is removed, as it isn't part of the note's content.
+pub fn read_to_string(path: &Path, fallback: EncodingFallback) -> Result<String> {
+    let mut bytes = fs::read(path).context(ReadSnafu { path })?;
+    if let Some((encoding, bom_length)) =
+        Encoding::for_bom(&bytes).filter(|&(encoding, _)| encoding != UTF_8)
+    {
+        return decode_utf16(
+            path,
+            encoding,
+            bytes.get(bom_length..).unwrap_or_default(),
+            fallback,
+        );
+    }
+    if bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    }
+    match str::from_utf8(&bytes) {
+        Ok(content) => Ok(content.to_owned()),
+        Err(err) => match fallback {
+            EncodingFallback::Error => Err(err).context(CharacterEncodingSnafu { path }),
+            EncodingFallback::Lossy => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+            EncodingFallback::Encoding(encoding) => {
+                let (content, _had_errors) = encoding.decode_without_bom_handling(&bytes);
+                Ok(content.into_owned())
+            }
+        },
+    }
+}
+
+// Decode `bytes` (without byte order mark) using the UTF-16 `encoding`.
+fn decode_utf16(
+    path: &Path,
+    encoding: &'static Encoding,
+    bytes: &[u8],
+    fallback: EncodingFallback,
+) -> Result<String> {
+    if let Some(content) = encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        return Ok(content.into_owned());
+    }
+    match fallback {
+        EncodingFallback::Error => Utf16EncodingSnafu { path }.fail(),
+        EncodingFallback::Lossy | EncodingFallback::Encoding(_) => {
+            let (content, _had_errors) = encoding.decode_without_bom_handling(bytes);
+            Ok(content.into_owned())
+        }
+    }
+}
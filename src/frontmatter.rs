@@ -1,4 +1,4 @@
-use serde_yaml::Result;
+use serde_yaml::{Result, Value};
 
 /// YAML front matter from an Obsidian note.
 ///
@@ -33,6 +33,70 @@ pub fn frontmatter_from_str(mut s: &str) -> Result<Frontmatter> {
     Ok(frontmatter)
 }
 
+/// Parse frontmatter from `s`, applying `fallback` when `s` contains valid YAML which is not a
+/// mapping.
+///
+/// Alongside the frontmatter, this returns the value which was discarded when
+/// [`FrontmatterFallback::Ignore`] was applied.
+#[allow(clippy::module_name_repetitions)]
+pub fn frontmatter_from_str_with_fallback(
+    s: &str,
+    fallback: &FrontmatterFallback,
+) -> Result<(Frontmatter, Option<Value>)> {
+    let key = match fallback {
+        FrontmatterFallback::Error => return Ok((frontmatter_from_str(s)?, None)),
+        FrontmatterFallback::Ignore => None,
+        FrontmatterFallback::WrapInKey(key) => Some(key),
+    };
+    match serde_yaml::from_str(s)? {
+        Value::Mapping(frontmatter) => Ok((frontmatter, None)),
+        Value::Null => Ok((Frontmatter::new(), None)),
+        value => match key {
+            Some(key) => {
+                let mut frontmatter = Frontmatter::new();
+                frontmatter.insert(Value::String(key.clone()), value);
+                Ok((frontmatter, None))
+            }
+            None => Ok((Frontmatter::new(), Some(value))),
+        },
+    }
+}
+
+/// Split frontmatter with indented `---` fences, which may be preceded by blank lines or HTML
+/// comments, off `content`.
+///
+/// Returns the YAML of the frontmatter along with the rest of `content`, which keeps the comments
+/// preceding the frontmatter. Returns `None` when there's no such frontmatter, including when
+/// `content` starts with frontmatter right away.
+#[allow(clippy::module_name_repetitions)]
+pub fn split_leading_frontmatter(content: &str) -> Option<(String, String)> {
+    let mut comments = String::new();
+    let mut rest = content.trim_start();
+    while let Some(comment) = rest.strip_prefix("<!--") {
+        let end = comment.find("-->")?.saturating_add("<!---->".len());
+        comments.push_str(rest.get(..end)?);
+        comments.push('\n');
+        rest = rest.get(end..)?.trim_start();
+    }
+    if rest.len() == content.len() {
+        return None;
+    }
+
+    let mut lines = rest.split_inclusive('\n');
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+    let mut yaml = String::new();
+    for line in lines.by_ref() {
+        if matches!(line.trim(), "---" | "...") {
+            comments.extend(lines);
+            return Some((yaml, comments));
+        }
+        yaml.push_str(line);
+    }
+    None
+}
+
 // Would be nice to rename this to just to_str, but that would be a breaking change.
 #[allow(clippy::module_name_repetitions)]
 pub fn frontmatter_to_str(frontmatter: &Frontmatter) -> Result<String> {
@@ -47,6 +111,191 @@ pub fn frontmatter_to_str(frontmatter: &Frontmatter) -> Result<String> {
     Ok(buffer)
 }
 
+/// Serialize `frontmatter` as TOML, delimited by `+++` lines as used by Hugo.
+///
+/// # Errors
+///
+/// Returns an error when `frontmatter` contains values which have no TOML equivalent, such as
+/// null values or mappings with complex keys.
+#[allow(clippy::module_name_repetitions)]
+pub fn frontmatter_to_toml_str(
+    frontmatter: &Frontmatter,
+) -> std::result::Result<String, toml::ser::Error> {
+    let table = mapping_to_toml(frontmatter, "")?;
+
+    let mut buffer = String::new();
+    buffer.push_str("+++\n");
+    buffer.push_str(&toml::to_string(&table)?);
+    buffer.push_str("+++\n");
+    Ok(buffer)
+}
+
+fn mapping_to_toml(
+    mapping: &Frontmatter,
+    path: &str,
+) -> std::result::Result<toml::Table, toml::ser::Error> {
+    let mut table = toml::Table::new();
+    for (key, value) in mapping {
+        let key = match key {
+            Value::String(key) => key.clone(),
+            Value::Number(key) => key.to_string(),
+            Value::Bool(key) => key.to_string(),
+            _ => {
+                return Err(unsupported_toml_value(
+                    path,
+                    "has a key which isn't a string",
+                ));
+            }
+        };
+        let key_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        table.insert(key, value_to_toml(value, &key_path)?);
+    }
+    Ok(table)
+}
+
+fn value_to_toml(value: &Value, path: &str) -> std::result::Result<toml::Value, toml::ser::Error> {
+    match value {
+        Value::Null => Err(unsupported_toml_value(path, "is null")),
+        Value::Bool(boolean) => Ok(toml::Value::Boolean(*boolean)),
+        Value::Number(number) => number.as_i64().map_or_else(
+            || {
+                number
+                    .as_f64()
+                    .map(toml::Value::Float)
+                    .ok_or_else(|| unsupported_toml_value(path, "is an unsupported number"))
+            },
+            |int| Ok(toml::Value::Integer(int)),
+        ),
+        Value::String(string) => Ok(toml::Value::String(string.clone())),
+        Value::Sequence(values) => values
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| value_to_toml(item, &format!("{path}[{idx}]")))
+            .collect::<std::result::Result<_, _>>()
+            .map(toml::Value::Array),
+        Value::Mapping(mapping) => mapping_to_toml(mapping, path).map(toml::Value::Table),
+        // TOML has no concept of tags, so only the tagged value itself is kept.
+        Value::Tagged(tagged) => value_to_toml(&tagged.value, path),
+    }
+}
+
+fn unsupported_toml_value(path: &str, reason: &str) -> toml::ser::Error {
+    <toml::ser::Error as serde::ser::Error>::custom(format!(
+        "frontmatter value `{path}` {reason}, which can't be represented in TOML"
+    ))
+}
+
+/// Merge `overlay` into `base`, returning the combined frontmatter.
+///
+/// Values from `overlay` take precedence over those in `base`. With [`FrontmatterMerge::Deep`],
+/// mappings which appear under the same key in both are merged recursively rather than being
+/// replaced. The keys of `overlay` come first in the result, followed by any keys which only
+/// appear in `base`.
+///
+/// # Example
+///
+/// ```
+/// use obsidian_export::{merge_frontmatter, Frontmatter, FrontmatterMerge};
+///
+/// let defaults: Frontmatter = serde_yaml::from_str("layout: post\nseo: {index: true}").unwrap();
+/// let note: Frontmatter = serde_yaml::from_str("seo: {title: Note}").unwrap();
+///
+/// let merged = merge_frontmatter(&defaults, &note, FrontmatterMerge::Deep);
+/// let expected: Frontmatter =
+///     serde_yaml::from_str("seo: {title: Note, index: true}\nlayout: post").unwrap();
+/// assert_eq!(merged, expected);
+/// ```
+#[must_use]
+#[allow(clippy::module_name_repetitions)]
+pub fn merge_frontmatter(
+    base: &Frontmatter,
+    overlay: &Frontmatter,
+    strategy: FrontmatterMerge,
+) -> Frontmatter {
+    let mut merged = overlay.clone();
+    for (key, base_value) in base {
+        match (merged.get_mut(key), base_value) {
+            (None, _) => {
+                merged.insert(key.clone(), base_value.clone());
+            }
+            (Some(Value::Mapping(value)), Value::Mapping(base_mapping))
+                if strategy == FrontmatterMerge::Deep =>
+            {
+                *value = merge_frontmatter(base_mapping, value, strategy);
+            }
+            (Some(_), _) => {}
+        }
+    }
+    merged
+}
+
+/// Return a copy of `frontmatter` with its keys sorted, including the keys of nested mappings.
+///
+/// String keys are sorted alphabetically (by byte value). Keys of other types, which are rare in
+/// practice, are placed in a consistent order relative to them.
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::serde_yaml;
+/// use obsidian_export::{sort_frontmatter, Frontmatter};
+///
+/// let frontmatter: Frontmatter = serde_yaml::from_str("b: 1\na: {d: 2, c: 3}").unwrap();
+/// assert_eq!(
+///     serde_yaml::to_string(&sort_frontmatter(&frontmatter)).unwrap(),
+///     "a:\n  c: 3\n  d: 2\nb: 1\n"
+/// );
+/// ```
+#[must_use]
+#[allow(clippy::module_name_repetitions)]
+pub fn sort_frontmatter(frontmatter: &Frontmatter) -> Frontmatter {
+    let mut entries: Vec<_> = frontmatter.iter().collect();
+    entries.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.clone(), sort_value(value)))
+        .collect()
+}
+
+// Sort the keys of any mappings within `value`, see sort_frontmatter.
+fn sort_value(value: &Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(sort_frontmatter(mapping)),
+        Value::Sequence(values) => Value::Sequence(values.iter().map(sort_value).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// Available strategies for merging frontmatter, see [`merge_frontmatter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+pub enum FrontmatterMerge {
+    /// Merge only the top-level keys: a value which appears in both replaces the other entirely.
+    #[default]
+    Shallow,
+    /// Merge nested mappings recursively as well.
+    Deep,
+}
+
+/// The format in which frontmatter is written to exported notes.
+///
+/// Frontmatter is always read as YAML, which is what Obsidian uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+pub enum FrontmatterFormat {
+    /// YAML, delimited by `---` lines.
+    #[default]
+    Yaml,
+    /// TOML, delimited by `+++` lines (as used by Hugo).
+    Toml,
+}
+
 /// Available strategies for the inclusion of frontmatter in notes.
 #[derive(Debug, Clone, Copy)]
 // Would be nice to rename this to just Strategy, but that would be a breaking change.
@@ -62,6 +311,21 @@ pub enum FrontmatterStrategy {
     Never,
 }
 
+/// Available strategies for dealing with frontmatter which is valid YAML, but not a mapping of
+/// keys to values (for example when it's a list or a plain string).
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+pub enum FrontmatterFallback {
+    /// Fail with [`ExportError::FrontMatterDecodeError`][crate::ExportError::FrontMatterDecodeError].
+    #[default]
+    Error,
+    /// Print a warning and treat the note as if it has no frontmatter.
+    Ignore,
+    /// Wrap the value under the given key, turning it into a mapping with just that key.
+    WrapInKey(String),
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
@@ -92,4 +356,134 @@ mod tests {
             format!("---\nfoo: bar\n---\n")
         );
     }
+
+    #[test]
+    fn property_types_round_trip() {
+        let frontmatter =
+            frontmatter_from_str("created: 2024-01-02\ncount: 5\ndone: true\nratio: 0.5\n")
+                .unwrap();
+        // YAML has no distinct date type in serde_yaml, so dates are kept as strings which
+        // serialize without quotes, leaving them to be resolved as dates by downstream tools.
+        assert_eq!(
+            frontmatter.get("created"),
+            Some(&Value::String("2024-01-02".into()))
+        );
+        assert_eq!(frontmatter.get("count"), Some(&Value::Number(5_u64.into())));
+        assert_eq!(frontmatter.get("done"), Some(&Value::Bool(true)));
+        assert_eq!(
+            frontmatter_to_str(&frontmatter).unwrap(),
+            "---\ncreated: 2024-01-02\ncount: 5\ndone: true\nratio: 0.5\n---\n"
+        );
+    }
+
+    #[test]
+    fn frontmatter_to_toml() {
+        let frontmatter = frontmatter_from_str(
+            "title: A note\ndraft: false\nweight: 3\ntags: [one, two]\nparams: {ratio: 0.5}",
+        )
+        .unwrap();
+        assert_eq!(
+            frontmatter_to_toml_str(&frontmatter).unwrap(),
+            "+++\ntitle = \"A note\"\ndraft = false\nweight = 3\ntags = [\"one\", \"two\"]\n\n[params]\nratio = 0.5\n+++\n"
+        );
+        assert_eq!(
+            frontmatter_to_toml_str(&Frontmatter::new()).unwrap(),
+            "+++\n+++\n"
+        );
+    }
+
+    #[test]
+    fn frontmatter_to_toml_rejects_null() {
+        let frontmatter = frontmatter_from_str("params:\n  list: [a, ~]").unwrap();
+        let err = frontmatter_to_toml_str(&frontmatter).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "frontmatter value `params.list[1]` is null, which can't be represented in TOML"
+        );
+    }
+
+    #[test]
+    fn split_frontmatter_after_blank_lines_and_comments() {
+        assert_eq!(
+            split_leading_frontmatter("\n\n---\ntitle: Note\n---\n\nBody\n"),
+            Some(("title: Note\n".to_owned(), "\nBody\n".to_owned()))
+        );
+        assert_eq!(
+            split_leading_frontmatter("  ---\n  title: Note\n  ---\nBody\n"),
+            Some(("  title: Note\n".to_owned(), "Body\n".to_owned()))
+        );
+        assert_eq!(
+            split_leading_frontmatter("<!-- a\ncomment -->\n---\ntitle: Note\n...\nBody\n"),
+            Some((
+                "title: Note\n".to_owned(),
+                "<!-- a\ncomment -->\nBody\n".to_owned()
+            ))
+        );
+        // Frontmatter right at the start is left to the Markdown parser.
+        assert_eq!(split_leading_frontmatter("---\ntitle: Note\n---\n"), None);
+        assert_eq!(split_leading_frontmatter("\nText\n---\n"), None);
+        assert_eq!(split_leading_frontmatter("\n---\nNo closing fence\n"), None);
+    }
+
+    #[test]
+    fn merge_frontmatter_precedence() {
+        let base = frontmatter_from_str("a: base\nb: base\nnested: {x: 1, y: 1}").unwrap();
+        let overlay = frontmatter_from_str("b: overlay\nc: overlay\nnested: {y: 2}").unwrap();
+
+        assert_eq!(
+            merge_frontmatter(&base, &overlay, FrontmatterMerge::Shallow),
+            frontmatter_from_str("b: overlay\nc: overlay\nnested: {y: 2}\na: base").unwrap()
+        );
+        assert_eq!(
+            merge_frontmatter(&base, &overlay, FrontmatterMerge::Deep),
+            frontmatter_from_str("b: overlay\nc: overlay\nnested: {y: 2, x: 1}\na: base").unwrap()
+        );
+        // A mapping is never merged into a value of a different type.
+        let list_overlay = frontmatter_from_str("nested: [1, 2]").unwrap();
+        assert_eq!(
+            merge_frontmatter(&base, &list_overlay, FrontmatterMerge::Deep),
+            frontmatter_from_str("nested: [1, 2]\na: base\nb: base").unwrap()
+        );
+    }
+
+    #[test]
+    fn sort_frontmatter_keys() {
+        let frontmatter = frontmatter_from_str(
+            "title: Note\ntags: [b, a]\nauthor: {name: A, email: a@b.c}\nlist:\n- {z: 1, y: 2}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            frontmatter_to_str(&frontmatter).unwrap(),
+            "---\ntitle: Note\ntags:\n- b\n- a\nauthor:\n  name: A\n  email: a@b.c\nlist:\n- z: 1\n  y: 2\n---\n"
+        );
+        assert_eq!(
+            frontmatter_to_str(&sort_frontmatter(&frontmatter)).unwrap(),
+            "---\nauthor:\n  email: a@b.c\n  name: A\nlist:\n- y: 2\n  z: 1\ntags:\n- b\n- a\ntitle: Note\n---\n"
+        );
+    }
+
+    #[test]
+    fn non_mapping_frontmatter_with_fallback() {
+        frontmatter_from_str_with_fallback("- foo", &FrontmatterFallback::Error).unwrap_err();
+
+        assert_eq!(
+            frontmatter_from_str_with_fallback("- foo", &FrontmatterFallback::Ignore).unwrap(),
+            (
+                Frontmatter::new(),
+                Some(Value::Sequence(vec![Value::String("foo".into())]))
+            )
+        );
+
+        let mut expected = Frontmatter::new();
+        expected.insert(Value::String("value".into()), Value::String("foo".into()));
+        assert_eq!(
+            frontmatter_from_str_with_fallback(
+                "foo",
+                &FrontmatterFallback::WrapInKey("value".into())
+            )
+            .unwrap(),
+            (expected, None)
+        );
+    }
 }
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser};
+
+use crate::{find_section, postprocessors, top_level_headings};
+
+/// `NoteAnchors` holds the anchors of the headings of a note, which links to sections of that
+/// note point to.
+#[derive(Debug, Default)]
+pub struct NoteAnchors {
+    // The position, level and text of the headings which delimit sections, as returned by
+    // top_level_headings.
+    headings: Vec<(usize, HeadingLevel, String)>,
+    // The anchor of each heading, keyed by the position of the heading in the note's events.
+    anchors: HashMap<usize, String>,
+}
+
+impl NoteAnchors {
+    /// Parse the headings of the note with the given (raw) content.
+    pub fn parse(content: &str) -> Self {
+        let parser_options = Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+            | Options::ENABLE_TABLES;
+        let events: Vec<Event<'_>> = Parser::new_ext(content, parser_options).collect();
+        Self {
+            headings: top_level_headings(&events),
+            anchors: postprocessors::heading_anchors(&events)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Return the anchor of the heading which starts `section` (a heading name or heading path,
+    /// see [`find_section`]), if the note has such a heading.
+    pub fn section_anchor(&self, section: &str) -> Option<&str> {
+        let (idx, _) = find_section(&self.headings, section)?;
+        self.anchors.get(&idx).map(String::as_str)
+    }
+
+    /// Return whether any heading of the note has the given anchor.
+    pub fn contains(&self, anchor: &str) -> bool {
+        self.anchors.values().any(|existing| existing == anchor)
+    }
+}
+
+/// `AnchorCache` holds the anchors of the notes which were linked to so far, so that each note is
+/// only read and parsed once per export, no matter how many links point to its sections.
+///
+/// Clones share the same cache.
+#[derive(Debug, Clone, Default)]
+pub struct AnchorCache {
+    notes: Arc<Mutex<HashMap<PathBuf, Arc<NoteAnchors>>>>,
+}
+
+impl AnchorCache {
+    /// Return the anchors of `note`, calling `read` to get its content when the note wasn't
+    /// parsed before. A note which can't be read (`read` returns `None`) has no anchors.
+    pub fn get<F>(&self, note: &Path, read: F) -> Arc<NoteAnchors>
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        if let Some(anchors) = self.lock().get(note) {
+            return Arc::clone(anchors);
+        }
+        // The lock isn't held while parsing, so that other notes can be looked up in the meantime.
+        // This may cause a note to be parsed more than once, but the result is the same.
+        let anchors = Arc::new(
+            read().map_or_else(NoteAnchors::default, |content| NoteAnchors::parse(&content)),
+        );
+        Arc::clone(self.lock().entry(note.to_path_buf()).or_insert(anchors))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Arc<NoteAnchors>>> {
+        self.notes
+            .lock()
+            .expect("anchor cache mutex should not be poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_cache_reads_notes_once() {
+        let cache = AnchorCache::default();
+        let note = Path::new("Doc.md");
+        let content = "# Notes\n\n# Chapter\n\n## Notes\n".to_owned();
+
+        let anchors = cache.get(note, || Some(content));
+        assert_eq!(Some("notes-1"), anchors.section_anchor("Chapter#Notes"));
+        let cached = cache.get(note, || panic!("note should only be read once"));
+        assert_eq!(Some("notes"), cached.section_anchor("Notes"));
+        assert_eq!(None, cached.section_anchor("Missing"));
+    }
+}
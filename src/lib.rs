@@ -1,36 +1,155 @@
-pub use {pulldown_cmark, serde_yaml};
+pub use {encoding_rs, ignore, pulldown_cmark, pulldown_cmark_to_cmark, serde_yaml};
 
+// These need to be updated along with the versions of these dependencies in Cargo.toml.
+/// The version of [`pulldown_cmark`] required by obsidian-export.
+pub const PULLDOWN_CMARK_VERSION: &str = "0.12.0";
+/// The version of [`pulldown_cmark_to_cmark`] required by obsidian-export.
+pub const PULLDOWN_CMARK_TO_CMARK_VERSION: &str = "20.0.0";
+/// The version of [`serde_yaml`] required by obsidian-export.
+pub const SERDE_YAML_VERSION: &str = "0.9.34";
+
+mod canvas;
 mod context;
+mod encoding;
 mod frontmatter;
+mod headings;
+mod links;
 pub mod postprocessors;
 mod references;
+mod validate;
 mod walker;
 
-use std::ffi::OsString;
-use std::fs::{self, File};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
 use std::io::ErrorKind;
-use std::path::{Path, PathBuf};
-use std::{fmt, str};
+use std::ops::{Bound, RangeBounds};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{fmt, str, thread};
 
 pub use context::Context;
+pub use encoding::EncodingFallback;
 use filetime::set_file_mtime;
-use frontmatter::{frontmatter_from_str, frontmatter_to_str};
-pub use frontmatter::{Frontmatter, FrontmatterStrategy};
+use frontmatter::{
+    frontmatter_from_str_with_fallback,
+    frontmatter_to_str,
+    frontmatter_to_toml_str,
+    split_leading_frontmatter,
+};
+pub use frontmatter::{
+    merge_frontmatter,
+    sort_frontmatter,
+    Frontmatter,
+    FrontmatterFallback,
+    FrontmatterFormat,
+    FrontmatterMerge,
+    FrontmatterStrategy,
+};
+use headings::{AnchorCache, NoteAnchors};
+pub use links::LinkGraph;
 use pathdiff::diff_paths;
-use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use pulldown_cmark::{CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
 use pulldown_cmark_to_cmark::cmark_with_options;
 use rayon::prelude::*;
-use references::{ObsidianNoteReference, RefParser, RefParserState, RefType};
+use references::SECTION_RANGE_MARKER;
+pub use references::{
+    extract_references,
+    ObsidianNoteReference,
+    RefParser,
+    RefParserState,
+    RefType,
+};
 use slug::slugify;
 use snafu::{ResultExt, Snafu};
 use unicode_normalization::UnicodeNormalization;
+pub use validate::{validate_vault, BrokenLink, BrokenLinkReason};
 pub use walker::{vault_contents, WalkOptions};
 
 /// A series of markdown [Event]s that are generated while traversing an Obsidian markdown note.
 pub type MarkdownEvents<'a> = Vec<Event<'a>>;
 
+type BoxedSink<'s> = Box<dyn FnMut(&Path, &[u8]) -> std::io::Result<()> + Send + 's>;
+
+/// Output describes where exported notes and attachments are written to.
+enum Output<'s> {
+    /// Write files to the export destination on the filesystem.
+    Filesystem,
+    /// Hand files to a sink (see [`Exporter::export_to`]), serializing calls to it.
+    Sink(Mutex<BoxedSink<'s>>),
+}
+
+/// `StartAt` is a path to start the export at, see [`Exporter::start_at_many`].
+#[derive(Debug, Clone)]
+struct StartAt {
+    path: PathBuf,
+    /// Whether `path` is a single file rather than a directory. This is checked once when the
+    /// path is set, as it's needed to find the destination of every exported file.
+    is_file: bool,
+}
+
+impl StartAt {
+    fn new(path: PathBuf) -> Self {
+        let is_file = path.is_file();
+        Self { path, is_file }
+    }
+
+    /// Return the path of `file` relative to this path, or `None` when it isn't under it.
+    fn relative<'p>(&self, file: &'p Path) -> Option<&'p Path> {
+        match self.is_file {
+            true => (file == self.path)
+                .then(|| file.file_name().map(Path::new))
+                .flatten(),
+            false => file.strip_prefix(&self.path).ok(),
+        }
+    }
+}
+
+/// `ExportState` holds the state which is shared between all files during an export.
+#[derive(Default)]
+struct ExportState {
+    /// Links found in the notes which were exported so far.
+    links: Mutex<LinkGraph>,
+    /// The destinations written to so far, mapped to the files which were written to them.
+    destinations: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// The number of files which were processed so far.
+    processed: AtomicUsize,
+    /// The total number of files which are going to be processed.
+    total: usize,
+}
+
+impl ExportState {
+    /// Record that `src` is going to be written to `dest`, failing when a different file was
+    /// written to `dest` already.
+    fn claim_destination(&self, src: &Path, dest: &Path) -> Result<()> {
+        let existing = self
+            .destinations
+            .lock()
+            .expect("destinations mutex should not be poisoned")
+            .entry(dest.to_path_buf())
+            .or_insert_with(|| src.to_path_buf())
+            .clone();
+        if existing == src {
+            return Ok(());
+        }
+        // Sort both sources so that the error doesn't depend on the order in which files happened
+        // to be exported.
+        let (a, b) = match existing.as_path() < src {
+            true => (existing, src.to_path_buf()),
+            false => (src.to_path_buf(), existing),
+        };
+        Err(ExportError::DestinationCollision {
+            path: dest.to_path_buf(),
+            a,
+            b,
+        })
+    }
+}
+
 /// A post-processing function that is to be called after an Obsidian note has been fully parsed and
 /// converted to regular markdown syntax.
 ///
@@ -63,6 +182,8 @@ pub type MarkdownEvents<'a> = Vec<Event<'a>>;
 /// It's possible to pass the same functions to [`Exporter::add_postprocessor`] and
 /// [`Exporter::add_embed_postprocessor`]. The [`Context::note_depth`] method may be used to
 /// determine whether a note is a root note or an embedded note in this situation.
+/// Alternatively, [`Exporter::add_postprocessor_for_depth`] registers a function which only runs
+/// on notes at a given range of depths, whether they're root notes or embedded notes.
 ///
 /// # Examples
 ///
@@ -131,11 +252,194 @@ pub type MarkdownEvents<'a> = Vec<Event<'a>>;
 /// ```
 pub type Postprocessor<'f> =
     dyn Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult + Send + Sync + 'f;
+
+/// A post-processing function that is called with the rendered text of an exported note, just
+/// before it's written.
+///
+/// Text postprocessors are called in the order they've been added through
+/// [`Exporter::add_text_postprocessor`], after all [postprocessors][Postprocessor] have run and the
+/// note has been rendered back to markdown (including its frontmatter). Like postprocessors, they
+/// return a [`PostprocessorResult`] to stop processing or skip the note. They're useful for
+/// transformations which are easier to do on the final text than on [`MarkdownEvents`].
+///
+/// Text postprocessors don't run on embedded notes, nor on notes whose output was replaced through
+/// [`Context::set_output_bytes`].
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::{Context, Exporter, PostprocessorResult};
+/// # use std::path::PathBuf;
+/// # use tempfile::TempDir;
+/// #
+/// # let tmp_dir = TempDir::new().expect("failed to make tempdir");
+/// # let source = PathBuf::from("tests/testdata/input/postprocessors");
+/// # let destination = tmp_dir.path().to_path_buf();
+/// let mut exporter = Exporter::new(source, destination);
+///
+/// // Collapse runs of blank lines into a single blank line.
+/// exporter.add_text_postprocessor(&|_context: &mut Context, text: &mut String| {
+///     while text.contains("\n\n\n") {
+///         *text = text.replace("\n\n\n", "\n\n");
+///     }
+///     PostprocessorResult::Continue
+/// });
+/// # exporter.run().unwrap();
+/// ```
+pub type TextPostprocessor<'f> =
+    dyn Fn(&mut Context, &mut String) -> PostprocessorResult + Send + Sync + 'f;
+
+/// A pre-processing function that is called with the raw text of an Obsidian note before it's
+/// parsed.
+///
+/// Preprocessors are called in the order they've been added through [`Exporter::add_preprocessor`]
+/// (for root notes) or [`Exporter::add_embed_preprocessor`] (for embedded notes), with the
+/// [Context] of the note being parsed. They may modify the note's text in-place, which is useful
+/// for transformations which are easier to do on text than on [`MarkdownEvents`], such as
+/// removing custom markup. The text includes the note's frontmatter.
+///
+/// Changes made by preprocessors aren't reflected in [`Context::source_content`], which always
+/// holds the note's text as it was read from disk.
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::Exporter;
+/// # use std::path::PathBuf;
+/// # use tempfile::TempDir;
+/// #
+/// # let tmp_dir = TempDir::new().expect("failed to make tempdir");
+/// # let source = PathBuf::from("tests/testdata/input/postprocessors");
+/// # let destination = tmp_dir.path().to_path_buf();
+/// let mut exporter = Exporter::new(source, destination);
+///
+/// // Remove a custom marker from embedded notes only.
+/// exporter.add_embed_preprocessor(&|_context, text| {
+///     *text = text.replace("<!-- embedded -->", "");
+/// });
+/// # exporter.run().unwrap();
+/// ```
+pub type Preprocessor<'f> = dyn Fn(&Context, &mut String) + Send + Sync + 'f;
+
+/// A function which may rewrite the URLs of links to external resources (such as web pages).
+///
+/// It's called with the destination URL of each link which has a URL scheme (such as `https:` or
+/// `mailto:`) and returns the URL to use instead, or `None` to leave the link unchanged. See
+/// [`Exporter::external_link_handler`].
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::Exporter;
+/// # use std::path::PathBuf;
+/// # let mut exporter = Exporter::new(PathBuf::from("source"), PathBuf::from("destination"));
+/// // Upgrade plain HTTP links to HTTPS.
+/// exporter.external_link_handler(Some(&|url: &str| {
+///     url.strip_prefix("http://")
+///         .map(|rest| format!("https://{rest}"))
+/// }));
+/// ```
+pub type ExternalLinkHandler<'f> = dyn Fn(&str) -> Option<String> + Send + Sync + 'f;
+
+/// A function which may rewrite the language and content of fenced code blocks.
+///
+/// It's called with the info string of each fenced code block (usually its language, which is
+/// empty when none is given) and the code it contains, and returns the info string and code to use
+/// instead. The code of non-empty code blocks includes a trailing newline. See
+/// [`Exporter::code_block_fn`].
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::Exporter;
+/// # use std::path::PathBuf;
+/// # let mut exporter = Exporter::new(PathBuf::from("source"), PathBuf::from("destination"));
+/// // Use the full name of the JavaScript language.
+/// exporter.code_block_fn(Some(&|lang: &str, code: &str| {
+///     let lang = if lang == "js" { "javascript" } else { lang };
+///     (lang.to_owned(), code.to_owned())
+/// }));
+/// ```
+pub type CodeBlockHandler<'f> = dyn Fn(&str, &str) -> (String, String) + Send + Sync + 'f;
+
+/// A function which is called for links and embeds referring to attachments which don't exist.
+///
+/// It's called with the referenced file (as written in the reference) and the [`Context`] of the
+/// note containing the reference, instead of printing a warning. See
+/// [`Exporter::missing_attachment_handler`].
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::{Context, Exporter};
+/// # use std::path::PathBuf;
+/// # let mut exporter = Exporter::new(PathBuf::from("source"), PathBuf::from("destination"));
+/// exporter.missing_attachment_handler(Some(&|file: &str, context: &Context| {
+///     eprintln!("{} is missing {file}", context.current_file().display());
+/// }));
+/// ```
+pub type MissingAttachmentHandler<'f> = dyn Fn(&str, &Context) + Send + Sync + 'f;
+
+/// A function which is called after each file has been written to the destination.
+///
+/// It's called with the path of the source file in the vault and the path of the file it was
+/// written to. As files are exported in parallel, it may be called from multiple threads at once.
+/// See [`Exporter::on_note_written`].
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::Exporter;
+/// # use std::path::{Path, PathBuf};
+/// # let mut exporter = Exporter::new(PathBuf::from("source"), PathBuf::from("destination"));
+/// exporter.on_note_written(Some(&|source: &Path, destination: &Path| {
+///     println!("{} -> {}", source.display(), destination.display());
+/// }));
+/// ```
+pub type NoteWrittenHook<'f> = dyn Fn(&Path, &Path) + Send + Sync + 'f;
+
+/// A function which is called after each file in the vault has been processed, to report the
+/// progress of an export.
+///
+/// It's called with the number of files which have been processed so far, the total number of
+/// files which are going to be processed and the path of the file which was just processed. As
+/// files are exported in parallel, it may be called from multiple threads at once, and calls may
+/// arrive slightly out of order. See [`Exporter::on_progress`].
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::Exporter;
+/// # use std::path::{Path, PathBuf};
+/// # let mut exporter = Exporter::new(PathBuf::from("source"), PathBuf::from("destination"));
+/// exporter.on_progress(Some(&|done: usize, total: usize, file: &Path| {
+///     println!("[{done}/{total}] {}", file.display());
+/// }));
+/// ```
+pub type ProgressHandler<'f> = dyn Fn(usize, usize, &Path) + Send + Sync + 'f;
 type Result<T, E = ExportError> = std::result::Result<T, E>;
 
-const PERCENTENCODE_CHARS: &AsciiSet = &CONTROLS.add(b' ').add(b'(').add(b')').add(b'%').add(b'?');
+// Characters which are percent-encoded in the path of links to files. `#` is included so that
+// filenames containing it aren't mistaken for a section fragment, which is only appended (without
+// encoding its `#` separator) after the path has been encoded.
+pub(crate) const PERCENTENCODE_CHARS: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'(')
+    .add(b')')
+    .add(b'%')
+    .add(b'?')
+    .add(b'#');
 const NOTE_RECURSION_LIMIT: usize = 10;
 
+// Extensions of the file formats Obsidian supports as attachments. Embeds of images become image
+// tags, and embeds of audio and video become media elements. Together, these also tell references
+// to missing attachments apart from references to missing notes whose names contain a dot, like
+// `[[Release 1.2]]`.
+const IMAGE_EXTENSIONS: &[&str] = &["avif", "bmp", "gif", "jpeg", "jpg", "png", "svg", "webp"];
+const AUDIO_EXTENSIONS: &[&str] = &["3gp", "flac", "m4a", "mp3", "ogg", "wav"];
+const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mov", "mp4", "ogv", "webm"];
+const PDF_EXTENSIONS: &[&str] = &["pdf"];
+
 #[non_exhaustive]
 #[derive(Debug, Snafu)]
 /// `ExportError` represents all errors which may be returned when using this crate.
@@ -161,6 +465,18 @@ pub enum ExportError {
         source: ignore::Error,
     },
 
+    #[snafu(display("Encountered a symlink loop at '{}'", path.display()))]
+    /// This occurs when following symlinks while walking a directory leads to a directory which
+    /// is also one of its own ancestors (see [`WalkOptions::follow_symlinks`]).
+    SymlinkLoop { path: PathBuf },
+
+    #[snafu(display("Failed to read ignore rules from '{}'", path.display()))]
+    /// This occurs when an ignore file cannot be read or contains invalid patterns.
+    IgnoreFileError {
+        path: PathBuf,
+        source: ignore::Error,
+    },
+
     #[snafu(display("Failed to read the mtime of '{}'", path.display()))]
     /// This occurs when a file's modified time cannot be read
     ModTimeReadError {
@@ -179,11 +495,66 @@ pub enum ExportError {
     /// This occurs when an operation is requested on a file or directory which does not exist.
     PathDoesNotExist { path: PathBuf },
 
-    #[snafu(display("Invalid character encoding encountered"))]
-    /// This error may occur when invalid UTF8 is encountered.
-    ///
-    /// Currently, operations which assume UTF8 perform lossy encoding however.
-    CharacterEncodingError { source: str::Utf8Error },
+    #[snafu(display("Invalid character encoding encountered in '{}'", path.display()))]
+    /// This error occurs when a note contains invalid UTF8 and [`EncodingFallback::Error`] is in
+    /// effect (see [`Exporter::encoding_fallback`]).
+    CharacterEncodingError {
+        path: PathBuf,
+        source: str::Utf8Error,
+    },
+
+    #[snafu(display("Invalid UTF-16 encountered in '{}'", path.display()))]
+    /// This error occurs when a note which starts with a UTF-16 byte order mark contains invalid
+    /// UTF-16 and [`EncodingFallback::Error`] is in effect (see [`Exporter::encoding_fallback`]).
+    Utf16EncodingError { path: PathBuf },
+
+    #[snafu(display(
+        "Both '{}' and '{}' would be exported to '{}'",
+        a.display(),
+        b.display(),
+        path.display()
+    ))]
+    /// This occurs when two files end up with the same destination, for example because a
+    /// [postprocessor][Postprocessor] changed the [`Context::destination`] of a note.
+    DestinationCollision {
+        path: PathBuf,
+        a: PathBuf,
+        b: PathBuf,
+    },
+
+    #[snafu(display("Destination '{}' already exists", path.display()))]
+    /// This occurs when a file would be written to a destination which already exists while
+    /// [`OverwritePolicy::Error`] is in effect (see [`Exporter::overwrite_policy`]).
+    DestinationExists { path: PathBuf },
+
+    #[snafu(display(
+        "Refusing to clean '{}' as it overlaps with the vault at '{}'",
+        destination.display(),
+        root.display()
+    ))]
+    /// This occurs when [`Exporter::clean_destination`] is enabled while the vault is inside the
+    /// destination, the destination is inside the vault, or both are the same directory.
+    /// Cleaning the destination would remove files from the vault in that case.
+    CleanOverlapsVault { root: PathBuf, destination: PathBuf },
+
+    #[snafu(display("Found {} link(s) to sections which don't exist", links.len()))]
+    /// This occurs when [`Exporter::strict_section_links`] is enabled and notes link to sections
+    /// of other notes which don't exist. All such links are collected in `links`, sorted by the
+    /// note containing them, and returned once all notes have been exported.
+    BrokenSectionLinks { links: Vec<BrokenLink> },
+
+    #[snafu(display("'{}' isn't a file in the vault which is being exported", path.display()))]
+    /// This occurs when a note passed to [`Exporter::reachable_from`] doesn't exist, or is
+    /// excluded from the export by an ignore rule.
+    ReachableFromNotInVault { path: PathBuf },
+
+    #[snafu(display("Failed to decode canvas '{}'", path.display()))]
+    /// This occurs when a canvas file can't be decoded while
+    /// [rendering canvas files][Exporter::render_canvas].
+    CanvasDecodeError {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
 
     #[snafu(display("Recursion limit exceeded"))]
     /// This error occurs when embedded notes are too deeply nested or cause an infinite loop.
@@ -213,6 +584,12 @@ pub enum ExportError {
         #[snafu(source(from(serde_yaml::Error, Box::new)))]
         source: Box<serde_yaml::Error>,
     },
+
+    #[snafu(display("Failed to encode TOML frontmatter for '{}'", path.display()))]
+    FrontMatterTomlEncodeError {
+        path: PathBuf,
+        source: toml::ser::Error,
+    },
 }
 
 /// Emitted by [Postprocessor]s to signal the next action to take.
@@ -227,6 +604,157 @@ pub enum PostprocessorResult {
     StopAndSkipNote,
 }
 
+/// Available styles for embeds which refer to a note (or file) which can't be found, see
+/// [`Exporter::missing_embed_style`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MissingEmbedStyle {
+    /// Remove the embed entirely.
+    #[default]
+    Remove,
+    /// Keep the embed as-is, in its original `![[reference]]` form.
+    Literal,
+    /// Replace the embed with its reference text in italics, the same way as links to missing
+    /// notes.
+    Emphasis,
+}
+
+/// Available behaviors for the frontmatter of notes which are embedded into other notes, see
+/// [`Exporter::embed_frontmatter_behavior`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmbedFrontmatterBehavior {
+    /// Drop the frontmatter of embedded notes entirely.
+    #[default]
+    Drop,
+    /// Insert a heading above the embedded content, using the `title` property of the embedded
+    /// note or its filename when it has none.
+    ///
+    /// The heading is placed one level below the heading the embed falls under in the embedding
+    /// note, or at level 1 when there is no such heading.
+    TitleHeading,
+    /// Wrap the embedded content in a `[!note]` callout, titled the same way as
+    /// [`EmbedFrontmatterBehavior::TitleHeading`].
+    Callout,
+}
+
+/// Available styles for listing the notes which link to a note, see
+/// [`Exporter::generate_backlinks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BacklinkStyle {
+    /// Store links to the notes which link to a note as a list under the given frontmatter key.
+    FrontmatterKey(String),
+    /// Append a section with the given heading to the end of a note, listing links to the notes
+    /// which link to it.
+    AppendedSection(String),
+}
+
+/// Available ways to write links found in frontmatter properties, see
+/// [`Exporter::frontmatter_links`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FrontmatterLinkStyle {
+    /// Replace wikilinks with a markdown link, such as `[Note](Note.md)`.
+    MarkdownLink,
+    /// Replace wikilinks with just the path of the linked file, such as `Note.md`.
+    Path,
+}
+
+/// Available line endings for exported notes, see [`Exporter::line_ending`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LineEnding {
+    /// Unix-style line endings (`\n`).
+    #[default]
+    Lf,
+    /// Windows-style line endings (`\r\n`).
+    CrLf,
+}
+
+/// Available policies for files which already exist in the destination, see
+/// [`Exporter::overwrite_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverwritePolicy {
+    /// Replace existing files.
+    #[default]
+    Overwrite,
+    /// Leave existing files untouched, without writing the exported file.
+    Skip,
+    /// Abort the export with [`ExportError::DestinationExists`].
+    Error,
+}
+
+/// `IndexConfig` describes the index note written by [`Exporter::emit_index`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct IndexConfig {
+    /// The path of the index note, relative to the destination.
+    ///
+    /// By default `index.md` is used.
+    pub path: PathBuf,
+    /// The text of the heading at the top of the index note.
+    ///
+    /// By default `Index` is used.
+    pub title: String,
+    /// Whether to write an empty `.nojekyll` file to the root of the destination as well, which
+    /// stops GitHub Pages from processing the exported files with Jekyll.
+    ///
+    /// This is disabled by default.
+    pub nojekyll: bool,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("index.md"),
+            title: "Index".to_owned(),
+            nojekyll: false,
+        }
+    }
+}
+
+/// A note exported by [`Exporter::iter_notes`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ExportedNote {
+    /// The path of the note within the vault.
+    pub source: PathBuf,
+    /// The path the note would be written to by [`Exporter::run`].
+    pub destination: PathBuf,
+    /// The frontmatter of the note, after postprocessors have run.
+    pub frontmatter: Frontmatter,
+    /// The full contents of the exported note, including its frontmatter.
+    pub contents: Vec<u8>,
+}
+
+// The range of note depths a postprocessor added through Exporter::add_postprocessor_for_depth
+// applies to.
+type DepthRange = (Bound<usize>, Bound<usize>);
+
+/// A postprocessor which is either borrowed from the caller or owned by the exporter.
+#[derive(Clone)]
+enum PostprocessorRef<'a> {
+    /// Added through [`Exporter::add_postprocessor`].
+    Borrowed(&'a Postprocessor<'a>),
+    /// Added through [`Exporter::add_postprocessor_arc`]. These are `'static` so that dropping the
+    /// exporter can't involve data borrowed for `'a`, which would otherwise require everything
+    /// borrowed by the exporter to strictly outlive it.
+    Shared(Arc<Postprocessor<'static>>),
+}
+
+impl<'a> std::ops::Deref for PostprocessorRef<'a> {
+    type Target = Postprocessor<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Borrowed(processor) => processor,
+            Self::Shared(processor) => processor.as_ref(),
+        }
+    }
+}
+
 #[derive(Clone)]
 /// Exporter provides the main interface to this library.
 ///
@@ -234,32 +762,183 @@ pub enum PostprocessorResult {
 /// customization using [`Exporter::frontmatter_strategy`] and [`Exporter::walk_options`].
 ///
 /// After that, calling [`Exporter::run`] will start the export process.
+#[allow(clippy::struct_excessive_bools)]
 pub struct Exporter<'a> {
     root: PathBuf,
     destination: PathBuf,
-    start_at: PathBuf,
+    start_at: Vec<StartAt>,
+    reachable_from: Vec<PathBuf>,
+    resolve_siblings: bool,
     frontmatter_strategy: FrontmatterStrategy,
+    frontmatter_format: FrontmatterFormat,
+    sort_frontmatter_keys: bool,
+    default_frontmatter: Frontmatter,
+    default_frontmatter_merge: FrontmatterMerge,
+    source_path_key: Option<String>,
+    honor_frontmatter_directives: bool,
+    frontmatter_fallback: FrontmatterFallback,
+    lenient_frontmatter: bool,
+    encoding_fallback: EncodingFallback,
     vault_contents: Option<Vec<PathBuf>>,
     walk_options: WalkOptions<'a>,
     process_embeds_recursively: bool,
+    deduplicate_embeds: bool,
     preserve_mtime: bool,
-    postprocessors: Vec<&'a Postprocessor<'a>>,
+    clean_destination: bool,
+    overwrite_policy: OverwritePolicy,
+    cmark_options: pulldown_cmark_to_cmark::Options<'a>,
+    link_base: Option<String>,
+    flatten_separator: Option<String>,
+    lowercase_output: bool,
+    folder_notes: bool,
+    folder_note_index: String,
+    daily_note_folder: Option<PathBuf>,
+    preserve_wikilinks: bool,
+    resolve_preserved_wikilinks: bool,
+    normalize_line_endings: bool,
+    line_ending: LineEnding,
+    trailing_newline: bool,
+    markdown_extensions: Vec<String>,
+    io_retries: usize,
+    demote_embedded_headings: bool,
+    embed_cycle_prefix: Option<String>,
+    max_embed_bytes: Option<usize>,
+    max_embeds_per_note: Option<usize>,
+    skip_attachment_extensions: Vec<String>,
+    external_attachments_dir: Option<PathBuf>,
+    render_canvas: bool,
+    missing_embed_style: MissingEmbedStyle,
+    embed_frontmatter_behavior: EmbedFrontmatterBehavior,
+    collect_link_graph: bool,
+    backlinks_index: Option<PathBuf>,
+    backlink_style: Option<BacklinkStyle>,
+    frontmatter_link_style: Option<FrontmatterLinkStyle>,
+    note_backlinks: LinkGraph,
+    validate_section_links: bool,
+    strict_section_links: bool,
+    anchor_cache: AnchorCache,
+    broken_section_links: Arc<Mutex<Vec<BrokenLink>>>,
+    index: Option<IndexConfig>,
+    link_graph: Option<LinkGraph>,
+    external_link_handler: Option<&'a ExternalLinkHandler<'a>>,
+    code_block_fn: Option<&'a CodeBlockHandler<'a>>,
+    missing_attachment_handler: Option<&'a MissingAttachmentHandler<'a>>,
+    on_note_written: Option<&'a NoteWrittenHook<'a>>,
+    on_progress: Option<&'a ProgressHandler<'a>>,
+    preprocessors: Vec<&'a Preprocessor<'a>>,
+    embed_preprocessors: Vec<&'a Preprocessor<'a>>,
+    postprocessors: Vec<PostprocessorRef<'a>>,
     embed_postprocessors: Vec<&'a Postprocessor<'a>>,
+    depth_postprocessors: Vec<(DepthRange, &'a Postprocessor<'a>)>,
+    text_postprocessors: Vec<&'a TextPostprocessor<'a>>,
 }
 
 impl<'a> fmt::Debug for Exporter<'a> {
+    #[allow(clippy::too_many_lines)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("WalkOptions")
             .field("root", &self.root)
             .field("destination", &self.destination)
+            .field("reachable_from", &self.reachable_from)
+            .field("resolve_siblings", &self.resolve_siblings)
             .field("frontmatter_strategy", &self.frontmatter_strategy)
+            .field("frontmatter_format", &self.frontmatter_format)
+            .field("sort_frontmatter_keys", &self.sort_frontmatter_keys)
+            .field("default_frontmatter", &self.default_frontmatter)
+            .field("default_frontmatter_merge", &self.default_frontmatter_merge)
+            .field("source_path_key", &self.source_path_key)
+            .field(
+                "honor_frontmatter_directives",
+                &self.honor_frontmatter_directives,
+            )
+            .field("frontmatter_fallback", &self.frontmatter_fallback)
+            .field("lenient_frontmatter", &self.lenient_frontmatter)
+            .field("encoding_fallback", &self.encoding_fallback)
             .field("vault_contents", &self.vault_contents)
             .field("walk_options", &self.walk_options)
             .field(
                 "process_embeds_recursively",
                 &self.process_embeds_recursively,
             )
+            .field("deduplicate_embeds", &self.deduplicate_embeds)
             .field("preserve_mtime", &self.preserve_mtime)
+            .field("clean_destination", &self.clean_destination)
+            .field("overwrite_policy", &self.overwrite_policy)
+            .field("cmark_options", &self.cmark_options)
+            .field("link_base", &self.link_base)
+            .field("flatten_separator", &self.flatten_separator)
+            .field("lowercase_output", &self.lowercase_output)
+            .field("folder_notes", &self.folder_notes)
+            .field("folder_note_index", &self.folder_note_index)
+            .field("daily_note_folder", &self.daily_note_folder)
+            .field("preserve_wikilinks", &self.preserve_wikilinks)
+            .field(
+                "resolve_preserved_wikilinks",
+                &self.resolve_preserved_wikilinks,
+            )
+            .field("normalize_line_endings", &self.normalize_line_endings)
+            .field("line_ending", &self.line_ending)
+            .field("trailing_newline", &self.trailing_newline)
+            .field("markdown_extensions", &self.markdown_extensions)
+            .field("io_retries", &self.io_retries)
+            .field("demote_embedded_headings", &self.demote_embedded_headings)
+            .field("embed_cycle_prefix", &self.embed_cycle_prefix)
+            .field("max_embed_bytes", &self.max_embed_bytes)
+            .field("max_embeds_per_note", &self.max_embeds_per_note)
+            .field(
+                "skip_attachment_extensions",
+                &self.skip_attachment_extensions,
+            )
+            .field("external_attachments_dir", &self.external_attachments_dir)
+            .field("render_canvas", &self.render_canvas)
+            .field("missing_embed_style", &self.missing_embed_style)
+            .field(
+                "embed_frontmatter_behavior",
+                &self.embed_frontmatter_behavior,
+            )
+            .field("collect_link_graph", &self.collect_link_graph)
+            .field("backlinks_index", &self.backlinks_index)
+            .field("backlink_style", &self.backlink_style)
+            .field("frontmatter_link_style", &self.frontmatter_link_style)
+            .field("note_backlinks", &self.note_backlinks)
+            .field("validate_section_links", &self.validate_section_links)
+            .field("strict_section_links", &self.strict_section_links)
+            .field("anchor_cache", &self.anchor_cache)
+            .field("broken_section_links", &self.broken_section_links)
+            .field("index", &self.index)
+            .field("link_graph", &self.link_graph)
+            .field(
+                "external_link_handler",
+                &self
+                    .external_link_handler
+                    .map(|_| "<external link handler>"),
+            )
+            .field(
+                "code_block_fn",
+                &self.code_block_fn.map(|_| "<code block handler>"),
+            )
+            .field(
+                "missing_attachment_handler",
+                &self
+                    .missing_attachment_handler
+                    .map(|_| "<missing attachment handler>"),
+            )
+            .field(
+                "on_note_written",
+                &self.on_note_written.map(|_| "<note written hook>"),
+            )
+            .field(
+                "on_progress",
+                &self.on_progress.map(|_| "<progress handler>"),
+            )
+            .field(
+                "preprocessors",
+                &format!("<{} preprocessors active>", self.preprocessors.len()),
+            )
+            .field(
+                "embed_preprocessors",
+                &format!("<{} preprocessors active>", self.embed_preprocessors.len()),
+            )
             .field(
                 "postprocessors",
                 &format!("<{} postprocessors active>", self.postprocessors.len()),
@@ -271,6 +950,17 @@ impl<'a> fmt::Debug for Exporter<'a> {
                     self.embed_postprocessors.len()
                 ),
             )
+            .field(
+                "depth_postprocessors",
+                &format!(
+                    "<{} postprocessors active>",
+                    self.depth_postprocessors.len()
+                ),
+            )
+            .field(
+                "text_postprocessors",
+                &format!("<{} postprocessors active>", self.text_postprocessors.len()),
+            )
             .finish()
     }
 }
@@ -278,19 +968,79 @@ impl<'a> fmt::Debug for Exporter<'a> {
 impl<'a> Exporter<'a> {
     /// Create a new exporter which reads notes from `root` and exports these to
     /// `destination`.
+    ///
+    /// When `root` is a single file, only that file is exported, and it's considered to be the
+    /// only file in the vault (see [`Exporter::resolve_siblings`]).
     #[must_use]
     pub fn new(root: PathBuf, destination: PathBuf) -> Self {
         Self {
-            start_at: root.clone(),
+            start_at: vec![StartAt::new(root.clone())],
+            reachable_from: vec![],
+            resolve_siblings: false,
             root,
             destination,
             frontmatter_strategy: FrontmatterStrategy::Auto,
+            frontmatter_format: FrontmatterFormat::default(),
+            sort_frontmatter_keys: false,
+            default_frontmatter: Frontmatter::new(),
+            default_frontmatter_merge: FrontmatterMerge::default(),
+            source_path_key: None,
+            honor_frontmatter_directives: false,
+            frontmatter_fallback: FrontmatterFallback::default(),
+            lenient_frontmatter: false,
+            encoding_fallback: EncodingFallback::default(),
             walk_options: WalkOptions::default(),
             process_embeds_recursively: true,
+            deduplicate_embeds: false,
             preserve_mtime: false,
+            clean_destination: false,
+            overwrite_policy: OverwritePolicy::default(),
+            cmark_options: pulldown_cmark_to_cmark::Options::default(),
+            link_base: None,
+            flatten_separator: None,
+            lowercase_output: false,
+            folder_notes: false,
+            folder_note_index: "index.md".to_owned(),
+            daily_note_folder: None,
+            preserve_wikilinks: false,
+            resolve_preserved_wikilinks: false,
+            normalize_line_endings: false,
+            line_ending: LineEnding::default(),
+            trailing_newline: true,
+            markdown_extensions: vec!["md".to_owned()],
+            io_retries: 0,
+            demote_embedded_headings: false,
+            embed_cycle_prefix: Some("→ ".to_owned()),
+            max_embed_bytes: None,
+            max_embeds_per_note: None,
+            skip_attachment_extensions: vec![],
+            external_attachments_dir: None,
+            render_canvas: false,
+            missing_embed_style: MissingEmbedStyle::default(),
+            embed_frontmatter_behavior: EmbedFrontmatterBehavior::default(),
+            collect_link_graph: false,
+            backlinks_index: None,
+            backlink_style: None,
+            frontmatter_link_style: None,
+            note_backlinks: LinkGraph::new(),
+            validate_section_links: false,
+            strict_section_links: false,
+            anchor_cache: AnchorCache::default(),
+            broken_section_links: Arc::default(),
+            index: None,
+            link_graph: None,
             vault_contents: None,
+            external_link_handler: None,
+            code_block_fn: None,
+            missing_attachment_handler: None,
+            on_note_written: None,
+            on_progress: None,
+            preprocessors: vec![],
+            embed_preprocessors: vec![],
             postprocessors: vec![],
             embed_postprocessors: vec![],
+            depth_postprocessors: vec![],
+            text_postprocessors: vec![],
         }
     }
 
@@ -299,11 +1049,79 @@ impl<'a> Exporter<'a> {
     /// Normally all notes under `root` (except for notes excluded by ignore rules) will be
     /// exported. When `start_at` is set, only notes under this path will be exported to the
     /// target destination.
+    ///
+    /// This replaces any paths set through [`Exporter::start_at_many`].
     pub fn start_at(&mut self, start_at: PathBuf) -> &mut Self {
-        self.start_at = start_at;
+        self.start_at = vec![StartAt::new(start_at)];
+        self
+    }
+
+    /// Set multiple starting points for the export.
+    ///
+    /// This works like [`Exporter::start_at`], except that notes under any of the given paths are
+    /// exported. Each file is exported relative to the path it's found under, so exporting
+    /// `vault/projects` and `vault/reference` results in the contents of both directories being
+    /// merged into the destination. Links between notes are adjusted to match. When paths overlap
+    /// (like `vault/projects` and `vault/projects/active`), files are exported relative to the
+    /// most specific path they're found under.
+    ///
+    /// When `paths` is empty, the whole vault is exported.
+    pub fn start_at_many(&mut self, paths: Vec<PathBuf>) -> &mut Self {
+        self.start_at = match paths.is_empty() {
+            true => vec![StartAt::new(self.root.clone())],
+            false => paths.into_iter().map(StartAt::new).collect(),
+        };
         self
     }
 
+    /// Only export the given notes and the files they reference, directly or indirectly.
+    ///
+    /// Starting from `notes`, all links and embeds (both wikilinks and Markdown links) are
+    /// followed to find the notes and attachments which can be reached from them. Everything else
+    /// is left out of the export, as if it was excluded by an ignore rule. Paths must include
+    /// `root`, like with [`Exporter::start_at`]. This is combined with [`Exporter::start_at`],
+    /// so only reachable files under the `start_at` paths are exported.
+    ///
+    /// Since notes need to be read an additional time to find the references between them, this
+    /// slows down the export of large vaults. When `notes` is empty, all files are exported. When
+    /// any of `notes` isn't part of the vault, the export fails with
+    /// [`ExportError::ReachableFromNotInVault`].
+    pub fn reachable_from(&mut self, notes: Vec<PathBuf>) -> &mut Self {
+        self.reachable_from = notes;
+        self
+    }
+
+    /// Set whether the files next to `root` are part of the vault when `root` is a single file.
+    ///
+    /// By default, a single file is exported as if it were the only file in the vault, so links
+    /// to other notes become plain text and embeds of other files are removed. When `resolve` is
+    /// true, the files in the same directory as `root` (but not in its subdirectories) are
+    /// considered part of the vault as well. Links to them point next to the exported note and
+    /// notes are embedded, but the linked files themselves aren't exported, so they need to be
+    /// present at the destination for these links to work.
+    pub fn resolve_siblings(&mut self, resolve: bool) -> &mut Self {
+        self.resolve_siblings = resolve;
+        self
+    }
+
+    // Return the start_at path when exporting just a single file.
+    fn single_start_at_file(&self) -> Option<&Path> {
+        match self.start_at.as_slice() {
+            [start_at] if self.root.is_file() || start_at.is_file => Some(&start_at.path),
+            _ => None,
+        }
+    }
+
+    // Return the path of `file` relative to the start_at path it's found under, or `None` when it
+    // isn't under any of them. When start_at paths overlap (like `a` and `a/b`), the most specific
+    // one is used, regardless of the order they were given in.
+    fn relative_to_start_at<'p>(&self, file: &'p Path) -> Option<&'p Path> {
+        self.start_at
+            .iter()
+            .filter_map(|start_at| start_at.relative(file))
+            .min_by_key(|relative| relative.components().count())
+    }
+
     /// Set the [`WalkOptions`] to be used for this exporter.
     pub fn walk_options(&mut self, options: WalkOptions<'a>) -> &mut Self {
         self.walk_options = options;
@@ -316,66 +1134,1426 @@ impl<'a> Exporter<'a> {
         self
     }
 
-    /// Set the behavior when recursive embeds are encountered.
+    /// Set the [`FrontmatterFormat`] in which frontmatter is written to exported notes.
     ///
-    /// When `recursive` is true (the default), emdeds are always processed recursively. This may
-    /// lead to infinite recursion when note A embeds B, but B also embeds A.
-    /// (When this happens, [`ExportError::RecursionLimitExceeded`] will be returned by
-    /// [`Exporter::run`]).
+    /// Frontmatter is always read as YAML, regardless of this setting.
+    pub fn frontmatter_format(&mut self, format: FrontmatterFormat) -> &mut Self {
+        self.frontmatter_format = format;
+        self
+    }
+
+    /// Set whether the keys of frontmatter should be sorted alphabetically in exported notes.
     ///
-    /// When `recursive` is false, if a note is encountered for a second time while processing the
-    /// original note, instead of embedding it again a link to the note is inserted instead.
-    pub fn process_embeds_recursively(&mut self, recursive: bool) -> &mut Self {
-        self.process_embeds_recursively = recursive;
+    /// Frontmatter is always re-serialized when notes are exported, which normalizes quoting and
+    /// indentation. By default, keys keep the order in which they appear in the note. When `sort`
+    /// is true, keys (including those of nested mappings) are sorted instead, so that the same
+    /// frontmatter is written identically across the whole vault. See [`sort_frontmatter`].
+    ///
+    /// Keys are sorted after all [postprocessors][Postprocessor] have run.
+    pub fn sort_frontmatter_keys(&mut self, sort: bool) -> &mut Self {
+        self.sort_frontmatter_keys = sort;
         self
     }
 
-    /// Set whether the modified time of exported files should be preserved.
+    /// Set default [`Frontmatter`] to add to every exported note.
     ///
-    /// When `preserve` is true, the modified time of exported files will be set to the modified
-    /// time of the source file.
-    pub fn preserve_mtime(&mut self, preserve: bool) -> &mut Self {
-        self.preserve_mtime = preserve;
+    /// Top-level keys from `defaults` are added to the frontmatter of each note which doesn't
+    /// define these keys itself. Values from a note's own frontmatter always take precedence.
+    ///
+    /// Defaults are applied before any [postprocessors][Postprocessor] run. Use
+    /// [`Exporter::default_frontmatter_merge`] to merge nested mappings as well.
+    pub fn default_frontmatter(&mut self, defaults: Frontmatter) -> &mut Self {
+        self.default_frontmatter = defaults;
         self
     }
 
-    /// Append a function to the chain of [postprocessors][Postprocessor] to run on exported
-    /// Obsidian Markdown notes.
-    pub fn add_postprocessor(&mut self, processor: &'a Postprocessor<'_>) -> &mut Self {
-        self.postprocessors.push(processor);
+    /// Set how [`Exporter::default_frontmatter`] is merged with the frontmatter of each note.
+    ///
+    /// By default, only top-level keys are merged ([`FrontmatterMerge::Shallow`]). See
+    /// [`merge_frontmatter`] for details.
+    pub fn default_frontmatter_merge(&mut self, strategy: FrontmatterMerge) -> &mut Self {
+        self.default_frontmatter_merge = strategy;
         self
     }
 
-    /// Append a function to the chain of [postprocessors][Postprocessor] for embeds.
-    pub fn add_embed_postprocessor(&mut self, processor: &'a Postprocessor<'_>) -> &mut Self {
-        self.embed_postprocessors.push(processor);
+    /// Add the path of each note within the vault to its frontmatter, under `key`.
+    ///
+    /// The path is relative to the root of the vault and always uses forward slashes as
+    /// separators, regardless of the platform, such as `Books/Dune.md`. Any value the note
+    /// already has for `key` is replaced. The path is added before [postprocessors][Postprocessor]
+    /// run, so they can make use of it (or change it).
+    pub fn inject_source_path(&mut self, key: String) -> &mut Self {
+        self.source_path_key = Some(key);
         self
     }
 
-    /// Export notes using the settings configured on this exporter.
-    pub fn run(&mut self) -> Result<()> {
-        if !self.root.exists() {
-            return Err(ExportError::PathDoesNotExist {
-                path: self.root.clone(),
-            });
+    /// Set whether notes may change how they're exported through keys in their frontmatter.
+    ///
+    /// When `honor` is true, the following keys are recognized:
+    ///
+    /// - `export: false` skips the note entirely.
+    /// - `export-destination: path/to/note.md` writes the note to the given path, relative to the
+    ///   destination, instead of mirroring its location in the vault. Links to the note from other
+    ///   notes aren't updated accordingly.
+    /// - `export-frontmatter: always|never|auto` overrides [`Exporter::frontmatter_strategy`] for
+    ///   the note.
+    ///
+    /// These keys are removed from the frontmatter of the exported note. They're applied after
+    /// [`Exporter::default_frontmatter`] and [`Exporter::inject_source_path`] but before any
+    /// [postprocessors][Postprocessor] run, and take precedence over the corresponding options of
+    /// the exporter. Defaults to false, in which case these keys are exported like any other.
+    pub fn honor_frontmatter_directives(&mut self, honor: bool) -> &mut Self {
+        self.honor_frontmatter_directives = honor;
+        self
+    }
+
+    // Apply the directives in the frontmatter of the note described by `context` (see
+    // honor_frontmatter_directives), removing them from its frontmatter. Returns the frontmatter
+    // strategy for the note, or None when the note should be skipped.
+    fn apply_frontmatter_directives(&self, context: &mut Context) -> Option<FrontmatterStrategy> {
+        if !self.honor_frontmatter_directives {
+            return Some(self.frontmatter_strategy);
+        }
+        let mut take = |key: &str| {
+            context
+                .frontmatter
+                .remove(serde_yaml::Value::String(key.to_owned()))
+        };
+        let export = take("export");
+        let destination = take("export-destination");
+        let strategy = take("export-frontmatter");
+        if export == Some(serde_yaml::Value::Bool(false)) {
+            return None;
         }
+        if let Some(destination) = destination {
+            match destination.as_str().map(Path::new) {
+                Some(path)
+                    if path
+                        .components()
+                        .all(|component| matches!(component, Component::Normal(_))) =>
+                {
+                    context.destination = self.destination.join(path);
+                }
+                _ => eprintln!(
+                    "Warning: Ignoring invalid export-destination, which must be a relative path within the destination\n\tValue: {:?}\n\tSource: '{}'\n",
+                    destination,
+                    context.current_file().display(),
+                ),
+            }
+        }
+        let Some(strategy) = strategy else {
+            return Some(self.frontmatter_strategy);
+        };
+        match strategy.as_str() {
+            Some("always") => Some(FrontmatterStrategy::Always),
+            Some("never") => Some(FrontmatterStrategy::Never),
+            Some("auto") => Some(FrontmatterStrategy::Auto),
+            _ => {
+                eprintln!(
+                    "Warning: Ignoring invalid export-frontmatter, which must be one of always, never or auto\n\tValue: {:?}\n\tSource: '{}'\n",
+                    strategy,
+                    context.current_file().display(),
+                );
+                Some(self.frontmatter_strategy)
+            }
+        }
+    }
 
-        self.vault_contents = Some(vault_contents(
-            self.root.as_path(),
-            self.walk_options.clone(),
-        )?);
+    /// Set the [`FrontmatterFallback`] to be used for notes with frontmatter which isn't a mapping
+    /// of keys to values.
+    ///
+    /// By default, such notes cause [`ExportError::FrontMatterDecodeError`] to be returned.
+    pub fn frontmatter_fallback(&mut self, fallback: FrontmatterFallback) -> &mut Self {
+        self.frontmatter_fallback = fallback;
+        self
+    }
+
+    /// Set whether frontmatter with indented `---` fences should be recognized.
+    ///
+    /// By default, the `---` lines around frontmatter must not be indented. Enabling this also
+    /// treats a block between indented fences at the start of a note as frontmatter, even when
+    /// preceded by blank lines or HTML comments (`<!-- ... -->`). Note that a note which starts
+    /// with text between two indented horizontal rules is then mistaken for frontmatter as well.
+    pub fn lenient_frontmatter(&mut self, lenient: bool) -> &mut Self {
+        self.lenient_frontmatter = lenient;
+        self
+    }
+
+    /// Set the [`EncodingFallback`] to be used for notes which aren't valid UTF-8.
+    ///
+    /// By default, such notes cause [`ExportError::CharacterEncodingError`] to be returned.
+    ///
+    /// Notes which start with a UTF-16 byte order mark are always decoded as UTF-16. The fallback
+    /// only applies to these when they contain invalid UTF-16, in which case the
+    /// [`EncodingFallback::Error`] strategy returns [`ExportError::Utf16EncodingError`].
+    pub fn encoding_fallback(&mut self, fallback: EncodingFallback) -> &mut Self {
+        self.encoding_fallback = fallback;
+        self
+    }
+
+    /// Set the behavior when recursive embeds are encountered.
+    ///
+    /// When `recursive` is true (the default), emdeds are always processed recursively. This may
+    /// lead to infinite recursion when note A embeds B, but B also embeds A.
+    /// (When this happens, [`ExportError::RecursionLimitExceeded`] will be returned by
+    /// [`Exporter::run`]).
+    ///
+    /// When `recursive` is false, if a note is encountered for a second time while processing the
+    /// original note, instead of embedding it again a link to the note is inserted instead.
+    pub fn process_embeds_recursively(&mut self, recursive: bool) -> &mut Self {
+        self.process_embeds_recursively = recursive;
+        self
+    }
+
+    /// Set the text which is inserted before the link to a note when an embed is replaced by a
+    /// link.
+    ///
+    /// This happens when a cycle of embeds is broken (see
+    /// [`Exporter::process_embeds_recursively`]), when repeated embeds are deduplicated (see
+    /// [`Exporter::deduplicate_embeds`]) or when embeds exceed [`Exporter::max_embed_bytes`] or
+    /// [`Exporter::max_embeds_per_note`].
+    /// Defaults to `"→ "`. When `prefix` is `None`, only the link itself is inserted.
+    pub fn embed_cycle_prefix(&mut self, prefix: Option<String>) -> &mut Self {
+        self.embed_cycle_prefix = prefix;
+        self
+    }
+
+    /// Set whether notes which are embedded multiple times should be deduplicated.
+    ///
+    /// When `deduplicate` is true, the first embed of a note (or a given section of a note) is
+    /// included in full, but any further embeds of that same note within the same root note are
+    /// replaced with a link to the note instead.
+    pub fn deduplicate_embeds(&mut self, deduplicate: bool) -> &mut Self {
+        self.deduplicate_embeds = deduplicate;
+        self
+    }
+
+    /// Set the maximum amount of embedded content (in bytes) a single note may contain.
+    ///
+    /// The size of embedded notes, including anything embedded by those notes in turn, is added
+    /// up for each note being exported. Once embedding a note would exceed `max_bytes`, that embed
+    /// and all further embeds in the note are replaced with a link to the embedded note instead,
+    /// and a warning is printed. Sizes are measured on the rendered markdown of the embedded
+    /// content.
+    ///
+    /// Defaults to `None`, which doesn't limit the size of embedded content.
+    pub fn max_embed_bytes(&mut self, max_bytes: Option<usize>) -> &mut Self {
+        self.max_embed_bytes = max_bytes;
+        self
+    }
+
+    /// Set the maximum number of notes which may be embedded into a single note.
+    ///
+    /// Embeds are counted for each note being exported, including those in embedded notes. Once
+    /// `max_embeds` notes have been embedded, any further embeds of notes are replaced with a link
+    /// to the embedded note instead, and a warning is printed. Embedded images and other files
+    /// don't count towards this limit.
+    ///
+    /// Defaults to `None`, which doesn't limit the number of embeds.
+    pub fn max_embeds_per_note(&mut self, max_embeds: Option<usize>) -> &mut Self {
+        self.max_embeds_per_note = max_embeds;
+        self
+    }
+
+    /// Set whether headings in embedded notes should be demoted.
+    ///
+    /// When `demote` is true, the headings of an embedded note are demoted by one level for each
+    /// level of embedding (so by [`Context::note_depth`] minus one in total), keeping a single
+    /// outline when a note starting with a `# Heading` is embedded in a note with its own title.
+    /// Headings can't be demoted beyond level 6, so those stay at level 6.
+    pub fn demote_embedded_headings(&mut self, demote: bool) -> &mut Self {
+        self.demote_embedded_headings = demote;
+        self
+    }
+
+    /// Set whether the modified time of exported files should be preserved.
+    ///
+    /// When `preserve` is true, the modified time of exported files will be set to the modified
+    /// time of the source file.
+    pub fn preserve_mtime(&mut self, preserve: bool) -> &mut Self {
+        self.preserve_mtime = preserve;
+        self
+    }
+
+    /// Set whether files in the destination which weren't written by the export should be removed.
+    ///
+    /// When `clean` is true, files in the destination directory which weren't produced by
+    /// [`Exporter::run`], such as the exported copies of notes which have since been deleted or
+    /// renamed, are removed after all files have been exported successfully. Directories which
+    /// end up empty are removed as well. Hidden files and directories (whose names start with a
+    /// `.`, like `.git`) are left alone, as is everything outside of the destination.
+    ///
+    /// Nothing is removed when a single file is exported, nor when exporting through
+    /// [`Exporter::export_to`]. The export fails with [`ExportError::CleanOverlapsVault`] before
+    /// anything is written when the vault and the destination overlap, such as when the vault is
+    /// a subdirectory of the destination.
+    pub fn clean_destination(&mut self, clean: bool) -> &mut Self {
+        self.clean_destination = clean;
+        self
+    }
+
+    /// Set what happens when a file is exported to a destination which already exists.
+    ///
+    /// By default, existing files are overwritten. See [`OverwritePolicy`] for the alternatives.
+    /// Files which are skipped because they already exist are neither passed to
+    /// [`Exporter::on_note_written`], nor removed by [`Exporter::clean_destination`].
+    pub fn overwrite_policy(&mut self, policy: OverwritePolicy) -> &mut Self {
+        self.overwrite_policy = policy;
+        self
+    }
+
+    /// Set the [`pulldown_cmark_to_cmark::Options`] used to render exported notes back to markdown.
+    ///
+    /// This controls the style of the markdown that is written, such as the characters used for
+    /// list bullets, emphasis and code block fences.
+    pub fn cmark_options(&mut self, options: pulldown_cmark_to_cmark::Options<'a>) -> &mut Self {
+        self.cmark_options = options;
+        self
+    }
+
+    /// Set a base URL or path to prefix all links to notes and attachments with.
+    ///
+    /// By default, links are relative to the note they appear in. When `link_base` is set, links
+    /// are made relative to the root of the export instead and prefixed with `link_base`. This is
+    /// useful when notes are published under a sub-path, such as `https://example.com/notes/`.
+    ///
+    /// Links to external resources present in the source notes are not affected. The
+    /// [`prefix_internal_links`][postprocessors::prefix_internal_links] postprocessor applies a
+    /// prefix the same way, for when links can't be built this way from the start.
+    pub fn link_base(&mut self, link_base: String) -> &mut Self {
+        self.link_base = Some(link_base);
+        self
+    }
+
+    /// Export all files directly into the destination, without any subdirectories.
+    ///
+    /// The directories a file is in are made part of its filename instead, joined by `separator`.
+    /// For example, with a separator of `-`, `a/b/Note.md` is exported as `a-b-Note.md`. Links
+    /// to notes and attachments are adjusted to match. Files which end up with the same name,
+    /// such as `a/b-c.md` and `a-b/c.md`, result in [`ExportError::DestinationCollision`].
+    pub fn flatten_output(&mut self, separator: String) -> &mut Self {
+        self.flatten_separator = Some(separator);
+        self
+    }
+
+    /// Set whether the paths of exported files should be lowercased.
+    ///
+    /// When `lowercase` is true, all directories and filenames within the destination are
+    /// lowercased, so `Notes/My Note.md` is exported as `notes/my note.md`. Links to notes and
+    /// attachments are lowercased to match. The destination itself, as well as paths set through
+    /// frontmatter or by postprocessors, are left as-is, as are links rewritten by
+    /// [`resolve_markdown_links`][postprocessors::resolve_markdown_links]. Files which end up with
+    /// the same name, such as `Note.md` and `note.md`, result in
+    /// [`ExportError::DestinationCollision`].
+    pub fn lowercase_output(&mut self, lowercase: bool) -> &mut Self {
+        self.lowercase_output = lowercase;
+        self
+    }
+
+    // Return `relative_path` (relative to a start_at path) as it's exported, which is flattened
+    // into a single filename when flatten_output is enabled and lowercased when lowercase_output
+    // is enabled.
+    fn flattened_path(&self, relative_path: &Path) -> PathBuf {
+        let path = self.flatten_separator.as_ref().map_or_else(
+            || relative_path.to_path_buf(),
+            |separator| {
+                let components: Vec<_> = relative_path
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect();
+                PathBuf::from(components.join(separator))
+            },
+        );
+        match self.lowercase_output {
+            true => PathBuf::from(path.to_string_lossy().to_lowercase()),
+            false => path,
+        }
+    }
+
+    /// Set whether references to folders should resolve to the folder's index note.
+    ///
+    /// Obsidian "folder notes" are notes which describe the folder they are stored in. When
+    /// `folder_notes` is true, a reference such as `[[Folder]]` which doesn't match any regular
+    /// note resolves to `Folder/Folder.md` or, failing that, to the index note within `Folder`
+    /// (see [`Exporter::folder_note_index`]).
+    ///
+    /// A regular note named `Folder.md` always takes precedence over a folder note.
+    pub fn folder_notes(&mut self, folder_notes: bool) -> &mut Self {
+        self.folder_notes = folder_notes;
+        self
+    }
+
+    /// Set the filename of index notes used for [folder notes][Exporter::folder_notes].
+    ///
+    /// Defaults to `index.md`.
+    pub fn folder_note_index(&mut self, filename: String) -> &mut Self {
+        self.folder_note_index = filename;
+        self
+    }
+
+    /// Set the folder in which daily notes are stored, relative to the root of the vault.
+    ///
+    /// Obsidian's daily notes are named after their date, and plugins link to them by date only,
+    /// like `[[2024-01-15]]`. When a folder is set, such date references resolve to the matching
+    /// note within `folder` (or its subfolders) first, even when a note with the same name exists
+    /// elsewhere in the vault. Date references without a matching note in `folder`, and all other
+    /// references, are resolved as usual.
+    pub fn daily_note_folder(&mut self, folder: Option<PathBuf>) -> &mut Self {
+        self.daily_note_folder = folder;
+        self
+    }
+
+    /// Set whether Obsidian wikilinks should be preserved instead of being converted.
+    ///
+    /// By default, links (`[[note]]`) are converted to regular markdown links and embeds
+    /// (`![[note]]`) are replaced by the content of the embedded note. When `preserve` is true,
+    /// both are left as wikilinks instead, for use with tools which understand wikilinks
+    /// themselves (or to export to another Obsidian vault).
+    ///
+    /// References are still looked up in the vault, and a warning is printed for any which can't
+    /// be found. See also [`Exporter::resolve_preserved_wikilinks`].
+    pub fn preserve_wikilinks(&mut self, preserve: bool) -> &mut Self {
+        self.preserve_wikilinks = preserve;
+        self
+    }
 
+    /// Set whether wikilinks preserved by [`Exporter::preserve_wikilinks`] should be resolved.
+    ///
+    /// By default, preserved wikilinks are kept verbatim. When `resolve` is true, the target of
+    /// each wikilink is replaced with the full path of the file it refers to (relative to the root
+    /// of the vault), with the original text kept as the wikilink's alias. This avoids ambiguity
+    /// when several notes share the same name. References which can't be resolved are kept
+    /// verbatim.
+    pub fn resolve_preserved_wikilinks(&mut self, resolve: bool) -> &mut Self {
+        self.resolve_preserved_wikilinks = resolve;
+        self
+    }
+
+    /// Set whether Windows-style line endings (`\r\n`) should be converted to `\n` when reading
+    /// notes.
+    ///
+    /// Exported notes are written with the line endings set through [`Exporter::line_ending`], but
+    /// by default the original line endings are kept in the raw note contents which
+    /// postprocessors see through [`Context::source_content`]. Enabling this normalizes those as
+    /// well, which saves postprocessors from having to deal with both kinds of line endings.
+    pub fn normalize_line_endings(&mut self, normalize: bool) -> &mut Self {
+        self.normalize_line_endings = normalize;
+        self
+    }
+
+    /// Set the line endings used in exported notes, including their frontmatter.
+    ///
+    /// This defaults to [`LineEnding::Lf`]. Notes whose output is replaced by a postprocessor
+    /// (see [`Context::set_output_bytes`]) are written as-is.
+    pub fn line_ending(&mut self, line_ending: LineEnding) -> &mut Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set whether exported notes should end with a line ending.
+    ///
+    /// This is enabled by default. When disabled, trailing line endings are removed from
+    /// exported notes.
+    pub fn trailing_newline(&mut self, trailing_newline: bool) -> &mut Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Set the file extensions (without leading `.`) of files which should be treated as notes.
+    ///
+    /// Files with any of these extensions are parsed as notes, while all other files are copied
+    /// as-is. Note references without an extension (`[[Note]]`) may refer to a file with any of
+    /// these extensions. Defaults to `["md"]`.
+    pub fn markdown_extensions(&mut self, extensions: Vec<String>) -> &mut Self {
+        self.markdown_extensions = extensions;
+        self
+    }
+
+    /// Return whether `file` is considered a note (as opposed to an attachment), based on its
+    /// extension.
+    ///
+    /// See also [`Exporter::markdown_extensions`].
+    #[must_use]
+    pub fn is_markdown_file(&self, file: &Path) -> bool {
+        file.extension().is_some_and(|ext| {
+            self.markdown_extensions
+                .iter()
+                .any(|markdown_ext| ext == markdown_ext.as_str())
+        })
+    }
+
+    /// Set the extensions of attachments which should be skipped entirely.
+    ///
+    /// Files with any of these `extensions` (matched case-insensitively, with or without a leading
+    /// `.`) are not copied to the destination. Links and embeds which refer to such files are
+    /// replaced with their link text as plain text, without printing a warning. Notes are never
+    /// skipped, even if their extension is listed here.
+    pub fn skip_attachment_extensions(&mut self, extensions: Vec<String>) -> &mut Self {
+        self.skip_attachment_extensions = extensions
+            .into_iter()
+            .map(|ext| ext.trim_start_matches('.').to_owned())
+            .collect();
+        self
+    }
+
+    // Return whether `file` is an attachment with one of the skip_attachment_extensions.
+    fn is_skipped_attachment(&self, file: &Path) -> bool {
+        !self.is_markdown_file(file)
+            && file.extension().is_some_and(|ext| {
+                self.skip_attachment_extensions
+                    .iter()
+                    .any(|skipped| ext.eq_ignore_ascii_case(skipped.as_str()))
+            })
+    }
+
+    /// Set the directory (relative to the destination) which attachments outside of
+    /// [`Exporter::start_at`] are copied to.
+    ///
+    /// Normally, only files under `start_at` are exported, so links and embeds which refer to
+    /// attachments elsewhere in the vault point outside of the destination. When `dir` is set,
+    /// such attachments are copied to `dir` instead, keeping their path relative to the root of
+    /// the vault, and links to them are adjusted accordingly. Only attachments which are actually
+    /// referenced by an exported note are copied.
+    pub fn external_attachments_dir(&mut self, dir: Option<PathBuf>) -> &mut Self {
+        self.external_attachments_dir = dir;
+        self
+    }
+
+    // Return the destination of `file` when it's an attachment which should be copied to the
+    // external_attachments_dir.
+    fn external_attachment_destination(&self, file: &Path) -> Option<PathBuf> {
+        let dir = self.external_attachments_dir.as_ref()?;
+        if self.is_markdown_file(file)
+            || self.is_rendered_canvas(file)
+            || self.relative_to_start_at(file).is_some()
+        {
+            return None;
+        }
+        let relative_path = file.strip_prefix(&self.root).ok()?;
+        Some(self.export_root().join(dir).join(relative_path))
+    }
+
+    // Return the directory which notes are exported into.
+    fn export_root(&self) -> &Path {
+        match self.single_start_at_file().is_some() && !self.destination.is_dir() {
+            true => self.destination.parent().unwrap_or(&self.destination),
+            false => &self.destination,
+        }
+    }
+
+    /// Set whether Obsidian canvas (`.canvas`) files should be rendered as markdown.
+    ///
+    /// By default, canvas files are copied as-is, like any other attachment. When `render` is
+    /// true, they're exported as a note (with a `.md` extension) instead, listing the cards on
+    /// the canvas from top to bottom: text cards become paragraphs, cards showing a note become a
+    /// link to that note and cards showing an attachment embed it. Links to canvas files point to
+    /// the rendered note.
+    pub fn render_canvas(&mut self, render: bool) -> &mut Self {
+        self.render_canvas = render;
+        self
+    }
+
+    // Return whether `file` is a canvas file which should be rendered as markdown.
+    fn is_rendered_canvas(&self, file: &Path) -> bool {
+        self.render_canvas
+            && file
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("canvas"))
+    }
+
+    /// Set how embeds which refer to a file that can't be found should be exported.
+    ///
+    /// By default, such embeds are removed (see [`MissingEmbedStyle`]). A warning is printed for
+    /// each of them regardless of `style`.
+    pub fn missing_embed_style(&mut self, style: MissingEmbedStyle) -> &mut Self {
+        self.missing_embed_style = style;
+        self
+    }
+
+    /// Set what happens to the frontmatter of notes which are embedded into other notes.
+    ///
+    /// By default, it's dropped (see [`EmbedFrontmatterBehavior`]).
+    pub fn embed_frontmatter_behavior(&mut self, behavior: EmbedFrontmatterBehavior) -> &mut Self {
+        self.embed_frontmatter_behavior = behavior;
+        self
+    }
+
+    /// Set whether the links between notes should be collected while exporting.
+    ///
+    /// When `collect` is true, each link and embed which refers to another file in the vault is
+    /// recorded. Once the export has finished, the resulting graph is available through
+    /// [`Exporter::link_graph`].
+    pub fn collect_link_graph(&mut self, collect: bool) -> &mut Self {
+        self.collect_link_graph = collect;
+        self
+    }
+
+    /// Set the path of a note which lists the backlinks of all exported files.
+    ///
+    /// When `path` is set, links between notes are collected (see
+    /// [`Exporter::collect_link_graph`]) and, once all notes have been exported, a note is written
+    /// to `path` (relative to the destination) listing each file which is linked to, along with
+    /// the notes which link to it.
+    pub fn backlinks_index(&mut self, path: Option<PathBuf>) -> &mut Self {
+        self.backlinks_index = path;
+        self
+    }
+
+    /// Set whether each note should list the notes which link to it (its backlinks), and how.
+    ///
+    /// Before exporting, the links and embeds of all notes which are exported are collected.
+    /// Notes which are linked to by other notes then get links to those notes, sorted by path,
+    /// either in their frontmatter or in a section appended to the end of the note, as determined
+    /// by `style`. The links are created the same way as for wikilinks, so they're affected by
+    /// settings such as [`Exporter::link_base`]. Notes without backlinks are left unchanged.
+    pub fn generate_backlinks(&mut self, style: Option<BacklinkStyle>) -> &mut Self {
+        self.backlink_style = style;
+        self
+    }
+
+    /// Set whether wikilinks in frontmatter properties should be resolved, and how they're written.
+    ///
+    /// Obsidian properties may link to other notes, such as `related: ["[[Note A]]", "[[Note
+    /// B]]"]`. When `style` is set, frontmatter values which consist of a single wikilink, as
+    /// well as such items of lists, are replaced with a link to the referenced file as
+    /// determined by `style`. The links are created the same way as for wikilinks in the note,
+    /// so they're affected by settings such as [`Exporter::link_base`]. Wikilinks to files
+    /// which can't be found are left as-is, and a warning is printed.
+    pub fn frontmatter_links(&mut self, style: Option<FrontmatterLinkStyle>) -> &mut Self {
+        self.frontmatter_link_style = style;
+        self
+    }
+
+    /// Set whether links to sections of other notes should be checked.
+    ///
+    /// When enabled, a warning is printed for each link like `[[Other#Section]]` whose anchor
+    /// doesn't match any heading in `Other`, which would result in a link to a non-existent
+    /// section. The link itself is still created. Links to blocks (`[[Other#^block]]`) aren't
+    /// checked, nor are links to sections within the same note; use
+    /// [`validate_internal_anchors`][postprocessors::validate_internal_anchors] for those. To fail
+    /// the export on such links, see [`Exporter::strict_section_links`].
+    pub fn validate_section_links(&mut self, validate: bool) -> &mut Self {
+        self.validate_section_links = validate;
+        self
+    }
+
+    /// Set whether links to sections of other notes which don't exist should fail the export.
+    ///
+    /// This checks links the same way as [`Exporter::validate_section_links`] (whether that's
+    /// enabled or not), printing a warning for each. Notes are still exported, but once all of
+    /// them have been exported, [`Exporter::run`] fails with [`ExportError::BrokenSectionLinks`]
+    /// listing every link which was found. [`Exporter::iter_notes`] only prints the warnings.
+    pub fn strict_section_links(&mut self, strict: bool) -> &mut Self {
+        self.strict_section_links = strict;
+        self
+    }
+
+    /// Set whether a note listing all exported notes should be written, as described by `config`.
+    ///
+    /// Once all notes have been exported, a note is written to [`IndexConfig::path`] (relative to
+    /// the destination) which links to each exported note, in order of their destination. Notes
+    /// skipped by a postprocessor aren't included. Links are relative to the index note, or
+    /// prefixed with [`Exporter::link_base`] when it's set. Optionally, a `.nojekyll` file for
+    /// GitHub Pages is written as well (see [`IndexConfig::nojekyll`]).
+    ///
+    /// Writing the index fails with [`ExportError::DestinationCollision`] when a note from the
+    /// vault is exported to the same path.
+    pub fn emit_index(&mut self, config: Option<IndexConfig>) -> &mut Self {
+        self.index = config;
+        self
+    }
+
+    /// Return the links between notes which were found during the last export.
+    ///
+    /// This is only available after [`Exporter::run`] (or [`Exporter::export_to`]) has completed
+    /// successfully with [`Exporter::collect_link_graph`] or [`Exporter::backlinks_index`] set.
+    #[must_use]
+    pub const fn link_graph(&self) -> Option<&LinkGraph> {
+        self.link_graph.as_ref()
+    }
+
+    // Return whether links should be recorded while exporting.
+    const fn collects_links(&self) -> bool {
+        self.collect_link_graph
+            || self.backlinks_index.is_some()
+            || self.external_attachments_dir.is_some()
+    }
+
+    /// Set a function to rewrite the URLs of external links with.
+    ///
+    /// The handler is called for each link with a URL scheme (such as `https:`) in the exported
+    /// notes, including links in embedded notes. Links to other notes and attachments in the vault
+    /// aren't passed to it. See [`ExternalLinkHandler`] for an example.
+    ///
+    /// Handlers run before any [postprocessors][Postprocessor].
+    pub fn external_link_handler(
+        &mut self,
+        handler: Option<&'a ExternalLinkHandler<'_>>,
+    ) -> &mut Self {
+        self.external_link_handler = handler;
+        self
+    }
+
+    // Rewrite the URLs of the external links in `events` through the external_link_handler.
+    fn rewrite_external_links(&self, events: &mut MarkdownEvents<'_>) {
+        let Some(handler) = self.external_link_handler else {
+            return;
+        };
+        for event in events.iter_mut() {
+            if let Event::Start(Tag::Link { dest_url, .. }) = event {
+                if postprocessors::has_url_scheme(dest_url) {
+                    if let Some(url) = handler(dest_url) {
+                        *dest_url = CowStr::from(url);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Set a function to rewrite fenced code blocks with.
+    ///
+    /// The function is called for each fenced code block in the exported notes, including code
+    /// blocks in embedded notes, and may change both its language and its content (see
+    /// [`CodeBlockHandler`]). Indented code blocks, which have no language, aren't passed to it.
+    ///
+    /// Like [`Exporter::external_link_handler`], it runs before any
+    /// [postprocessors][Postprocessor].
+    pub fn code_block_fn(&mut self, handler: Option<&'a CodeBlockHandler<'_>>) -> &mut Self {
+        self.code_block_fn = handler;
+        self
+    }
+
+    /// Set a function to call for references to attachments which don't exist.
+    ///
+    /// Links and embeds which refer to an attachment (an image, audio, video or PDF file, going by
+    /// its extension) are passed to the handler when the file can't be found in the vault, or no
+    /// longer exists on disk (such as a broken symlink). Without a handler, a warning is printed,
+    /// like for missing notes. Either way, the reference is exported the same way as a reference
+    /// to a missing note. See [`MissingAttachmentHandler`] for an example.
+    pub fn missing_attachment_handler(
+        &mut self,
+        handler: Option<&'a MissingAttachmentHandler<'_>>,
+    ) -> &mut Self {
+        self.missing_attachment_handler = handler;
+        self
+    }
+
+    /// Set a function to call after each file has been written to the destination.
+    ///
+    /// The hook is called for notes as well as attachments, right after the file has been written
+    /// (and its modification time copied, with [`Exporter::preserve_mtime`]). This allows acting on
+    /// files while the export is still running. Notes which are skipped aren't passed to it, nor
+    /// are files passed to the sink of [`Exporter::export_to`]. See [`NoteWrittenHook`] for an
+    /// example.
+    pub fn on_note_written(&mut self, hook: Option<&'a NoteWrittenHook<'_>>) -> &mut Self {
+        self.on_note_written = hook;
+        self
+    }
+
+    /// Set a function to call after each file has been processed, to report the progress of an
+    /// export.
+    ///
+    /// The total passed to it is the number of notes and attachments which are exported, which is
+    /// known once the vault has been walked. Notes which are skipped by a postprocessor are still
+    /// counted as processed, so that the final call reports all files as done. Files written
+    /// afterwards, such as the index of [`Exporter::emit_index`] and attachments copied to
+    /// [`Exporter::external_attachments_dir`], aren't counted. See
+    /// [`ProgressHandler`] for an example.
+    pub fn on_progress(&mut self, handler: Option<&'a ProgressHandler<'_>>) -> &mut Self {
+        self.on_progress = handler;
+        self
+    }
+
+    // Report a reference to `file` which can't be found. References to attachments are passed to
+    // the missing_attachment_handler, if set. Otherwise a warning is printed, describing the
+    // reference as `kind` when it isn't an attachment.
+    fn report_missing_file(&self, file: &str, kind: &str, context: &Context) {
+        let is_attachment = Path::new(file)
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| {
+                [
+                    IMAGE_EXTENSIONS,
+                    AUDIO_EXTENSIONS,
+                    VIDEO_EXTENSIONS,
+                    PDF_EXTENSIONS,
+                ]
+                .iter()
+                .any(|extensions| is_extension_in(ext, extensions))
+            });
+        if let (true, Some(handler)) = (is_attachment, self.missing_attachment_handler) {
+            handler(file, context);
+            return;
+        }
+        eprintln!(
+            "Warning: Unable to find {}\n\tReference: '{}'\n\tSource: '{}'\n",
+            if is_attachment {
+                "referenced attachment"
+            } else {
+                kind
+            },
+            file,
+            context.current_file().display(),
+        );
+    }
+
+    // Rewrite the fenced code blocks in `events` through the code_block_fn.
+    fn rewrite_code_blocks(&self, events: &mut MarkdownEvents<'_>) {
+        let Some(handler) = self.code_block_fn else {
+            return;
+        };
+        let mut rewritten = Vec::with_capacity(events.len());
+        let mut code_block: Option<(CowStr<'_>, String)> = None;
+        for event in events.drain(..) {
+            match (event, &mut code_block) {
+                (Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))), None) => {
+                    code_block = Some((lang, String::new()));
+                }
+                (Event::Text(text), Some((_, code))) => code.push_str(&text),
+                (Event::End(TagEnd::CodeBlock), Some((lang, code))) => {
+                    let (lang, code) = handler(lang, code);
+                    rewritten.push(Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(
+                        CowStr::from(lang),
+                    ))));
+                    if !code.is_empty() {
+                        rewritten.push(Event::Text(CowStr::from(code)));
+                    }
+                    rewritten.push(Event::End(TagEnd::CodeBlock));
+                    code_block = None;
+                }
+                (event, _) => rewritten.push(event),
+            }
+        }
+        *events = rewritten;
+    }
+
+    /// Set how many times writing a file should be retried after a transient IO error.
+    ///
+    /// Some filesystems, network filesystems in particular, occasionally fail with errors which
+    /// go away when simply trying again. When `retries` is greater than zero, writes which fail
+    /// with such an error are retried up to `retries` times, waiting a little longer between each
+    /// attempt. Other errors are returned immediately. Defaults to `0` (no retries).
+    pub fn io_retries(&mut self, retries: usize) -> &mut Self {
+        self.io_retries = retries;
+        self
+    }
+
+    /// Append a function to the chain of [preprocessors][Preprocessor] to run on the text of
+    /// Obsidian Markdown notes before they're parsed.
+    pub fn add_preprocessor(&mut self, processor: &'a Preprocessor<'_>) -> &mut Self {
+        self.preprocessors.push(processor);
+        self
+    }
+
+    /// Append a function to the chain of [preprocessors][Preprocessor] for embeds.
+    ///
+    /// These run instead of the regular preprocessors when a note is parsed to be embedded into
+    /// another note.
+    pub fn add_embed_preprocessor(&mut self, processor: &'a Preprocessor<'_>) -> &mut Self {
+        self.embed_preprocessors.push(processor);
+        self
+    }
+
+    /// Append a function to the chain of [postprocessors][Postprocessor] to run on exported
+    /// Obsidian Markdown notes.
+    pub fn add_postprocessor(&mut self, processor: &'a Postprocessor<'_>) -> &mut Self {
+        self.postprocessors
+            .push(PostprocessorRef::Borrowed(processor));
+        self
+    }
+
+    /// Append a function to the chain of [postprocessors][Postprocessor] to run on exported
+    /// Obsidian Markdown notes, transferring ownership of it to the exporter.
+    ///
+    /// This works like [`Exporter::add_postprocessor`], but as the exporter shares ownership of
+    /// `processor` instead of borrowing it, the exporter can outlive the scope the postprocessor
+    /// was created in. This makes it possible to, for example, store an `Exporter<'static>` in a
+    /// struct. The postprocessor must not borrow any data, so values it uses need to be moved
+    /// into it. Postprocessors added through either method run in the order they were added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use obsidian_export::{Exporter, PostprocessorResult};
+    /// # use std::path::PathBuf;
+    /// # use std::sync::Arc;
+    /// fn make_exporter(source: PathBuf, destination: PathBuf) -> Exporter<'static> {
+    ///     let mut exporter = Exporter::new(source, destination);
+    ///     exporter.add_postprocessor_arc(Arc::new(|_context, _events| PostprocessorResult::Continue));
+    ///     exporter
+    /// }
+    /// ```
+    pub fn add_postprocessor_arc(&mut self, processor: Arc<Postprocessor<'static>>) -> &mut Self {
+        self.postprocessors
+            .push(PostprocessorRef::Shared(processor));
+        self
+    }
+
+    /// Append a function to the chain of [postprocessors][Postprocessor] for embeds.
+    pub fn add_embed_postprocessor(&mut self, processor: &'a Postprocessor<'_>) -> &mut Self {
+        self.embed_postprocessors.push(processor);
+        self
+    }
+
+    /// Append a function to the chain of [postprocessors][Postprocessor] to run on notes whose
+    /// [depth][Context::note_depth] falls within `depths`.
+    ///
+    /// Root notes have a depth of 1 and notes embedded into them a depth of 2 and up, so for
+    /// example `1..=1` only runs `processor` on root notes while `2..` only runs it on embedded
+    /// notes. These postprocessors run after those added through
+    /// [`Exporter::add_postprocessor`] (for root notes) or [`Exporter::add_embed_postprocessor`]
+    /// (for embedded notes), in the order they were added.
+    pub fn add_postprocessor_for_depth<R: RangeBounds<usize>>(
+        &mut self,
+        depths: R,
+        processor: &'a Postprocessor<'_>,
+    ) -> &mut Self {
+        let depths = (depths.start_bound().cloned(), depths.end_bound().cloned());
+        self.depth_postprocessors.push((depths, processor));
+        self
+    }
+
+    /// Append a function to the chain of [text postprocessors][TextPostprocessor] to run on the
+    /// rendered text of exported notes.
+    pub fn add_text_postprocessor(&mut self, processor: &'a TextPostprocessor<'_>) -> &mut Self {
+        self.text_postprocessors.push(processor);
+        self
+    }
+
+    // Return the postprocessors added through add_postprocessor_for_depth which apply to notes at
+    // the given depth.
+    fn postprocessors_for_depth(
+        &self,
+        depth: usize,
+    ) -> impl Iterator<Item = &Postprocessor<'a>> + '_ {
+        self.depth_postprocessors
+            .iter()
+            .filter(move |(depths, _)| depths.contains(&depth))
+            .map(|(_, processor)| *processor)
+    }
+
+    /// Export notes using the settings configured on this exporter.
+    pub fn run(&mut self) -> Result<()> {
+        self.export(&Output::Filesystem)
+    }
+
+    /// Export notes using the settings configured on this exporter, passing the results to `sink`
+    /// instead of writing them to the filesystem.
+    ///
+    /// The full export pipeline runs as usual, but instead of being written to the destination
+    /// directory, each exported note and attachment is passed to `sink` along with its path
+    /// (relative to the destination directory) and its contents. The destination directory
+    /// doesn't need to exist and isn't written to.
+    ///
+    /// Notes are exported in parallel, but calls to `sink` are serialized so that it doesn't need
+    /// to be [`Sync`]. Errors returned by `sink` abort the export with
+    /// [`ExportError::WriteError`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::collections::HashMap;
+    /// # use std::path::PathBuf;
+    /// # use obsidian_export::Exporter;
+    /// let mut exporter = Exporter::new(PathBuf::from("vault"), PathBuf::from("out"));
+    /// let mut files = HashMap::new();
+    /// exporter
+    ///     .export_to(|path, contents| {
+    ///         files.insert(path.to_path_buf(), contents.to_vec());
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn export_to<F>(&mut self, sink: F) -> Result<()>
+    where
+        F: FnMut(&Path, &[u8]) -> std::io::Result<()> + Send,
+    {
+        self.export(&Output::Sink(Mutex::new(Box::new(sink))))
+    }
+
+    /// Export a single note from `content` instead of reading it from a file, returning the
+    /// exported note rather than writing it to the destination.
+    ///
+    /// The note is treated as if it were located at `path`, which doesn't need to exist. Links and
+    /// embeds are resolved against the vault at `root` (as passed to [`Exporter::new`]), or are
+    /// treated as references to missing notes when `root` doesn't exist.
+    ///
+    /// `None` is returned when the note is skipped by a postprocessor.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::path::{Path, PathBuf};
+    /// # use obsidian_export::Exporter;
+    /// let mut exporter = Exporter::new(PathBuf::from("no-vault"), PathBuf::from("out"));
+    /// let note = exporter
+    ///     .export_str(Path::new("note.md"), "Some *markdown*".to_owned())
+    ///     .unwrap();
+    /// assert_eq!(note, Some(b"Some *markdown*\n".to_vec()));
+    /// ```
+    pub fn export_str(&mut self, path: &Path, content: String) -> Result<Option<Vec<u8>>> {
+        let vault_contents = match self.root.exists() {
+            true => self.walk_vault()?,
+            false => vec![],
+        };
+        self.vault_contents = Some(vault_contents);
+
+        let dest = self.destination_for(path).unwrap_or_else(|| {
+            self.destination
+                .join(path.file_name().unwrap_or(path.as_os_str()))
+        });
+        let note = self
+            .render_obsidian_note(path, &dest, Some(content))
+            .context(FileExportSnafu { path })?;
+        Ok(note.map(|(_, contents)| contents))
+    }
+
+    /// Export the notes in the vault one at a time, returning them rather than writing them to the
+    /// destination.
+    ///
+    /// Each note is only exported once the iterator reaches it, so callers can process notes
+    /// lazily, for example to stream them into an archive. Notes are returned in order of their
+    /// source path and are exported using the same settings as [`Exporter::run`], but notes
+    /// skipped by a postprocessor are left out. Attachments aren't included; use
+    /// [`Exporter::files_to_export`] to find those.
+    ///
+    /// Unlike [`Exporter::run`], notes are exported sequentially and the destination doesn't need
+    /// to exist, nor is it written to.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::path::PathBuf;
+    /// # use obsidian_export::Exporter;
+    /// let mut exporter = Exporter::new(PathBuf::from("vault"), PathBuf::from("out"));
+    /// for note in exporter.iter_notes().unwrap() {
+    ///     let note = note.unwrap();
+    ///     println!("{}: {} bytes", note.source.display(), note.contents.len());
+    /// }
+    /// ```
+    pub fn iter_notes(
+        &mut self,
+    ) -> Result<impl Iterator<Item = Result<ExportedNote>> + use<'_, 'a>> {
+        let mut files = self.prepare_export(false)?;
+        files.retain(|(src, _)| self.is_exported_as_note(src));
+        files.sort();
+        let exporter = &*self;
+        Ok(files.into_iter().filter_map(move |(src, dest)| {
+            match exporter.render_note_file(&src, &dest) {
+                Ok(Some((context, contents))) => Some(Ok(ExportedNote {
+                    source: src,
+                    destination: context.destination,
+                    frontmatter: context.frontmatter,
+                    contents,
+                })),
+                Ok(None) => None,
+                Err(err) => Some(Err(ExportError::FileExportError {
+                    path: src,
+                    source: Box::new(err),
+                })),
+            }
+        }))
+    }
+
+    /// Return the files which would be exported using the settings configured on this exporter,
+    /// without exporting them.
+    ///
+    /// Each entry consists of the path of a source file and the path it would be exported to,
+    /// sorted by source path. Ignore rules and [`Exporter::start_at`] are taken into account, but
+    /// since postprocessors aren't run, notes which would be skipped by a postprocessor (such as
+    /// [`filter_by_tags`][crate::postprocessors::filter_by_tags]) are still included.
+    pub fn files_to_export(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut files = self.files_to_export_from(&self.walk_vault()?, true)?;
+        files.sort();
+        Ok(files)
+    }
+
+    // Return the files in `vault_contents` which are exported, along with their destinations,
+    // taking reachable_from into account. See export_destinations for `to_filesystem`.
+    fn files_to_export_from(
+        &self,
+        vault_contents: &[PathBuf],
+        to_filesystem: bool,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut files = self.export_destinations(vault_contents, to_filesystem)?;
+        self.retain_reachable_files(&mut files, vault_contents)?;
+        Ok(files)
+    }
+
+    // Walk the vault and return the files to export like files_to_export_from, resetting the state
+    // left behind by a previous export. This is shared by export and iter_notes, so that both
+    // export the same files using the same state.
+    fn prepare_export(&mut self, to_filesystem: bool) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let vault_contents = self.walk_vault()?;
+        let files = self.files_to_export_from(&vault_contents, to_filesystem)?;
+        self.anchor_cache = AnchorCache::default();
+        self.broken_section_links = Arc::default();
+        self.vault_contents = Some(vault_contents);
+        self.note_backlinks = self.collect_backlinks(&files)?;
+        Ok(files)
+    }
+
+    fn export(&mut self, output: &Output<'_>) -> Result<()> {
+        let files = self.prepare_export(matches!(output, Output::Filesystem))?;
+        let cleans_destination = self.clean_destination
+            && matches!(output, Output::Filesystem)
+            && self.single_start_at_file().is_none();
+        let clean_root = match cleans_destination {
+            true => Some(self.check_clean_destination()?),
+            false => None,
+        };
+
+        let state = ExportState {
+            total: files.len(),
+            ..ExportState::default()
+        };
+        files.into_par_iter().try_for_each(|(src, dest)| {
+            self.export_note(&src, &dest, output, &state)?;
+            self.report_progress(&src, &state);
+            Ok(())
+        })?;
+
+        if self.collects_links() {
+            let graph = std::mem::take(
+                &mut *state
+                    .links
+                    .lock()
+                    .expect("links mutex should not be poisoned"),
+            );
+            self.export_external_attachments(&graph, output, &state)?;
+            if let Some(index) = &self.backlinks_index {
+                self.write_backlinks_index(index, &graph, output, &state)?;
+            }
+            if self.collect_link_graph || self.backlinks_index.is_some() {
+                self.link_graph = Some(graph);
+            }
+        }
+        if let Some(config) = &self.index {
+            self.write_index(config, output, &state)?;
+        }
+        if self.strict_section_links {
+            let mut links = std::mem::take(
+                &mut *self
+                    .broken_section_links
+                    .lock()
+                    .expect("broken_section_links mutex should not be poisoned"),
+            );
+            if !links.is_empty() {
+                // Notes are exported in parallel, so sort the links to report them in a stable
+                // order.
+                links.sort_by(|a, b| (&a.source, &a.reference).cmp(&(&b.source, &b.reference)));
+                return Err(ExportError::BrokenSectionLinks { links });
+            }
+        }
+        if let Some(vault_root) = clean_root {
+            let written: HashSet<PathBuf> = std::mem::take(
+                &mut *state
+                    .destinations
+                    .lock()
+                    .expect("destinations mutex should not be poisoned"),
+            )
+            .into_keys()
+            .collect();
+            remove_stale_files(&self.destination, &written, &vault_root)?;
+        }
+        Ok(())
+    }
+
+    // Make sure cleaning the destination can't remove files from the vault, which is the case
+    // when either one is inside the other. Returns the canonical path of the vault root.
+    fn check_clean_destination(&self) -> Result<PathBuf> {
+        let canonicalize = |path: &Path| {
+            fs::canonicalize(path).context(ReadSnafu {
+                path: path.to_path_buf(),
+            })
+        };
+        let root = canonicalize(self.vault_root())?;
+        let destination = canonicalize(&self.destination)?;
+        if root.starts_with(&destination) || destination.starts_with(&root) {
+            return Err(ExportError::CleanOverlapsVault {
+                root: self.vault_root().to_path_buf(),
+                destination: self.destination.clone(),
+            });
+        }
+        Ok(root)
+    }
+
+    // Write a note to the path in `config` (relative to the destination) which lists all notes
+    // which were exported, along with a .nojekyll file when requested.
+    fn write_index(
+        &self,
+        config: &IndexConfig,
+        output: &Output<'_>,
+        state: &ExportState,
+    ) -> Result<()> {
+        let index = self.destination.join(&config.path);
+        let index_dir = index.parent().unwrap_or(&self.destination);
+        let mut notes: Vec<PathBuf> = state
+            .destinations
+            .lock()
+            .expect("destinations mutex should not be poisoned")
+            .iter()
+            .filter(|(_, src)| self.is_exported_as_note(src))
+            .map(|(dest, _)| dest.clone())
+            .collect();
+        notes.sort();
+        state.claim_destination(&config.path, &index)?;
+
+        let mut events = vec![
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                id: None,
+                classes: vec![],
+                attrs: vec![],
+            }),
+            Event::Text(CowStr::from(config.title.clone())),
+            Event::End(TagEnd::Heading(HeadingLevel::H1)),
+            Event::Start(Tag::List(None)),
+        ];
+        for note in &notes {
+            let link = self.link_base.as_ref().map_or_else(
+                || {
+                    let path = diff_paths(note, index_dir).unwrap_or_else(|| note.clone());
+                    utf8_percent_encode(&path.to_string_lossy(), PERCENTENCODE_CHARS).to_string()
+                },
+                |link_base| {
+                    let path = note.strip_prefix(&self.destination).unwrap_or(note);
+                    format!(
+                        "{}/{}",
+                        link_base.trim_end_matches('/'),
+                        utf8_percent_encode(&path.to_string_lossy(), PERCENTENCODE_CHARS)
+                    )
+                },
+            );
+            let label = note.file_stem().unwrap_or_default().to_string_lossy();
+            events.push(Event::Start(Tag::Item));
+            events.push(Event::Start(Tag::Link {
+                link_type: pulldown_cmark::LinkType::Inline,
+                dest_url: CowStr::from(link),
+                title: CowStr::from(""),
+                id: CowStr::from(""),
+            }));
+            events.push(Event::Text(CowStr::from(label.into_owned())));
+            events.push(Event::End(TagEnd::Link));
+            events.push(Event::End(TagEnd::Item));
+        }
+        events.push(Event::End(TagEnd::List(false)));
+
+        let contents =
+            self.apply_line_endings(render_markdown(&events, Some(self.cmark_options.clone())));
+        self.write_output(output, &index, contents.as_bytes())?;
+        if config.nojekyll {
+            let nojekyll = self.destination.join(".nojekyll");
+            state.claim_destination(Path::new(".nojekyll"), &nojekyll)?;
+            self.write_output(output, &nojekyll, b"")?;
+        }
+        Ok(())
+    }
+
+    // Copy the attachments outside of start_at which are linked to in `graph` to the
+    // external_attachments_dir.
+    fn export_external_attachments(
+        &self,
+        graph: &LinkGraph,
+        output: &Output<'_>,
+        state: &ExportState,
+    ) -> Result<()> {
+        graph
+            .backlinks()
+            .into_keys()
+            .filter_map(|src| Some((src, self.external_attachment_destination(src)?)))
+            .try_for_each(|(src, dest)| self.export_note(src, &dest, output, state))
+    }
+
+    // Write a note to `path` (relative to the destination) which lists the backlinks in `graph`.
+    fn write_backlinks_index(
+        &self,
+        path: &Path,
+        graph: &LinkGraph,
+        output: &Output<'_>,
+        state: &ExportState,
+    ) -> Result<()> {
+        let index = self.destination.join(path);
+        state.claim_destination(path, &index)?;
+        let index_dir = index.parent().unwrap_or(&self.destination);
+        let link_to = |file: &Path| -> Option<MarkdownEvents<'_>> {
+            let destination = self.destination_for(file)?;
+            let link = diff_paths(destination, index_dir)?;
+            let label = match self.is_markdown_file(file) {
+                true => file.file_stem(),
+                false => file.file_name(),
+            }?;
+            Some(vec![
+                Event::Start(Tag::Link {
+                    link_type: pulldown_cmark::LinkType::Inline,
+                    dest_url: CowStr::from(
+                        utf8_percent_encode(&link.to_string_lossy(), PERCENTENCODE_CHARS)
+                            .to_string(),
+                    ),
+                    title: CowStr::from(""),
+                    id: CowStr::from(""),
+                }),
+                Event::Text(CowStr::from(label.to_string_lossy().into_owned())),
+                Event::End(TagEnd::Link),
+            ])
+        };
+
+        let mut events = vec![
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                id: None,
+                classes: vec![],
+                attrs: vec![],
+            }),
+            Event::Text(CowStr::Borrowed("Backlinks")),
+            Event::End(TagEnd::Heading(HeadingLevel::H1)),
+        ];
+        for (target, sources) in graph.backlinks() {
+            let Some(target_link) = link_to(target) else {
+                continue;
+            };
+            events.push(Event::Start(Tag::Heading {
+                level: HeadingLevel::H2,
+                id: None,
+                classes: vec![],
+                attrs: vec![],
+            }));
+            events.extend(target_link);
+            events.push(Event::End(TagEnd::Heading(HeadingLevel::H2)));
+            events.push(Event::Start(Tag::List(None)));
+            for source_link in sources.into_iter().filter_map(link_to) {
+                events.push(Event::Start(Tag::Item));
+                events.extend(source_link);
+                events.push(Event::End(TagEnd::Item));
+            }
+            events.push(Event::End(TagEnd::List(false)));
+        }
+
+        let contents =
+            self.apply_line_endings(render_markdown(&events, Some(self.cmark_options.clone())));
+        self.write_output(output, &index, contents.as_bytes())?;
+        Ok(())
+    }
+
+    // Remove the files which can't be reached from the reachable_from notes from `files`.
+    fn retain_reachable_files(
+        &self,
+        files: &mut Vec<(PathBuf, PathBuf)>,
+        vault_contents: &[PathBuf],
+    ) -> Result<()> {
+        if self.reachable_from.is_empty() {
+            return Ok(());
+        }
+        let reachable = self.reachable_files(vault_contents)?;
+        files.retain(|(src, _)| reachable.contains(src.as_path()));
+        Ok(())
+    }
+
+    // Collect the links between the notes among `files` when generating backlinks, so that the
+    // notes which link to a note are known before it's exported.
+    fn collect_backlinks(&self, files: &[(PathBuf, PathBuf)]) -> Result<LinkGraph> {
+        let mut graph = LinkGraph::new();
+        if self.backlink_style.is_none() {
+            return Ok(graph);
+        }
+        for (src, _) in files.iter().filter(|(src, _)| self.is_markdown_file(src)) {
+            let content = encoding::read_to_string(src, self.encoding_fallback)
+                .context(FileExportSnafu { path: src })?;
+            for reference in note_references(&content) {
+                match self.lookup_note(&reference) {
+                    Some(target) if target != src => graph.insert(src, target),
+                    _ => {}
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    // Find the files in `vault_contents` which can be reached from the reachable_from notes by
+    // following links and embeds.
+    fn reachable_files<'v>(&self, vault_contents: &'v [PathBuf]) -> Result<HashSet<&'v Path>> {
+        let mut reachable: HashSet<&Path> = HashSet::new();
+        for note in &self.reachable_from {
+            let Some(file) = vault_contents.iter().find(|file| *file == note) else {
+                return Err(ExportError::ReachableFromNotInVault { path: note.clone() });
+            };
+            reachable.insert(file);
+        }
+        let mut queue: VecDeque<&Path> = reachable.iter().copied().collect();
+
+        while let Some(note) = queue.pop_front() {
+            if !self.is_markdown_file(note) {
+                continue;
+            }
+            let content = encoding::read_to_string(note, self.encoding_fallback)
+                .context(FileExportSnafu { path: note })?;
+            for reference in note_references(&content) {
+                let Some(target) = self.lookup_note_in(&reference, vault_contents) else {
+                    continue;
+                };
+                if reachable.insert(target) {
+                    queue.push_back(target);
+                }
+            }
+        }
+        Ok(reachable)
+    }
+
+    fn walk_vault(&self) -> Result<Vec<PathBuf>> {
+        if !self.root.exists() {
+            return Err(ExportError::PathDoesNotExist {
+                path: self.root.clone(),
+            });
+        }
+        if !self.root.is_file() || !self.resolve_siblings {
+            return vault_contents(self.root.as_path(), self.walk_options.clone());
+        }
+
+        // When exporting a single file, the files next to it are considered part of the vault as
+        // well, so that links to them can be resolved. Files in other directories aren't, to
+        // avoid walking a potentially large directory tree.
+        let parent = match self.vault_root() {
+            parent if parent.as_os_str().is_empty() => Path::new("."),
+            parent => parent,
+        };
+        let walk_options = WalkOptions {
+            max_depth: Some(1),
+            ..self.walk_options.clone()
+        };
+        let mut files: Vec<PathBuf> = vault_contents(parent, walk_options)?
+            .into_iter()
+            .map(|file| match parent == Path::new(".") {
+                true => file
+                    .strip_prefix(parent)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(file),
+                false => file,
+            })
+            .collect();
+        if !files.contains(&self.root) {
+            files.push(self.root.clone());
+        }
+        Ok(files)
+    }
+
+    // Determine which files in `vault_contents` should be exported, and their destinations.
+    // `to_filesystem` indicates whether the files are going to be written to the destination on
+    // the filesystem, which must exist in that case.
+    fn export_destinations(
+        &self,
+        vault_contents: &[PathBuf],
+        to_filesystem: bool,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
         // When a single file is specified, just need to export that specific file instead of
         // iterating over all discovered files. This also allows us to accept destination as either
         // a file or a directory name.
-        if self.root.is_file() || self.start_at.is_file() {
-            let source_filename = self
-                .start_at
+        if let Some(start_at) = self.single_start_at_file() {
+            let source_filename = start_at
                 .file_name()
                 .expect("File without a filename? How is that possible?")
                 .to_string_lossy();
 
-            let destination = match self.destination.is_dir() {
+            let destination = match self.destination.is_dir() || !to_filesystem {
                 true => self.destination.join(String::from(source_filename)),
                 false => {
                     let parent = self.destination.parent().unwrap_or(&self.destination);
@@ -389,97 +2567,421 @@ impl<'a> Exporter<'a> {
                     self.destination.clone()
                 }
             };
-            return self.export_note(&self.start_at, &destination);
+            return Ok(vec![(start_at.to_path_buf(), destination)]);
         }
 
-        if !self.destination.exists() {
+        if to_filesystem && !self.destination.exists() {
             return Err(ExportError::PathDoesNotExist {
                 path: self.destination.clone(),
             });
         }
-        self.vault_contents
-            .as_ref()
-            .unwrap()
-            .clone()
-            .into_par_iter()
-            .filter(|file| file.starts_with(&self.start_at))
-            .try_for_each(|file| {
-                let relative_path = file
-                    .strip_prefix(self.start_at.clone())
-                    .expect("file should always be nested under root")
-                    .to_path_buf();
-                let destination = &self.destination.join(relative_path);
-                self.export_note(&file, destination)
-            })?;
-        Ok(())
+        Ok(vault_contents
+            .iter()
+            .filter_map(|file| Some((file.clone(), self.destination_for(file)?)))
+            .collect())
+    }
+
+    /// Return the path `source` would be exported to, without exporting anything.
+    ///
+    /// This applies the same mapping from source to destination paths as [`Exporter::run`], taking
+    /// [`Exporter::start_at`] and [`Exporter::skip_attachment_extensions`] into account. `None` is
+    /// returned for files which wouldn't be exported for these reasons.
+    ///
+    /// `source` isn't checked against ignore rules, nor does it need to exist. Use
+    /// [`Exporter::files_to_export`] to get the destinations of all files which are exported.
+    #[must_use]
+    pub fn destination_for(&self, source: &Path) -> Option<PathBuf> {
+        if let Some(start_at) = self.single_start_at_file() {
+            if source != start_at {
+                return None;
+            }
+            return Some(match self.destination.is_dir() {
+                true => self.destination.join(start_at.file_name()?),
+                false => self.destination.clone(),
+            });
+        }
+        if self.is_skipped_attachment(source) {
+            return None;
+        }
+        let relative_path = self.relative_to_start_at(source)?;
+        let mut destination = self.destination.join(self.flattened_path(relative_path));
+        if self.is_rendered_canvas(source) {
+            destination.set_extension("md");
+        }
+        Some(destination)
+    }
+
+    fn export_note(
+        &self,
+        src: &Path,
+        dest: &Path,
+        output: &Output<'_>,
+        state: &ExportState,
+    ) -> Result<()> {
+        let written = match (self.is_exported_as_note(src), output) {
+            (true, _) => self.parse_and_export_obsidian_note(src, dest, output, state),
+            (false, Output::Filesystem) => state
+                .claim_destination(src, dest)
+                .and_then(|()| copy_file(src, dest, self.io_retries, self.overwrite_policy))
+                .map(|written| written.then(|| dest.to_path_buf())),
+            (false, Output::Sink(_)) => state
+                .claim_destination(src, dest)
+                .and_then(|()| fs::read(src).context(ReadSnafu { path: src }))
+                .and_then(|contents| self.write_output(output, dest, &contents))
+                .map(|_| None),
+        }
+        .context(FileExportSnafu { path: src })?;
+
+        if self.preserve_mtime && written.is_some() {
+            copy_mtime(src, dest).context(FileExportSnafu { path: src })?;
+        }
+        if let (Some(hook), Some(written)) = (self.on_note_written, written) {
+            hook(src, &written);
+        }
+        Ok(())
+    }
+
+    // Count `src` as processed and pass the progress to the on_progress handler. This is only done
+    // for the files which make up `state.total`, so files exported afterwards (like external
+    // attachments) don't make the progress run past the total.
+    fn report_progress(&self, src: &Path, state: &ExportState) {
+        if let Some(handler) = self.on_progress {
+            let processed = state
+                .processed
+                .fetch_add(1, Ordering::Relaxed)
+                .saturating_add(1);
+            handler(processed, state.total, src);
+        }
+    }
+
+    // Write `contents` to `dest`, or pass them on to the sink with `dest` made relative to the
+    // export destination. Returns whether the contents were written, which they aren't when `dest`
+    // already exists and is skipped according to the overwrite_policy.
+    fn write_output(&self, output: &Output<'_>, dest: &Path, contents: &[u8]) -> Result<bool> {
+        match output {
+            Output::Filesystem => {
+                write_file(dest, contents, self.io_retries, self.overwrite_policy)
+            }
+            Output::Sink(sink) => {
+                let path = dest.strip_prefix(&self.destination).unwrap_or(dest);
+                let mut sink = sink.lock().expect("sink mutex should not be poisoned");
+                sink(path, contents)
+                    .map(|()| true)
+                    .context(WriteSnafu { path })
+            }
+        }
     }
 
-    fn export_note(&self, src: &Path, dest: &Path) -> Result<()> {
-        match is_markdown_file(src) {
-            true => self.parse_and_export_obsidian_note(src, dest),
-            false => copy_file(src, dest),
-        }
-        .context(FileExportSnafu { path: src })?;
+    // Return whether `src` is exported as a note, rather than being copied as-is.
+    fn is_exported_as_note(&self, src: &Path) -> bool {
+        self.is_markdown_file(src) || self.is_rendered_canvas(src)
+    }
 
-        if self.preserve_mtime {
-            copy_mtime(src, dest).context(FileExportSnafu { path: src })?;
+    fn parse_and_export_obsidian_note(
+        &self,
+        src: &Path,
+        dest: &Path,
+        output: &Output<'_>,
+        state: &ExportState,
+    ) -> Result<Option<PathBuf>> {
+        let Some((context, contents)) = self.render_note_file(src, dest)? else {
+            return Ok(None);
+        };
+        state.claim_destination(src, &context.destination)?;
+        if self.collects_links() {
+            state
+                .links
+                .lock()
+                .expect("links mutex should not be poisoned")
+                .extend(context.take_links());
         }
+        let written = self.write_output(output, &context.destination, &contents)?;
+        Ok((written && matches!(output, Output::Filesystem)).then_some(context.destination))
+    }
 
-        Ok(())
+    // Render the note or canvas file at `src` like render_obsidian_note.
+    fn render_note_file(&self, src: &Path, dest: &Path) -> Result<Option<(Context, Vec<u8>)>> {
+        let content = match self.is_rendered_canvas(src) {
+            true => {
+                let contents = encoding::read_to_string(src, self.encoding_fallback)?;
+                Some(canvas::to_markdown(src, &contents, &|file| {
+                    self.is_markdown_file(file)
+                })?)
+            }
+            false => None,
+        };
+        self.render_obsidian_note(src, dest, content)
     }
 
-    fn parse_and_export_obsidian_note(&self, src: &Path, dest: &Path) -> Result<()> {
+    // Parse the note at `src` (using `content` instead of reading it from disk when given) and
+    // run it through all postprocessors. Returns the context of the note along with its rendered
+    // contents, or `None` when the note is skipped by a postprocessor.
+    fn render_obsidian_note(
+        &self,
+        src: &Path,
+        dest: &Path,
+        content: Option<String>,
+    ) -> Result<Option<(Context, Vec<u8>)>> {
         let mut context = Context::new(src.to_path_buf(), dest.to_path_buf());
+        context.set_vault_root(self.vault_root());
 
-        let (frontmatter, mut markdown_events) = self.parse_obsidian_note(src, &context)?;
-        context.frontmatter = frontmatter;
-        for func in &self.postprocessors {
+        let (frontmatter, mut markdown_events) = match content {
+            Some(content) => {
+                self.parse_obsidian_note_content(src, content, &mut context, &self.preprocessors)?
+            }
+            None => self.parse_obsidian_note(src, &mut context, &self.preprocessors)?,
+        };
+        context.frontmatter = merge_frontmatter(
+            &self.default_frontmatter,
+            &frontmatter,
+            self.default_frontmatter_merge,
+        );
+        if let Some(key) = &self.source_path_key {
+            let source_path = src.strip_prefix(self.vault_root()).unwrap_or(src);
+            let source_path: Vec<_> = source_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect();
+            context.frontmatter.insert(
+                serde_yaml::Value::String(key.clone()),
+                serde_yaml::Value::String(source_path.join("/")),
+            );
+        }
+        let Some(frontmatter_strategy) = self.apply_frontmatter_directives(&mut context) else {
+            return Ok(None);
+        };
+        self.resolve_frontmatter_links(&mut context);
+        self.add_backlinks(&mut context, &mut markdown_events);
+        self.rewrite_external_links(&mut markdown_events);
+        self.rewrite_code_blocks(&mut markdown_events);
+        let postprocessors = self
+            .postprocessors
+            .iter()
+            .map(|processor| &**processor)
+            .chain(self.postprocessors_for_depth(context.note_depth()));
+        for func in postprocessors {
             match func(&mut context, &mut markdown_events) {
                 PostprocessorResult::StopHere => break,
-                PostprocessorResult::StopAndSkipNote => return Ok(()),
+                PostprocessorResult::StopAndSkipNote => return Ok(None),
                 PostprocessorResult::Continue => (),
             }
         }
 
-        let mut outfile = create_file(&context.destination)?;
-        let write_frontmatter = match self.frontmatter_strategy {
+        if let Some(bytes) = context.take_output_bytes() {
+            return Ok(Some((context, bytes)));
+        }
+
+        let mut contents = String::new();
+        let write_frontmatter = match frontmatter_strategy {
             FrontmatterStrategy::Always => true,
             FrontmatterStrategy::Never => false,
             FrontmatterStrategy::Auto => !context.frontmatter.is_empty(),
         };
         if write_frontmatter {
-            let mut frontmatter_str = frontmatter_to_str(&context.frontmatter)
-                .context(FrontMatterEncodeSnafu { path: src })?;
-            frontmatter_str.push('\n');
-            outfile
-                .write_all(frontmatter_str.as_bytes())
-                .context(WriteSnafu {
-                    path: &context.destination,
-                })?;
-        }
-        outfile
-            .write_all(render_mdevents_to_mdtext(&markdown_events).as_bytes())
-            .context(WriteSnafu {
-                path: &context.destination,
-            })?;
-        Ok(())
+            if self.sort_frontmatter_keys {
+                context.frontmatter = sort_frontmatter(&context.frontmatter);
+            }
+            contents = match self.frontmatter_format {
+                FrontmatterFormat::Yaml => frontmatter_to_str(&context.frontmatter)
+                    .context(FrontMatterEncodeSnafu { path: src })?,
+                FrontmatterFormat::Toml => frontmatter_to_toml_str(&context.frontmatter)
+                    .context(FrontMatterTomlEncodeSnafu { path: src })?,
+            };
+        }
+        // Notes without a body (such as those emptied by a postprocessor) consist of just their
+        // frontmatter, without any trailing blank lines.
+        if !markdown_events.is_empty() {
+            if write_frontmatter {
+                contents.push('\n');
+            }
+            contents.push_str(&render_markdown(
+                &markdown_events,
+                Some(self.cmark_options.clone()),
+            ));
+        }
+        for func in &self.text_postprocessors {
+            match func(&mut context, &mut contents) {
+                PostprocessorResult::StopHere => break,
+                PostprocessorResult::StopAndSkipNote => return Ok(None),
+                PostprocessorResult::Continue => (),
+            }
+        }
+        Ok(Some((
+            context,
+            self.apply_line_endings(contents).into_bytes(),
+        )))
+    }
+
+    // Convert the line endings of the rendered note `contents` as configured through line_ending
+    // and trailing_newline.
+    fn apply_line_endings(&self, contents: String) -> String {
+        let mut contents = match self.line_ending {
+            LineEnding::Lf if !contents.contains('\r') => contents,
+            LineEnding::Lf => contents.replace("\r\n", "\n"),
+            LineEnding::CrLf => contents.replace("\r\n", "\n").replace('\n', "\r\n"),
+        };
+        if !self.trailing_newline {
+            let len = contents.trim_end_matches(['\r', '\n']).len();
+            contents.truncate(len);
+        }
+        contents
+    }
+
+    // Replace the wikilinks in the frontmatter of the note being exported (directly or as items of
+    // a list) with links to the referenced files, as configured through frontmatter_links.
+    fn resolve_frontmatter_links(&self, context: &mut Context) {
+        let Some(style) = self.frontmatter_link_style else {
+            return;
+        };
+        let mut frontmatter = std::mem::take(&mut context.frontmatter);
+        for value in frontmatter.values_mut() {
+            let items = match value {
+                serde_yaml::Value::Sequence(items) => items.iter_mut().collect(),
+                value => vec![value],
+            };
+            for item in items {
+                if let Some(link) = self.frontmatter_link(item, style, context) {
+                    *item = serde_yaml::Value::String(link);
+                }
+            }
+        }
+        context.frontmatter = frontmatter;
+    }
+
+    // Return the link which replaces the frontmatter `value` if it's a wikilink (`[[Note]]`) to a
+    // file which can be found.
+    fn frontmatter_link(
+        &self,
+        value: &serde_yaml::Value,
+        style: FrontmatterLinkStyle,
+        context: &Context,
+    ) -> Option<String> {
+        let text = value
+            .as_str()?
+            .trim()
+            .strip_prefix("[[")?
+            .strip_suffix("]]")?;
+        let reference = ObsidianNoteReference::from_str(text);
+        if let Some(file) = reference.file {
+            if self.lookup_existing_file(file).is_none() {
+                self.report_missing_file(file, "referenced note", context);
+                return None;
+            }
+        }
+        let link = self.make_generated_link_to_file(reference, context);
+        match style {
+            FrontmatterLinkStyle::MarkdownLink => {
+                Some(render_markdown(&link, None).trim_end().to_owned())
+            }
+            FrontmatterLinkStyle::Path => link.into_iter().find_map(|event| match event {
+                Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.into_string()),
+                _ => None,
+            }),
+        }
+    }
+
+    // Add links to the notes which link to the note being exported, as configured through
+    // generate_backlinks.
+    fn add_backlinks(&self, context: &mut Context, events: &mut MarkdownEvents<'_>) {
+        let Some(style) = &self.backlink_style else {
+            return;
+        };
+        let links: Vec<MarkdownEvents<'_>> = self
+            .note_backlinks
+            .links_to(context.current_file())
+            .into_iter()
+            .map(|source| {
+                let file = source.to_string_lossy();
+                let label = source.file_stem().unwrap_or_default().to_string_lossy();
+                let reference = ObsidianNoteReference {
+                    file: Some(&file),
+                    section: None,
+                    section_end: None,
+                    label: Some(&label),
+                };
+                self.make_generated_link_to_file(reference, context)
+            })
+            .collect();
+        if links.is_empty() {
+            return;
+        }
+
+        match style {
+            BacklinkStyle::FrontmatterKey(key) => {
+                let links = links
+                    .iter()
+                    .map(|link| {
+                        serde_yaml::Value::String(render_markdown(link, None).trim_end().to_owned())
+                    })
+                    .collect();
+                context.frontmatter.insert(
+                    serde_yaml::Value::String(key.clone()),
+                    serde_yaml::Value::Sequence(links),
+                );
+            }
+            BacklinkStyle::AppendedSection(title) => {
+                events.extend([
+                    Event::Start(Tag::Heading {
+                        level: HeadingLevel::H2,
+                        id: None,
+                        classes: vec![],
+                        attrs: vec![],
+                    }),
+                    Event::Text(CowStr::from(title.clone())),
+                    Event::End(TagEnd::Heading(HeadingLevel::H2)),
+                    Event::Start(Tag::List(None)),
+                ]);
+                for link in links {
+                    events.push(Event::Start(Tag::Item));
+                    events.extend(link);
+                    events.push(Event::End(TagEnd::Item));
+                }
+                events.push(Event::End(TagEnd::List(false)));
+            }
+        }
     }
 
-    #[allow(clippy::too_many_lines)]
-    #[allow(clippy::panic_in_result_fn)]
-    #[allow(clippy::shadow_unrelated)]
     fn parse_obsidian_note<'b>(
         &self,
         path: &Path,
-        context: &Context,
+        context: &mut Context,
+        preprocessors: &[&Preprocessor<'_>],
     ) -> Result<(Frontmatter, MarkdownEvents<'b>)> {
         if context.note_depth() > NOTE_RECURSION_LIMIT {
             return Err(ExportError::RecursionLimitExceeded {
                 file_tree: context.file_tree(),
             });
         }
-        let content = fs::read_to_string(path).context(ReadSnafu { path })?;
+        let content = encoding::read_to_string(path, self.encoding_fallback)?;
+        self.parse_obsidian_note_content(path, content, context, preprocessors)
+    }
+
+    // Parse `content` as the contents of the note at `path`.
+    #[allow(clippy::too_many_lines)]
+    #[allow(clippy::panic_in_result_fn)]
+    #[allow(clippy::shadow_unrelated)]
+    fn parse_obsidian_note_content<'b>(
+        &self,
+        path: &Path,
+        mut content: String,
+        context: &mut Context,
+        preprocessors: &[&Preprocessor<'_>],
+    ) -> Result<(Frontmatter, MarkdownEvents<'b>)> {
+        if self.normalize_line_endings {
+            content = content.replace("\r\n", "\n");
+        }
+        let source_content =
+            (!preprocessors.is_empty() || self.lenient_frontmatter).then(|| content.clone());
+        for func in preprocessors {
+            func(context, &mut content);
+        }
         let mut frontmatter = String::new();
+        if self.lenient_frontmatter {
+            if let Some((yaml, rest)) = split_leading_frontmatter(&content) {
+                frontmatter = yaml;
+                content = rest;
+            }
+        }
 
         let parser_options = Options::ENABLE_TABLES
             | Options::ENABLE_FOOTNOTES
@@ -519,93 +3021,196 @@ impl<'a> Exporter<'a> {
                 ref_parser.reset();
             }
             buffer.push(event.clone());
-            match ref_parser.state {
-                RefParserState::NoState => {
-                    match event {
-                        Event::Text(CowStr::Borrowed("![")) => {
-                            ref_parser.ref_type = Some(RefType::Embed);
-                            ref_parser.transition(RefParserState::ExpectSecondOpenBracket);
-                        }
-                        Event::Text(CowStr::Borrowed("[")) => {
-                            ref_parser.ref_type = Some(RefType::Link);
-                            ref_parser.transition(RefParserState::ExpectSecondOpenBracket);
-                        }
-                        _ => {
-                            events.push(event);
-                            buffer.clear();
-                        },
-                    };
+            if !ref_parser.feed(&event) {
+                if ref_parser.state == RefParserState::NoState {
+                    events.append(&mut buffer);
                 }
-                RefParserState::ExpectSecondOpenBracket => match event {
-                    Event::Text(CowStr::Borrowed("[")) => {
-                        ref_parser.transition(RefParserState::ExpectRefText);
-                    }
-                    _ => {
-                        ref_parser.transition(RefParserState::Resetting);
-                    }
-                },
-                RefParserState::ExpectRefText => match event {
-                    Event::Text(CowStr::Borrowed("]")) => {
-                        ref_parser.transition(RefParserState::Resetting);
-                    }
-                    Event::Text(text) => {
-                        ref_parser.ref_text.push_str(&text);
-                        ref_parser.transition(RefParserState::ExpectRefTextOrCloseBracket);
-                    }
-                    _ => {
-                        ref_parser.transition(RefParserState::Resetting);
-                    }
-                },
-                RefParserState::ExpectRefTextOrCloseBracket => match event {
-                    Event::Text(CowStr::Borrowed("]")) => {
-                        ref_parser.transition(RefParserState::ExpectFinalCloseBracket);
-                    }
-                    Event::Text(text) => {
-                        ref_parser.ref_text.push_str(&text);
-                    }
-                    _ => {
-                        ref_parser.transition(RefParserState::Resetting);
-                    }
-                },
-                RefParserState::ExpectFinalCloseBracket => match event {
-                    Event::Text(CowStr::Borrowed("]")) => match ref_parser.ref_type {
-                        Some(RefType::Link) => {
-                            let mut elements = self.make_link_to_file(
-                                ObsidianNoteReference::from_str(
-                                    ref_parser.ref_text.clone().as_ref()
-                                ),
-                                context,
-                            );
-                            events.append(&mut elements);
-                            buffer.clear();
-                            ref_parser.transition(RefParserState::Resetting);
-                        }
-                        Some(RefType::Embed) => {
-                            let mut elements = self.embed_file(
-                                ref_parser.ref_text.clone().as_ref(),
-                                context
-                            )?;
-                            events.append(&mut elements);
-                            buffer.clear();
-                            ref_parser.transition(RefParserState::Resetting);
-                        }
-                        None => panic!("In state ExpectFinalCloseBracket but ref_type is None"),
-                    },
-                    _ => {
-                        ref_parser.transition(RefParserState::Resetting);
-                    }
-                },
-                RefParserState::Resetting => panic!("Reached Resetting state, but it should have been handled prior to this match block"),
+                continue;
+            }
+            // Links to sections of the note itself are resolved against the note's own headings,
+            // which are parsed once the first such link is found. This uses the content being
+            // exported (after preprocessors) rather than the file on disk.
+            if context.note_anchors().is_none()
+                && ObsidianNoteReference::from_str(&ref_parser.ref_text)
+                    .section
+                    .is_some()
+            {
+                context.set_note_anchors(NoteAnchors::parse(&content));
+            }
+            match ref_parser.ref_type {
+                _ if self.preserve_wikilinks => {
+                    // Emitted as inline HTML because text events would have their brackets
+                    // escaped when rendered back to markdown.
+                    events.push(Event::InlineHtml(CowStr::from(
+                        self.preserved_wikilink(&ref_parser, context),
+                    )));
+                }
+                Some(RefType::Link) => {
+                    let mut elements = self.make_link_to_file(
+                        ObsidianNoteReference::from_str(&ref_parser.ref_text),
+                        context,
+                    );
+                    events.append(&mut elements);
+                }
+                Some(RefType::Embed) => {
+                    context.set_heading_level(last_heading_level(&events));
+                    let mut elements = self.embed_file(&ref_parser.ref_text, context)?;
+                    events.append(&mut elements);
+                }
+                None => panic!("In state ExpectFinalCloseBracket but ref_type is None"),
             }
+            buffer.clear();
+            ref_parser.transition(RefParserState::Resetting);
         }
         if !buffer.is_empty() {
             events.append(&mut buffer);
         }
 
-        Ok((
-            frontmatter_from_str(&frontmatter).context(FrontMatterDecodeSnafu { path })?,
-            events.into_iter().map(event_to_owned).collect(),
-        ))
+        let (frontmatter, ignored) =
+            frontmatter_from_str_with_fallback(&frontmatter, &self.frontmatter_fallback)
+                .context(FrontMatterDecodeSnafu { path })?;
+        if let Some(value) = ignored {
+            eprintln!(
+                "Warning: Ignoring frontmatter which is not a mapping\n\tValue: {:?}\n\tSource: '{}'\n",
+                value,
+                path.display(),
+            );
+        }
+
+        let events = events.into_iter().map(event_to_owned).collect();
+        context.set_source_content(source_content.unwrap_or(content));
+        Ok((frontmatter, events))
+    }
+
+    // Rebuild the wikilink for the reference held by `ref_parser`, resolving it to the full path
+    // of the referenced file if enabled. A warning is printed when the file can't be found.
+    fn preserved_wikilink(&self, ref_parser: &RefParser, context: &Context) -> String {
+        let prefix = match ref_parser.ref_type {
+            Some(RefType::Embed) => "!",
+            _ => "",
+        };
+        let reference = ObsidianNoteReference::from_str(&ref_parser.ref_text);
+        if let Some(file) = reference.file {
+            if self.lookup_note(file).is_none() {
+                eprintln!(
+                    "Warning: Unable to find referenced note\n\tReference: '{}'\n\tSource: '{}'\n",
+                    file,
+                    context.current_file().display(),
+                );
+            }
+        }
+        self.resolve_preserved_wikilinks
+            .then(|| self.resolved_wikilink(ref_parser, prefix))
+            .flatten()
+            .unwrap_or_else(|| format!("{prefix}[[{}]]", ref_parser.ref_text))
+    }
+
+    // Build a wikilink for the reference held by `ref_parser` which points to the full path of
+    // the referenced file, or `None` if the reference can't be resolved.
+    fn resolved_wikilink(&self, ref_parser: &RefParser, prefix: &str) -> Option<String> {
+        let reference = ObsidianNoteReference::from_str(&ref_parser.ref_text);
+        let path = self.lookup_note(reference.file?)?;
+
+        let mut target = path
+            .strip_prefix(self.vault_root())
+            .ok()?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if self.is_markdown_file(path) {
+            if let Some(stem_len) = target.rfind('.') {
+                target.truncate(stem_len);
+            }
+        }
+        if let Some(section) = reference.section {
+            target.push('#');
+            target.push_str(section);
+        }
+        if let Some(section_end) = reference.section_end {
+            target.push_str(SECTION_RANGE_MARKER);
+            target.push_str(section_end);
+        }
+
+        // Links keep displaying their original text by using it as the alias. Labels on embeds
+        // carry a different meaning (such as the size of an image), so those are only kept when
+        // they were given explicitly.
+        let label = match ref_parser.ref_type {
+            Some(RefType::Embed) => reference.label,
+            _ => reference.label.or(Some(ref_parser.ref_text.as_str())),
+        };
+        match label {
+            Some(label) if label != target => Some(format!("{prefix}[[{target}|{label}]]")),
+            _ => Some(format!("{prefix}[[{target}]]")),
+        }
+    }
+
+    // Return the root directory of the vault. This is the parent directory of `root` when a single
+    // file is being exported.
+    fn vault_root(&self) -> &Path {
+        if self.root.is_file() {
+            self.root.parent().unwrap_or(&self.root)
+        } else {
+            &self.root
+        }
+    }
+
+    // Like lookup_note, but attachments which no longer exist on disk (such as broken symlinks)
+    // aren't returned.
+    fn lookup_existing_file(&self, filename: &str) -> Option<&PathBuf> {
+        self.lookup_note(filename)
+            .filter(|path| self.is_markdown_file(path) || path.exists())
+    }
+
+    // Resolve a note reference to a file in the vault, taking folder notes into account when
+    // enabled.
+    fn lookup_note(&self, filename: &str) -> Option<&PathBuf> {
+        self.lookup_note_in(filename, self.vault_contents.as_ref().unwrap())
+    }
+
+    // Like lookup_note, but resolving `filename` to a file in `vault_contents`.
+    fn lookup_note_in<'v>(
+        &self,
+        filename: &str,
+        vault_contents: &'v [PathBuf],
+    ) -> Option<&'v PathBuf> {
+        let extensions = &self.markdown_extensions;
+        if let Some(daily_note) = self.lookup_daily_note_in(filename, vault_contents) {
+            return Some(daily_note);
+        }
+        if !self.folder_notes {
+            return lookup_filename_in_vault_with_extensions(filename, vault_contents, extensions);
+        }
+
+        let filename_normalized = filename.nfc().collect::<String>();
+        vault_contents
+            .iter()
+            .find(|path| {
+                path_matches_filename(path, &filename_normalized, extensions)
+                    && !is_self_named_folder_note(path)
+            })
+            .or_else(|| {
+                lookup_folder_note_in_vault(filename, vault_contents, &self.folder_note_index)
+            })
+            .or_else(|| {
+                lookup_filename_in_vault_with_extensions(filename, vault_contents, extensions)
+            })
+    }
+
+    // Resolve a date reference (like `2024-01-15`) to a note within the daily_note_folder, if set.
+    fn lookup_daily_note_in<'v>(
+        &self,
+        filename: &str,
+        vault_contents: &'v [PathBuf],
+    ) -> Option<&'v PathBuf> {
+        let folder = self.vault_root().join(self.daily_note_folder.as_ref()?);
+        if !is_date(filename) {
+            return None;
+        }
+        vault_contents.iter().find(|path| {
+            path.starts_with(&folder)
+                && path_matches_filename(path, filename, &self.markdown_extensions)
+        })
     }
 
     // Generate markdown elements for a file that is embedded within another note.
@@ -613,15 +3218,11 @@ impl<'a> Exporter<'a> {
     // - If the file being embedded is a note, it's content is included at the point of embed.
     // - If the file is an image, an image tag is generated.
     // - For other types of file, a regular link is created instead.
-    fn embed_file<'b>(
-        &self,
-        link_text: &'a str,
-        context: &'a Context,
-    ) -> Result<MarkdownEvents<'b>> {
+    fn embed_file<'b>(&self, link_text: &str, context: &Context) -> Result<MarkdownEvents<'b>> {
         let note_ref = ObsidianNoteReference::from_str(link_text);
 
         let path = match note_ref.file {
-            Some(file) => lookup_filename_in_vault(file, self.vault_contents.as_ref().unwrap()),
+            Some(file) => self.lookup_existing_file(file),
 
             // If we have None file it is either to a section or id within the same file and thus
             // the current embed logic will fail, recurssing until it reaches it's limit.
@@ -630,97 +3231,521 @@ impl<'a> Exporter<'a> {
         };
 
         if path.is_none() {
-            // TODO: Extract into configurable function.
-            eprintln!(
-                "Warning: Unable to find embedded note\n\tReference: '{}'\n\tSource: '{}'\n",
-                note_ref
-                    .file
-                    .unwrap_or_else(|| context.current_file().to_str().unwrap()),
-                context.current_file().display(),
+            let file = note_ref
+                .file
+                .unwrap_or_else(|| context.current_file().to_str().unwrap());
+            self.report_missing_file(file, "embedded note", context);
+            return Ok(match self.missing_embed_style {
+                MissingEmbedStyle::Remove => vec![],
+                // Emitted as inline HTML because text events would have their brackets escaped
+                // when rendered back to markdown.
+                MissingEmbedStyle::Literal => {
+                    vec![Event::InlineHtml(CowStr::from(format!("![[{link_text}]]")))]
+                }
+                MissingEmbedStyle::Emphasis => vec![
+                    Event::Start(Tag::Emphasis),
+                    Event::Text(CowStr::from(note_ref.display())),
+                    Event::End(TagEnd::Emphasis),
+                ],
+            });
+        }
+
+        let path = path.unwrap();
+        if self.is_skipped_attachment(path) {
+            return Ok(vec![Event::Text(CowStr::from(note_ref.display()))]);
+        }
+        if self.collects_links() {
+            context.record_link(path);
+        }
+        let mut child_context = Context::from_parent(context, path);
+        let no_ext = OsString::new();
+
+        if !self.process_embeds_recursively && context.file_tree().contains(path) {
+            return Ok(self.make_embed_fallback_link(note_ref, &child_context));
+        }
+
+        let is_note = self.is_markdown_file(path);
+        let events = match path.extension().unwrap_or(&no_ext).to_str() {
+            Some(_)
+                if is_note
+                    && self.deduplicate_embeds
+                    && !context.record_embedded_note(path, note_ref.section) =>
+            {
+                self.make_embed_fallback_link(note_ref, &child_context)
+            }
+            Some(_) if is_note && self.embed_limit_reached(link_text, context) => {
+                self.make_embed_fallback_link(note_ref, &child_context)
+            }
+            Some(_) if is_note => {
+                self.embed_note_with_limits(path, note_ref, link_text, context, &mut child_context)?
+            }
+            Some(ext) if is_extension_in(ext, IMAGE_EXTENSIONS) => {
+                self.make_link_to_file(note_ref, &child_context)
+                    .into_iter()
+                    .map(|event| match event {
+                        // make_link_to_file returns a link to a file. With this we turn the link
+                        // into an image reference instead. Slightly hacky, but avoids needing
+                        // to keep another utility function around for this, or introducing an
+                        // extra parameter on make_link_to_file.
+                        Event::Start(Tag::Link {
+                            link_type,
+                            dest_url,
+                            title,
+                            id,
+                        }) => Event::Start(Tag::Image {
+                            link_type,
+                            dest_url: CowStr::from(dest_url.into_string()),
+                            title: CowStr::from(title.into_string()),
+                            id: CowStr::from(id.into_string()),
+                        }),
+                        Event::End(TagEnd::Link) => Event::End(TagEnd::Image),
+                        _ => event,
+                    })
+                    .collect()
+            }
+            Some(ext) if is_extension_in(ext, AUDIO_EXTENSIONS) => {
+                self.embed_media("audio", note_ref, &child_context)
+            }
+            Some(ext) if is_extension_in(ext, VIDEO_EXTENSIONS) => {
+                self.embed_media("video", note_ref, &child_context)
+            }
+            _ => self.make_link_to_file(note_ref, &child_context),
+        };
+        Ok(events)
+    }
+
+    // Generate markdown elements for an embedded note, reduced to the referenced section (if any).
+    fn embed_note<'b>(
+        &self,
+        path: &Path,
+        note_ref: ObsidianNoteReference<'_>,
+        link_text: &str,
+        context: &Context,
+        child_context: &mut Context,
+    ) -> Result<MarkdownEvents<'b>> {
+        let (frontmatter, mut events) =
+            self.parse_obsidian_note(path, child_context, &self.embed_preprocessors)?;
+        child_context.frontmatter = frontmatter;
+        if let Some(section) = note_ref.section {
+            events = match note_ref.section_end {
+                Some(section_end) => {
+                    reduce_to_section_range(events, section, section_end)
+                        .unwrap_or_else(|reason| {
+                            eprintln!(
+                                "Warning: Unable to embed section range\n\tReference: '{}'\n\tReason: {}\n\tSource: '{}'\n",
+                                link_text,
+                                reason,
+                                context.current_file().display(),
+                            );
+                            vec![]
+                        })
+                }
+                None => reduce_to_section(events, section),
+            };
+        }
+        if self.demote_embedded_headings {
+            demote_headings(&mut events);
+        }
+        let postprocessors = self
+            .embed_postprocessors
+            .iter()
+            .copied()
+            .chain(self.postprocessors_for_depth(child_context.note_depth()));
+        for func in postprocessors {
+            // Postprocessors running on embeds shouldn't be able to change frontmatter (or any
+            // other metadata), so we give them a clone of the context.
+            match func(child_context, &mut events) {
+                PostprocessorResult::StopHere => break,
+                PostprocessorResult::StopAndSkipNote => {
+                    events = vec![];
+                }
+                PostprocessorResult::Continue => (),
+            }
+        }
+        Ok(self.decorate_embedded_note(path, context, child_context, events))
+    }
+
+    // Add the title of an embedded note to its events according to embed_frontmatter_behavior.
+    fn decorate_embedded_note<'b>(
+        &self,
+        path: &Path,
+        context: &Context,
+        child_context: &Context,
+        events: MarkdownEvents<'b>,
+    ) -> MarkdownEvents<'b> {
+        if self.embed_frontmatter_behavior == EmbedFrontmatterBehavior::Drop || events.is_empty() {
+            return events;
+        }
+        let title = child_context
+            .frontmatter
+            .get("title")
+            .and_then(|value| value.as_str())
+            .map_or_else(
+                || {
+                    path.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                },
+                ToOwned::to_owned,
             );
-            return Ok(vec![]);
+        if self.embed_frontmatter_behavior == EmbedFrontmatterBehavior::Callout {
+            // The callout is rendered to markdown up front because blockquote events would be
+            // rendered with an empty first line, where Obsidian expects the callout type, and
+            // with the brackets around the callout type escaped.
+            let mut callout = format!("> [!note] {title}\n>");
+            for line in render_markdown(&events, Some(self.cmark_options.clone()))
+                .trim_end()
+                .lines()
+            {
+                callout.push_str("\n>");
+                if !line.is_empty() {
+                    callout.push(' ');
+                    callout.push_str(line);
+                }
+            }
+            return vec![Event::InlineHtml(CowStr::from(callout))];
+        }
+        let mut decorated = Vec::with_capacity(events.len().saturating_add(3));
+        let level = context
+            .heading_level()
+            .map_or(HeadingLevel::H1, demote_heading);
+        decorated.push(Event::Start(Tag::Heading {
+            level,
+            id: None,
+            classes: vec![],
+            attrs: vec![],
+        }));
+        decorated.push(Event::Text(CowStr::from(title)));
+        decorated.push(Event::End(TagEnd::Heading(level)));
+        decorated.extend(events);
+        decorated
+    }
+
+    // Return whether the root note has already reached max_embeds_per_note (after printing a
+    // warning), in which case the note referenced by link_text shouldn't be embedded.
+    fn embed_limit_reached(&self, link_text: &str, context: &Context) -> bool {
+        let Some(max_embeds) = self.max_embeds_per_note else {
+            return false;
+        };
+        if context.embed_count() < max_embeds {
+            return false;
+        }
+        eprintln!(
+            "Warning: Note exceeds limit of {} embeds, inserting link instead\n\tReference: '{}'\n\tSource: '{}'\n",
+            max_embeds,
+            link_text,
+            context.current_file().display(),
+        );
+        true
+    }
+
+    // Embed a note like embed_note, within max_embed_bytes (if set). The note is counted towards
+    // max_embeds_per_note only once it's actually included, otherwise a link to it is inserted.
+    fn embed_note_with_limits<'b>(
+        &self,
+        path: &Path,
+        note_ref: ObsidianNoteReference<'_>,
+        link_text: &str,
+        context: &Context,
+        child_context: &mut Context,
+    ) -> Result<MarkdownEvents<'b>> {
+        let embed_count = context.embed_count();
+        let embedded = match self.max_embed_bytes {
+            Some(max_bytes) => self.embed_note_within_limit(
+                max_bytes,
+                path,
+                note_ref,
+                link_text,
+                context,
+                child_context,
+            )?,
+            None => Some(self.embed_note(path, note_ref, link_text, context, child_context)?),
+        };
+        Ok(embedded.map_or_else(
+            || {
+                // The notes embedded by this note aren't included either, so they don't count
+                // towards max_embeds_per_note.
+                context.set_embed_count(embed_count);
+                self.make_embed_fallback_link(note_ref, child_context)
+            },
+            |events| {
+                // Notes embedded by this note have already been counted themselves.
+                context.set_embed_count(context.embed_count().saturating_add(1));
+                events
+            },
+        ))
+    }
+
+    // Embed a note like embed_note, unless the size of all content embedded into the root note
+    // would exceed max_bytes as a result, in which case None is returned (after printing a
+    // warning).
+    fn embed_note_within_limit<'b>(
+        &self,
+        max_bytes: usize,
+        path: &Path,
+        note_ref: ObsidianNoteReference<'_>,
+        link_text: &str,
+        context: &Context,
+        child_context: &mut Context,
+    ) -> Result<Option<MarkdownEvents<'b>>> {
+        let embedded_bytes = context.embedded_bytes();
+        if embedded_bytes < max_bytes {
+            let events = self.embed_note(path, note_ref, link_text, context, child_context)?;
+            // Any notes embedded by this note have already added their size to the total, but
+            // they're also part of the rendered events, so the total is reset rather than added
+            // to in order to avoid counting them twice.
+            // Using max also ensures the total stays over the limit when one of those nested
+            // embeds already exceeded it.
+            let total = embedded_bytes
+                .saturating_add(render_markdown(&events, None).len())
+                .max(context.embedded_bytes());
+            context.set_embedded_bytes(total);
+            if total <= max_bytes {
+                return Ok(Some(events));
+            }
+        }
+        eprintln!(
+            "Warning: Embedded content exceeds size limit of {} bytes, inserting link instead\n\tReference: '{}'\n\tSource: '{}'\n",
+            max_bytes,
+            link_text,
+            context.current_file().display(),
+        );
+        Ok(None)
+    }
+
+    // Generate the link which replaces an embed that isn't included (to break a cycle, or because
+    // it's a duplicate), prefixed with `embed_cycle_prefix`.
+    fn make_embed_fallback_link<'b>(
+        &self,
+        note_ref: ObsidianNoteReference<'_>,
+        context: &Context,
+    ) -> MarkdownEvents<'b> {
+        let mut events = Vec::with_capacity(4);
+        if let Some(prefix) = &self.embed_cycle_prefix {
+            events.push(Event::Text(CowStr::from(prefix.clone())));
+        }
+        events.append(&mut self.make_link_to_file(note_ref, context));
+        events
+    }
+
+    // Generate an HTML media element (`<audio>` or `<video>`) for an embedded media file.
+    fn embed_media<'b>(
+        &self,
+        element: &str,
+        note_ref: ObsidianNoteReference<'_>,
+        context: &Context,
+    ) -> MarkdownEvents<'b> {
+        // Like with images, the link from make_link_to_file is turned into an HTML media element,
+        // so that the source path is built exactly the same way.
+        self.make_link_to_file(note_ref, context)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link { dest_url, .. }) => {
+                    Some(Event::InlineHtml(CowStr::from(format!(
+                        r#"<{element} controls src="{}"></{element}>"#,
+                        escape_html_attribute(&dest_url)
+                    ))))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Build a link to `target_file` which is relative to the root of the export, prefixed with
+    // `link_base`.
+    fn link_from_base(&self, target_file: &Path, link_base: &str) -> String {
+        let path = self.relative_to_start_at(target_file).map_or_else(
+            || {
+                let start_at = self
+                    .start_at
+                    .first()
+                    .expect("there should always be at least one start_at path");
+                let export_root = if start_at.is_file {
+                    start_at
+                        .path
+                        .parent()
+                        .expect("obsidian content files should always have a parent")
+                } else {
+                    start_at.path.as_path()
+                };
+                diff_paths(target_file, export_root).expect(
+                    "should be able to build relative path when target file is found in vault",
+                )
+            },
+            |relative_path| self.flattened_path(relative_path),
+        );
+        format!(
+            "{}/{}",
+            link_base.trim_end_matches('/'),
+            utf8_percent_encode(&path.to_string_lossy(), PERCENTENCODE_CHARS)
+        )
+    }
+
+    // Build a (percent-encoded) link to `target_file` from the note described by `context`.
+    fn link_to_file(&self, target_file: &Path, context: &Context) -> String {
+        if let Some(dest) = self.external_attachment_destination(target_file) {
+            return self.link_to_external_attachment(&dest, context);
+        }
+        let mut link = self.link_base.as_deref().map_or_else(
+            || {
+                self.flattened_link(target_file)
+                    .or_else(|| self.link_between_start_at_paths(target_file, context))
+                    .unwrap_or_else(|| {
+                        relative_link_to_file(target_file, context, self.lowercase_output)
+                    })
+            },
+            |link_base| self.link_from_base(target_file, link_base),
+        );
+        // Rendered canvas files are exported with a .md extension instead.
+        if self.is_rendered_canvas(target_file) {
+            if let Some(stem_len) = link.len().checked_sub(".canvas".len()) {
+                link.truncate(stem_len);
+                link.push_str(".md");
+            }
         }
+        link
+    }
+
+    // Build a link to `target_file` when flatten_output is enabled. Since all files are exported
+    // into the same directory, this is just the flattened filename.
+    fn flattened_link(&self, target_file: &Path) -> Option<String> {
+        self.flatten_separator.as_ref()?;
+        let link = self.flattened_path(self.relative_to_start_at(target_file)?);
+        Some(utf8_percent_encode(&link.to_string_lossy(), PERCENTENCODE_CHARS).to_string())
+    }
 
-        let path = path.unwrap();
-        let mut child_context = Context::from_parent(context, path);
-        let no_ext = OsString::new();
+    // Build a relative link to `target_file` based on the destinations of the linked files rather
+    // than their source paths. This is only needed (and done) when exporting multiple start_at
+    // paths, in which case files under different start_at paths may end up next to each other.
+    fn link_between_start_at_paths(&self, target_file: &Path, context: &Context) -> Option<String> {
+        if self.start_at.len() < 2 {
+            return None;
+        }
+        let dest = self
+            .destination
+            .join(self.flattened_path(self.relative_to_start_at(target_file)?));
+        let root_dest = self
+            .destination
+            .join(self.flattened_path(self.relative_to_start_at(context.root_file())?));
+        let link = diff_paths(dest, root_dest.parent()?)?;
+        Some(utf8_percent_encode(&link.to_string_lossy(), PERCENTENCODE_CHARS).to_string())
+    }
 
-        if !self.process_embeds_recursively && context.file_tree().contains(path) {
-            return Ok([
-                vec![Event::Text(CowStr::Borrowed("→ "))],
-                self.make_link_to_file(note_ref, &child_context),
-            ]
-            .concat());
+    // Build a link to an attachment which is copied to `dest` in the external_attachments_dir.
+    fn link_to_external_attachment(&self, dest: &Path, context: &Context) -> String {
+        let base = self.link_base.as_ref().map_or_else(
+            || context.destination.parent(),
+            |_| Some(self.export_root()),
+        );
+        let path = base
+            .and_then(|dir| diff_paths(dest, dir))
+            .unwrap_or_else(|| dest.to_owned());
+        let link = utf8_percent_encode(&path.to_string_lossy(), PERCENTENCODE_CHARS).to_string();
+        match &self.link_base {
+            Some(link_base) => format!("{}/{link}", link_base.trim_end_matches('/')),
+            None => link,
         }
+    }
 
-        let events = match path.extension().unwrap_or(&no_ext).to_str() {
-            Some("md") => {
-                let (frontmatter, mut events) = self.parse_obsidian_note(path, &child_context)?;
-                child_context.frontmatter = frontmatter;
-                if let Some(section) = note_ref.section {
-                    events = reduce_to_section(events, section);
-                }
-                for func in &self.embed_postprocessors {
-                    // Postprocessors running on embeds shouldn't be able to change frontmatter (or
-                    // any other metadata), so we give them a clone of the context.
-                    match func(&mut child_context, &mut events) {
-                        PostprocessorResult::StopHere => break,
-                        PostprocessorResult::StopAndSkipNote => {
-                            events = vec![];
-                        }
-                        PostprocessorResult::Continue => (),
-                    }
+    // Return the anchors of the headings of `note`, or None when `note` isn't a markdown note.
+    // The anchors of the current note come from the note being exported, those of other notes
+    // from the anchor cache. A note which can't be read is treated as having no headings.
+    fn note_anchors(&self, note: &Path, context: &Context) -> Option<Arc<NoteAnchors>> {
+        if note == context.current_file() {
+            return context.note_anchors();
+        }
+        if !self.is_markdown_file(note) {
+            return None;
+        }
+        Some(self.anchor_cache.get(note, || {
+            match encoding::read_to_string(note, self.encoding_fallback) {
+                Ok(content) => Some(content),
+                Err(err) => {
+                    eprintln!(
+                        "Warning: Unable to read headings of linked note\n\tFile: '{}'\n\tError: {}\n",
+                        note.display(),
+                        err,
+                    );
+                    None
                 }
-                events
-            }
-            Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "svg") => {
-                self.make_link_to_file(note_ref, &child_context)
-                    .into_iter()
-                    .map(|event| match event {
-                        // make_link_to_file returns a link to a file. With this we turn the link
-                        // into an image reference instead. Slightly hacky, but avoids needing
-                        // to keep another utility function around for this, or introducing an
-                        // extra parameter on make_link_to_file.
-                        Event::Start(Tag::Link {
-                            link_type,
-                            dest_url,
-                            title,
-                            id,
-                        }) => Event::Start(Tag::Image {
-                            link_type,
-                            dest_url: CowStr::from(dest_url.into_string()),
-                            title: CowStr::from(title.into_string()),
-                            id: CowStr::from(id.into_string()),
-                        }),
-                        Event::End(TagEnd::Link) => Event::End(TagEnd::Image),
-                        _ => event,
-                    })
-                    .collect()
             }
-            _ => self.make_link_to_file(note_ref, &child_context),
+        }))
+    }
+
+    // Warn about a link to `anchor` in `target_file` when validate_section_links or
+    // strict_section_links is enabled and the note doesn't have a heading with that anchor. With
+    // strict_section_links, the link is recorded to fail the export with once it's finished.
+    fn check_section_anchor(
+        &self,
+        target_file: &Path,
+        anchor: &str,
+        reference: &ObsidianNoteReference<'_>,
+        context: &Context,
+    ) {
+        if !self.validate_section_links && !self.strict_section_links {
+            return;
+        }
+        let Some(anchors) = self.note_anchors(target_file, context) else {
+            return;
         };
-        Ok(events)
+        if anchors.contains(anchor) {
+            return;
+        }
+        eprintln!(
+            "Warning: Link to a section which doesn't exist\n\tReference: '{}'\n\tSource: '{}'\n",
+            reference.display(),
+            context.current_file().display(),
+        );
+        if self.strict_section_links {
+            self.broken_section_links
+                .lock()
+                .expect("broken_section_links mutex should not be poisoned")
+                .push(BrokenLink {
+                    source: context.current_file().clone(),
+                    reference: reference_text(reference),
+                    kind: RefType::Link,
+                    reason: BrokenLinkReason::MissingSection,
+                });
+        }
     }
 
     fn make_link_to_file<'c>(
         &self,
         reference: ObsidianNoteReference<'_>,
         context: &Context,
+    ) -> MarkdownEvents<'c> {
+        self.link_events(reference, context, true)
+    }
+
+    // Like make_link_to_file, but for links generated by the exporter (such as backlinks) rather
+    // than written in the note, which aren't recorded as links of the note.
+    fn make_generated_link_to_file<'c>(
+        &self,
+        reference: ObsidianNoteReference<'_>,
+        context: &Context,
+    ) -> MarkdownEvents<'c> {
+        self.link_events(reference, context, false)
+    }
+
+    // Generate the events of a link to the file (or section) `reference` refers to, recording it
+    // as a link of the current note when `record` is true.
+    fn link_events<'c>(
+        &self,
+        reference: ObsidianNoteReference<'_>,
+        context: &Context,
+        record: bool,
     ) -> MarkdownEvents<'c> {
         let target_file = reference.file.map_or_else(
             || Some(context.current_file()),
-            |file| lookup_filename_in_vault(file, self.vault_contents.as_ref().unwrap()),
+            |file| self.lookup_existing_file(file),
         );
 
         if target_file.is_none() {
-            // TODO: Extract into configurable function.
-            eprintln!(
-                "Warning: Unable to find referenced note\n\tReference: '{}'\n\tSource: '{}'\n",
-                reference
-                    .file
-                    .unwrap_or_else(|| context.current_file().to_str().unwrap()),
-                context.current_file().display(),
-            );
+            let file = reference
+                .file
+                .unwrap_or_else(|| context.current_file().to_str().unwrap());
+            self.report_missing_file(file, "referenced note", context);
             return vec![
                 Event::Start(Tag::Emphasis),
                 Event::Text(CowStr::from(reference.display())),
@@ -728,24 +3753,22 @@ impl<'a> Exporter<'a> {
             ];
         }
         let target_file = target_file.unwrap();
-        // We use root_file() rather than current_file() here to make sure links are always
-        // relative to the outer-most note, which is the note which this content is inserted into
-        // in case of embedded notes.
-        let rel_link = diff_paths(
-            target_file,
-            context
-                .root_file()
-                .parent()
-                .expect("obsidian content files should always have a parent"),
-        )
-        .expect("should be able to build relative path when target file is found in vault");
-
-        let rel_link = rel_link.to_string_lossy();
-        let mut link = utf8_percent_encode(&rel_link, PERCENTENCODE_CHARS).to_string();
+        if self.is_skipped_attachment(target_file) {
+            return vec![Event::Text(CowStr::from(reference.display()))];
+        }
+        if record && self.collects_links() {
+            context.record_link(target_file);
+        }
+        let mut link = self.link_to_file(target_file, context);
 
         if let Some(section) = reference.section {
+            let anchor =
+                section_anchor(self.note_anchors(target_file, context).as_deref(), section);
+            if reference.file.is_some() && !section.starts_with('^') {
+                self.check_section_anchor(target_file, &anchor, &reference, context);
+            }
             link.push('#');
-            link.push_str(&slugify(section));
+            link.push_str(&anchor);
         }
 
         let link_tag = Tag::Link {
@@ -755,14 +3778,48 @@ impl<'a> Exporter<'a> {
             id: CowStr::from(""),
         };
 
-        vec![
-            Event::Start(link_tag),
-            Event::Text(CowStr::from(reference.display())),
-            Event::End(TagEnd::Link),
-        ]
+        let mut events = vec![Event::Start(link_tag)];
+        events.extend(link_label_events(&reference.display()));
+        events.push(Event::End(TagEnd::Link));
+        events
     }
 }
 
+// Return the files referenced by the note with `content`, as found in wikilinks, embeds and
+// Markdown links. References to sections within the same note and to URLs are left out.
+fn note_references(content: &str) -> Vec<String> {
+    let mut references: Vec<String> = extract_references(content)
+        .into_iter()
+        .filter_map(|(_, reference)| reference.file.map(str::to_owned))
+        .collect();
+    references.extend(markdown_link_targets(content));
+    references
+}
+
+// Return the (percent-decoded) paths of the files linked to by the Markdown links and images in
+// the note with `content`. Links to URLs or to sections within the same note are left out.
+fn markdown_link_targets(content: &str) -> Vec<String> {
+    let parser_options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_MATH
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    Parser::new_ext(content, parser_options)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. })
+                if !postprocessors::has_url_scheme(&dest_url) =>
+            {
+                let file = dest_url.split('#').next().unwrap_or_default();
+                (!file.is_empty())
+                    .then(|| percent_decode_str(file).decode_utf8_lossy().into_owned())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 /// Get the full path for the given filename when it's contained in `vault_contents`, taking into
 /// account:
 ///
@@ -772,49 +3829,210 @@ impl<'a> Exporter<'a> {
 fn lookup_filename_in_vault<'a>(
     filename: &str,
     vault_contents: &'a [PathBuf],
+) -> Option<&'a PathBuf> {
+    lookup_filename_in_vault_with_extensions(filename, vault_contents, &["md"])
+}
+
+/// Like [`lookup_filename_in_vault`], but references without an extension may match files with
+/// any of the given `extensions` instead of just `.md`.
+fn lookup_filename_in_vault_with_extensions<'a>(
+    filename: &str,
+    vault_contents: &'a [PathBuf],
+    extensions: &[impl AsRef<str>],
 ) -> Option<&'a PathBuf> {
     let filename = PathBuf::from(filename);
     let filename_normalized = filename.to_string_lossy().nfc().collect::<String>();
 
-    vault_contents.iter().find(|path| {
-        let path_normalized_str = path.to_string_lossy().nfc().collect::<String>();
-        let path_normalized = PathBuf::from(&path_normalized_str);
-        let path_normalized_lowered = PathBuf::from(&path_normalized_str.to_lowercase());
+    vault_contents
+        .iter()
+        .find(|path| path_matches_filename(path, &filename_normalized, extensions))
+}
+
+/// Check whether `path` matches the (NFC-normalized) note reference `filename_normalized`,
+/// following the same rules as [`lookup_filename_in_vault`].
+fn path_matches_filename(
+    path: &Path,
+    filename_normalized: &str,
+    extensions: &[impl AsRef<str>],
+) -> bool {
+    let path_normalized_str = path.to_string_lossy().nfc().collect::<String>();
+    let path_normalized = PathBuf::from(&path_normalized_str);
+    let path_normalized_lowered = PathBuf::from(&path_normalized_str.to_lowercase());
+    let filename_lowered = filename_normalized.to_lowercase();
+
+    // It would be convenient if we could just do `filename.set_extension("md")` at the start
+    // of this funtion so we don't need multiple separate + ".md" match cases here, however
+    // that would break with a reference of `[[Note.1]]` linking to `[[Note.1.md]]`.
+
+    path_normalized.ends_with(filename_normalized)
+        || path_normalized_lowered.ends_with(&filename_lowered)
+        || extensions.iter().any(|ext| {
+            let ext = ext.as_ref();
+            path_normalized.ends_with(format!("{filename_normalized}.{ext}"))
+                || path_normalized_lowered.ends_with(format!("{filename_lowered}.{ext}"))
+        })
+}
+
+/// Check whether the file extension `ext` is one of `extensions`, ignoring case.
+fn is_extension_in(ext: &str, extensions: &[&str]) -> bool {
+    extensions
+        .iter()
+        .any(|known| ext.eq_ignore_ascii_case(known))
+}
 
-        // It would be convenient if we could just do `filename.set_extension("md")` at the start
-        // of this funtion so we don't need multiple separate + ".md" match cases here, however
-        // that would break with a reference of `[[Note.1]]` linking to `[[Note.1.md]]`.
+/// Check whether `text` is a date in the `YYYY-MM-DD` format used to name daily notes.
+fn is_date(text: &str) -> bool {
+    text.len() == 10
+        && text.char_indices().all(|(index, char)| match index {
+            4 | 7 => char == '-',
+            _ => char.is_ascii_digit(),
+        })
+}
 
-        path_normalized.ends_with(&filename_normalized)
-            || path_normalized.ends_with(filename_normalized.clone() + ".md")
-            || path_normalized_lowered.ends_with(filename_normalized.to_lowercase())
-            || path_normalized_lowered.ends_with(filename_normalized.to_lowercase() + ".md")
+/// Check whether `path` is a note named after the folder it is stored in, such as
+/// `Folder/Folder.md`.
+fn is_self_named_folder_note(path: &Path) -> bool {
+    path.file_stem().is_some_and(|stem| {
+        path.parent()
+            .and_then(Path::file_name)
+            .is_some_and(|folder| folder == stem)
     })
 }
 
-fn render_mdevents_to_mdtext(markdown: &MarkdownEvents<'_>) -> String {
-    let mut buffer = String::new();
-    cmark_with_options(
-        markdown.iter(),
-        &mut buffer,
-        pulldown_cmark_to_cmark::Options::default(),
+/// Look up the folder note for the folder referenced by `filename`.
+///
+/// A note named after the folder (`Folder/Folder.md`) takes precedence over an index note
+/// (`Folder/<index_filename>`).
+fn lookup_folder_note_in_vault<'a>(
+    filename: &str,
+    vault_contents: &'a [PathBuf],
+    index_filename: &str,
+) -> Option<&'a PathBuf> {
+    let folder_normalized = filename
+        .trim_end_matches('/')
+        .nfc()
+        .collect::<String>()
+        .to_lowercase();
+    let in_folder = |path: &&PathBuf| {
+        path.parent().is_some_and(|parent| {
+            PathBuf::from(
+                parent
+                    .to_string_lossy()
+                    .nfc()
+                    .collect::<String>()
+                    .to_lowercase(),
+            )
+            .ends_with(&folder_normalized)
+        })
+    };
+
+    vault_contents
+        .iter()
+        .filter(in_folder)
+        .find(|path| is_self_named_folder_note(path))
+        .or_else(|| {
+            vault_contents.iter().filter(in_folder).find(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(index_filename))
+            })
+        })
+}
+
+/// Build a (percent-encoded) link to `target_file` which is relative to the note described by
+/// `context`, lowercasing it when `lowercase` is true.
+fn relative_link_to_file(target_file: &Path, context: &Context, lowercase: bool) -> String {
+    // We use root_file() rather than current_file() here to make sure links are always
+    // relative to the outer-most note, which is the note which this content is inserted into
+    // in case of embedded notes.
+    let rel_link = diff_paths(
+        target_file,
+        context
+            .root_file()
+            .parent()
+            .expect("obsidian content files should always have a parent"),
     )
-    .expect("formatting to string not expected to fail");
+    .expect("should be able to build relative path when target file is found in vault");
+
+    let rel_link = match lowercase {
+        true => rel_link.to_string_lossy().to_lowercase(),
+        false => rel_link.to_string_lossy().into_owned(),
+    };
+    utf8_percent_encode(&rel_link, PERCENTENCODE_CHARS).to_string()
+}
+
+/// Render [`MarkdownEvents`] back into markdown text.
+///
+/// This is the same rendering that is used when writing exported notes, so it may be used to
+/// inspect the output of [postprocessors][Postprocessor] without running a full export.
+///
+/// When `options` is `None`, the default [`pulldown_cmark_to_cmark::Options`] are used.
+///
+/// # Example
+///
+/// ```
+/// use obsidian_export::pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+/// use obsidian_export::render_markdown;
+///
+/// let events = vec![
+///     Event::Start(Tag::Paragraph),
+///     Event::Text(CowStr::Borrowed("Hello, ")),
+///     Event::Start(Tag::Strong),
+///     Event::Text(CowStr::Borrowed("world")),
+///     Event::End(TagEnd::Strong),
+///     Event::End(TagEnd::Paragraph),
+/// ];
+///
+/// assert_eq!(render_markdown(&events, None), "Hello, **world**\n");
+/// ```
+#[must_use]
+pub fn render_markdown(
+    markdown: &MarkdownEvents<'_>,
+    options: Option<pulldown_cmark_to_cmark::Options<'_>>,
+) -> String {
+    let mut buffer = String::new();
+    cmark_with_options(markdown.iter(), &mut buffer, options.unwrap_or_default())
+        .expect("formatting to string not expected to fail");
     buffer.push('\n');
     buffer
 }
 
-fn create_file(dest: &Path) -> Result<File> {
-    let file = File::create(dest)
-        .or_else(|err| {
-            if err.kind() == ErrorKind::NotFound {
-                let parent = dest.parent().expect("file should have a parent directory");
-                fs::create_dir_all(parent)?;
+/// Demote all headings in `events` by one level, leaving level 6 headings as-is.
+fn demote_headings(events: &mut MarkdownEvents<'_>) {
+    for event in events.iter_mut() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) | Event::End(TagEnd::Heading(level)) => {
+                *level = demote_heading(*level);
             }
-            File::create(dest)
-        })
-        .context(WriteSnafu { path: dest })?;
-    Ok(file)
+            _ => {}
+        }
+    }
+}
+
+// Return the heading level one below `level`, which stays at H6 once reached.
+const fn demote_heading(level: HeadingLevel) -> HeadingLevel {
+    match level {
+        HeadingLevel::H1 => HeadingLevel::H2,
+        HeadingLevel::H2 => HeadingLevel::H3,
+        HeadingLevel::H3 => HeadingLevel::H4,
+        HeadingLevel::H4 => HeadingLevel::H5,
+        HeadingLevel::H5 | HeadingLevel::H6 => HeadingLevel::H6,
+    }
+}
+
+// Return the level of the last heading in `events`, if there is one.
+fn last_heading_level(events: &[Event<'_>]) -> Option<HeadingLevel> {
+    events.iter().rev().find_map(|event| match event {
+        Event::Start(Tag::Heading { level, .. }) => Some(*level),
+        _ => None,
+    })
+}
+
+fn escape_html_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
 }
 
 fn copy_mtime(src: &Path, dest: &Path) -> Result<()> {
@@ -827,72 +4045,393 @@ fn copy_mtime(src: &Path, dest: &Path) -> Result<()> {
     Ok(())
 }
 
-fn copy_file(src: &Path, dest: &Path) -> Result<()> {
-    fs::copy(src, dest)
-        .or_else(|err| {
-            if err.kind() == ErrorKind::NotFound {
-                let parent = dest.parent().expect("file should have a parent directory");
-                fs::create_dir_all(parent)?;
+// Write `contents` to `dest`, unless `dest` already exists and mustn't be overwritten according to
+// `overwrite`. Returns whether the file was written.
+fn write_file(
+    dest: &Path,
+    contents: &[u8],
+    retries: usize,
+    overwrite: OverwritePolicy,
+) -> Result<bool> {
+    let mut policy = overwrite;
+    let result = retry_transient_errors(retries, || {
+        let mut file = create_file(dest, policy)?;
+        // Once created, the file is ours to overwrite when retrying.
+        policy = OverwritePolicy::Overwrite;
+        file.write_all(contents)
+    });
+    check_written(result, dest, overwrite)
+}
+
+// Create `dest` for writing, creating its parent directory when needed. Unless `overwrite` is
+// `OverwritePolicy::Overwrite`, this fails with `ErrorKind::AlreadyExists` when `dest` exists.
+// The check is done while creating the file, so that a file which is created concurrently (such
+// as another note exported to the same destination) isn't overwritten either.
+fn create_file(dest: &Path, overwrite: OverwritePolicy) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    match overwrite {
+        OverwritePolicy::Overwrite => options.write(true).create(true).truncate(true),
+        OverwritePolicy::Skip | OverwritePolicy::Error => options.write(true).create_new(true),
+    };
+    options.open(dest).or_else(|err| {
+        if err.kind() == ErrorKind::NotFound {
+            let parent = dest.parent().expect("file should have a parent directory");
+            fs::create_dir_all(parent)?;
+        }
+        options.open(dest)
+    })
+}
+
+// Turn the result of writing `dest` into whether the file was written. When `dest` already existed
+// and mustn't be overwritten according to `overwrite`, the file is either skipped or the write
+// fails with `DestinationExists`.
+fn check_written(
+    result: std::io::Result<()>,
+    dest: &Path,
+    overwrite: OverwritePolicy,
+) -> Result<bool> {
+    match (result, overwrite) {
+        (Ok(()), _) => Ok(true),
+        (Err(err), OverwritePolicy::Skip) if err.kind() == ErrorKind::AlreadyExists => Ok(false),
+        (Err(err), OverwritePolicy::Error) if err.kind() == ErrorKind::AlreadyExists => {
+            DestinationExistsSnafu { path: dest }.fail()
+        }
+        (Err(err), _) => Err(err).context(WriteSnafu { path: dest }),
+    }
+}
+
+// Remove the files under `dir` which aren't in `written`, skipping hidden files and directories as
+// well as the (canonical) `vault_root`. Subdirectories which end up empty are removed as well.
+// Returns whether `dir` ended up empty.
+fn remove_stale_files(dir: &Path, written: &HashSet<PathBuf>, vault_root: &Path) -> Result<bool> {
+    let mut is_empty = true;
+    for entry in fs::read_dir(dir).context(ReadSnafu { path: dir })? {
+        let entry = entry.context(ReadSnafu { path: dir })?;
+        let path = entry.path();
+        let file_type = entry.file_type().context(ReadSnafu { path: &path })?;
+        // The vault root is never descended into, even though check_clean_destination makes sure
+        // it can't be inside the destination, as a last line of defense against removing notes.
+        if entry.file_name().to_string_lossy().starts_with('.')
+            || written.contains(&path)
+            || (file_type.is_dir()
+                && fs::canonicalize(&path).is_ok_and(|canonical| canonical == vault_root))
+        {
+            is_empty = false;
+        } else if file_type.is_dir() {
+            if remove_stale_files(&path, written, vault_root)? {
+                fs::remove_dir(&path).context(WriteSnafu { path: &path })?;
+            } else {
+                is_empty = false;
+            }
+        } else {
+            fs::remove_file(&path).context(WriteSnafu { path: &path })?;
+        }
+    }
+    Ok(is_empty)
+}
+
+// Copy `src` to `dest` like write_file.
+fn copy_file(src: &Path, dest: &Path, retries: usize, overwrite: OverwritePolicy) -> Result<bool> {
+    let mut policy = overwrite;
+    let result = retry_transient_errors(retries, || {
+        // The file is created before copying, like in write_file, so that it's only written to
+        // when it may be according to `overwrite`.
+        create_file(dest, policy)?;
+        policy = OverwritePolicy::Overwrite;
+        fs::copy(src, dest).map(|_| ())
+    });
+    check_written(result, dest, overwrite)
+}
+
+/// Run `operation`, retrying it up to `retries` times (with exponential backoff) for as long as it
+/// fails with a transient error.
+fn retry_transient_errors<T>(
+    retries: usize,
+    mut operation: impl FnMut() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let mut backoff = Duration::from_millis(10);
+    for _ in 0..retries {
+        match operation() {
+            Err(err) if is_transient_error(&err) => {
+                thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
             }
-            fs::copy(src, dest)
+            result => return result,
+        }
+    }
+    operation()
+}
+
+/// Build the events for the text of a link labelled `label`.
+///
+/// Code spans (like `` `code` ``) and math (like `$x^2$`) in the label become inline code and
+/// inline math. Elsewhere, each bracket starts a new text event, as `pulldown_cmark_to_cmark`
+/// escapes special characters at the start of text events, so that brackets in the label can't
+/// end the text of the link early.
+fn link_label_events<'c>(label: &str) -> MarkdownEvents<'c> {
+    let mut events = vec![];
+    let mut rest = label;
+    loop {
+        // Whichever span starts first contains the other one, if any.
+        let next = match (split_code_span(rest), split_math_span(rest)) {
+            (Some(code), Some(math)) if math.0.len() < code.0.len() => Some(math),
+            (code, math) => code.or(math),
+        };
+        let Some((before, span, after)) = next else {
+            break;
+        };
+        push_label_text(&mut events, before);
+        events.push(span);
+        rest = after;
+    }
+    push_label_text(&mut events, rest);
+    events
+}
+
+/// Append `text` to `events` as done by [`link_label_events`], splitting it before each bracket.
+fn push_label_text(events: &mut MarkdownEvents<'_>, text: &str) {
+    let mut starts: Vec<usize> = text.match_indices(['[', ']']).map(|(idx, _)| idx).collect();
+    starts.insert(0, 0);
+    starts.push(text.len());
+    for part in starts
+        .windows(2)
+        .filter_map(|bounds| text.get(*bounds.first()?..*bounds.last()?))
+    {
+        if !part.is_empty() {
+            events.push(Event::Text(CowStr::from(part.to_owned())));
+        }
+    }
+}
+
+/// Split `text` around its first code span, returning the text before it, the code and the text
+/// after it. As in Markdown, a code span is delimited by two runs of the same number of
+/// backticks, and a single space is stripped from both sides of the code when it has one on each.
+fn split_code_span(text: &str) -> Option<(&str, Event<'static>, &str)> {
+    let backtick_runs = || {
+        text.match_indices('`').filter_map(|(idx, _)| {
+            let run = text.get(idx..)?;
+            let is_run_start = idx == 0 || !text.get(..idx)?.ends_with('`');
+            let len = run.len().saturating_sub(run.trim_start_matches('`').len());
+            is_run_start.then_some((idx, len))
         })
-        .context(WriteSnafu { path: dest })?;
-    Ok(())
+    };
+    backtick_runs().find_map(|(open, len)| {
+        let code_start = open.saturating_add(len);
+        let (close, _) = backtick_runs().find(|&(idx, run_len)| idx > open && run_len == len)?;
+        let code = text.get(code_start..close)?;
+        let code = match code.len() > 2 && code.starts_with(' ') && code.ends_with(' ') {
+            true => code.get(1..code.len().saturating_sub(1))?,
+            false => code,
+        };
+        Some((
+            text.get(..open)?,
+            Event::Code(CowStr::from(code.to_owned())),
+            text.get(close.saturating_add(len)..)?,
+        ))
+    })
+}
+
+/// Split `text` around its first inline math (`$math$`) like [`split_code_span`].
+fn split_math_span(text: &str) -> Option<(&str, Event<'static>, &str)> {
+    let (before, after_open) = text.split_once('$')?;
+    let (math, after) = after_open.split_once('$')?;
+    if math.is_empty() {
+        return None;
+    }
+    Some((
+        before,
+        Event::InlineMath(CowStr::from(math.to_owned())),
+        after,
+    ))
+}
+
+/// Return the anchor for the heading named `section` in the note with `anchors`: the heading's
+/// explicit id when it declares one (`# Heading {#custom-id}`), or a slug of the heading text
+/// otherwise. When `section` is a heading path (`Chapter 2#Notes`), the heading it selects is used,
+/// which may have a numbered anchor (`notes-1`) when an earlier heading has the same name.
+fn section_anchor(anchors: Option<&NoteAnchors>, section: &str) -> String {
+    anchors
+        .and_then(|note| note.section_anchor(section))
+        .map_or_else(|| slugify(section), str::to_owned)
+}
+
+/// Rebuild the text between the brackets of `reference`, like `note#section|label`.
+fn reference_text(reference: &ObsidianNoteReference<'_>) -> String {
+    let mut text = reference.file.unwrap_or_default().to_owned();
+    if let Some(section) = reference.section {
+        text.push('#');
+        text.push_str(section);
+    }
+    if let Some(section_end) = reference.section_end {
+        text.push_str(SECTION_RANGE_MARKER);
+        text.push_str(section_end);
+    }
+    if let Some(label) = reference.label {
+        text.push('|');
+        text.push_str(label);
+    }
+    text
 }
 
-fn is_markdown_file(file: &Path) -> bool {
-    let no_ext = OsString::new();
-    let ext = file.extension().unwrap_or(&no_ext).to_string_lossy();
-    ext == "md"
+/// Check whether `err` is an IO error which may go away when retrying the same operation.
+fn is_transient_error(err: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION, which occur on Windows when another
+    // process (such as a virus scanner or sync client) briefly holds on to a file.
+    const WINDOWS_TRANSIENT_ERRORS: [i32; 2] = [32, 33];
+
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut
+    ) || (cfg!(windows)
+        && err
+            .raw_os_error()
+            .is_some_and(|code| WINDOWS_TRANSIENT_ERRORS.contains(&code)))
 }
 
 /// Reduce a given `MarkdownEvents` to just those elements which are children of the given section
-/// (heading name).
+/// (heading name or heading path, see [`find_section`]).
 fn reduce_to_section<'a>(events: MarkdownEvents<'a>, section: &str) -> MarkdownEvents<'a> {
-    let mut filtered_events = Vec::with_capacity(events.len());
-    let mut target_section_encountered = false;
-    let mut currently_in_target_section = false;
-    let mut section_level = HeadingLevel::H1;
-    let mut last_level = HeadingLevel::H1;
-    let mut last_tag_was_heading = false;
-
-    for event in events {
-        filtered_events.push(event.clone());
+    let headings = top_level_headings(&events);
+    let Some((start_idx, section_level)) = find_section(&headings, section) else {
+        return events;
+    };
+    let end_idx = headings
+        .iter()
+        .find(|(idx, level, _)| *idx > start_idx && *level <= section_level)
+        .map_or(events.len(), |(idx, _, _)| *idx);
+    events
+        .into_iter()
+        .skip(start_idx)
+        .take(end_idx.saturating_sub(start_idx))
+        .collect()
+}
+
+/// Return the position, level and text of the headings in `events` which delimit sections.
+///
+/// Only headings which aren't nested in another element (such as a list item or blockquote)
+/// delimit sections, so that the structure of a section is always kept intact.
+fn top_level_headings(events: &[Event<'_>]) -> Vec<(usize, HeadingLevel, String)> {
+    let mut headings = vec![];
+    let mut current_heading: Option<(usize, HeadingLevel, String)> = None;
+    let mut depth = 0_usize;
+    for (idx, event) in events.iter().enumerate() {
         match event {
-            Event::Start(Tag::Heading { level, .. }) => {
-                last_tag_was_heading = true;
-                last_level = level;
-                if currently_in_target_section && level <= section_level {
-                    currently_in_target_section = false;
-                    filtered_events.pop();
-                }
+            Event::Start(Tag::Heading { level, .. }) if depth == 0 => {
+                current_heading = Some((idx, *level, String::new()));
             }
-            Event::Text(cowstr) => {
-                if !last_tag_was_heading {
-                    last_tag_was_heading = false;
-                    continue;
-                }
-                last_tag_was_heading = false;
-
-                if cowstr.to_string().to_lowercase() == section.to_lowercase() {
-                    target_section_encountered = true;
-                    currently_in_target_section = true;
-                    section_level = last_level;
-
-                    let current_event = filtered_events.pop().unwrap();
-                    let heading_start_event = filtered_events.pop().unwrap();
-                    filtered_events.clear();
-                    filtered_events.push(heading_start_event);
-                    filtered_events.push(current_event);
+            Event::Text(cowstr) | Event::Code(cowstr) => {
+                if let Some((_, _, ref mut text)) = current_heading {
+                    text.push_str(cowstr);
                 }
             }
+            Event::End(TagEnd::Heading(_)) => {
+                headings.extend(
+                    current_heading
+                        .take()
+                        .map(|(start, level, text)| (start, level, text.trim().to_owned())),
+                );
+            }
             _ => {}
         }
-        if target_section_encountered && !currently_in_target_section {
-            return filtered_events;
+        match event {
+            Event::Start(_) => depth = depth.saturating_add(1),
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// Find the heading among `headings` (as returned by [`top_level_headings`]) which starts the
+/// given section, returning its position and level.
+///
+/// Headings are matched by their text, ignoring case, and the first heading which matches is
+/// used. When several headings share the same name, a heading path such as `Chapter 2#Notes`
+/// (as in `[[Doc#Chapter 2#Notes]]`) selects the `Notes` heading nested below the `Chapter 2`
+/// heading, like Obsidian does. The headings of a path must be nested in the given order, but
+/// don't need to be direct children of each other. A heading whose text matches the full
+/// section (including the `#`) always takes precedence.
+fn find_section(
+    headings: &[(usize, HeadingLevel, String)],
+    section: &str,
+) -> Option<(usize, HeadingLevel)> {
+    let section = section.trim().to_lowercase();
+    let is_named = |text: &str, name: &str| text.to_lowercase() == name;
+    if let Some((idx, level, _)) = headings
+        .iter()
+        .find(|(_, _, text)| is_named(text, &section))
+    {
+        return Some((*idx, *level));
+    }
+
+    let path: Vec<&str> = section.split('#').map(str::trim).collect();
+    let (name, ancestors) = path.split_last()?;
+    if ancestors.is_empty() {
+        return None;
+    }
+    // The headings enclosing the current heading, from the outermost to the innermost.
+    let mut parents: Vec<&(usize, HeadingLevel, String)> = vec![];
+    for heading in headings {
+        let (idx, level, text) = heading;
+        parents.retain(|(_, parent_level, _)| parent_level < level);
+        let mut enclosing = parents.iter().map(|(_, _, parent)| parent.as_str());
+        if is_named(text, name)
+            && ancestors
+                .iter()
+                .all(|ancestor| enclosing.any(|parent| is_named(parent, ancestor)))
+        {
+            return Some((*idx, *level));
+        }
+        parents.push(heading);
+    }
+    None
+}
+
+/// Return the text of the heading in `content` which starts the given section (heading name or
+/// heading path, see [`find_section`]).
+pub(crate) fn section_heading(content: &str, section: &str) -> Option<String> {
+    let parser_options = Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_TABLES;
+    let events: Vec<Event<'_>> = Parser::new_ext(content, parser_options).collect();
+    let headings = top_level_headings(&events);
+    let (start_idx, _) = find_section(&headings, section)?;
+    headings
+        .into_iter()
+        .find(|(idx, _, _)| *idx == start_idx)
+        .map(|(_, _, text)| text)
+}
+
+/// Reduce a given `MarkdownEvents` to just those elements which are found between the heading
+/// named `start` (inclusive) and the heading named `end` (exclusive), regardless of heading level.
+/// Both may be heading paths, see [`find_section`].
+///
+/// An error describing the problem is returned when either heading can't be found, or when `end`
+/// only appears before `start`.
+fn reduce_to_section_range<'a>(
+    events: MarkdownEvents<'a>,
+    start: &str,
+    end: &str,
+) -> std::result::Result<MarkdownEvents<'a>, &'static str> {
+    let headings = top_level_headings(&events);
+    let (start_idx, _) = find_section(&headings, start).ok_or("start heading not found")?;
+    let following: Vec<_> = headings
+        .iter()
+        .filter(|(idx, _, _)| *idx > start_idx)
+        .cloned()
+        .collect();
+
+    match find_section(&following, end) {
+        Some((end_idx, _)) => Ok(events
+            .into_iter()
+            .skip(start_idx)
+            .take(end_idx.saturating_sub(start_idx))
+            .collect()),
+        None if find_section(&headings, end).is_some() => {
+            Err("end heading appears before start heading")
         }
+        None => Err("end heading not found"),
     }
-    filtered_events
 }
 
 fn event_to_owned<'a>(event: Event<'_>) -> Event<'a> {
@@ -1067,4 +4606,179 @@ mod tests {
         println!("Got: {:?}", result.unwrap_or(&PathBuf::from("")));
         assert_eq!(result, Some(&PathBuf::from(expected)));
     }
+
+    #[test]
+    fn test_render_markdown_with_options() {
+        let events = vec![
+            Event::Start(Tag::List(None)),
+            Event::Start(Tag::Item),
+            Event::Text(CowStr::Borrowed("Item")),
+            Event::End(TagEnd::Item),
+            Event::End(TagEnd::List(false)),
+        ];
+        assert_eq!(render_markdown(&events, None), "* Item\n");
+
+        let options = pulldown_cmark_to_cmark::Options {
+            list_token: '-',
+            ..Default::default()
+        };
+        assert_eq!(render_markdown(&events, Some(options)), "- Item\n");
+    }
+
+    fn heading_events(level: HeadingLevel, text: &'static str) -> MarkdownEvents<'static> {
+        vec![
+            Event::Start(Tag::Heading {
+                level,
+                id: None,
+                classes: vec![],
+                attrs: vec![],
+            }),
+            Event::Text(CowStr::Borrowed(text)),
+            Event::End(TagEnd::Heading(level)),
+        ]
+    }
+
+    fn paragraph_events(text: &'static str) -> MarkdownEvents<'static> {
+        vec![
+            Event::Start(Tag::Paragraph),
+            Event::Text(CowStr::Borrowed(text)),
+            Event::End(TagEnd::Paragraph),
+        ]
+    }
+
+    static SECTIONED_NOTE: LazyLock<MarkdownEvents<'static>> = LazyLock::new(|| {
+        [
+            heading_events(HeadingLevel::H1, "Intro"),
+            paragraph_events("Intro text"),
+            heading_events(HeadingLevel::H2, "Start"),
+            paragraph_events("Start text"),
+            heading_events(HeadingLevel::H3, "Middle"),
+            paragraph_events("Middle text"),
+            heading_events(HeadingLevel::H2, "End"),
+            paragraph_events("End text"),
+        ]
+        .concat()
+    });
+
+    #[test]
+    fn test_reduce_to_section_nested_structures() {
+        let parse = |text| -> MarkdownEvents<'_> {
+            Parser::new_ext(text, Options::ENABLE_TABLES).collect()
+        };
+        let section = "## Section\n\n- Item\n  - Nested\n\n    # Not a heading boundary\n  - \\# Also not a heading\n\n| A | B |\n| - | - |\n| 1 | 2 |\n\n### Subsection\n\n> # Quoted heading\n";
+        let note = format!("# Intro\n\nIntro text\n\n{section}\n## Next\n\nNext text\n");
+
+        assert_eq!(reduce_to_section(parse(&note), "section"), parse(section));
+        assert_eq!(
+            reduce_to_section(parse(&note), "Next"),
+            parse("## Next\n\nNext text\n")
+        );
+    }
+
+    #[test]
+    fn test_reduce_to_section_heading_path() {
+        let note = [
+            heading_events(HeadingLevel::H1, "Chapter 1"),
+            heading_events(HeadingLevel::H2, "Notes"),
+            paragraph_events("First"),
+            heading_events(HeadingLevel::H1, "Chapter 2"),
+            heading_events(HeadingLevel::H2, "Notes"),
+            paragraph_events("Second"),
+            heading_events(HeadingLevel::H2, "A # B"),
+            paragraph_events("Literal"),
+        ]
+        .concat();
+        let notes = |text| {
+            [
+                heading_events(HeadingLevel::H2, "Notes"),
+                paragraph_events(text),
+            ]
+            .concat()
+        };
+
+        assert_eq!(reduce_to_section(note.clone(), "Notes"), notes("First"));
+        assert_eq!(
+            reduce_to_section(note.clone(), "chapter 2#notes"),
+            notes("Second")
+        );
+        assert_eq!(
+            reduce_to_section(note.clone(), "A # B"),
+            [
+                heading_events(HeadingLevel::H2, "A # B"),
+                paragraph_events("Literal"),
+            ]
+            .concat()
+        );
+        // Unresolved paths leave the note as-is, like unknown headings.
+        assert_eq!(reduce_to_section(note.clone(), "Chapter 3#Notes"), note);
+    }
+
+    #[test]
+    fn test_reduce_to_section_range() {
+        assert_eq!(
+            reduce_to_section_range(SECTIONED_NOTE.clone(), "start", "End"),
+            Ok([
+                heading_events(HeadingLevel::H2, "Start"),
+                paragraph_events("Start text"),
+                heading_events(HeadingLevel::H3, "Middle"),
+                paragraph_events("Middle text"),
+            ]
+            .concat())
+        );
+    }
+
+    #[test]
+    fn test_reduce_to_section_range_errors() {
+        assert_eq!(
+            reduce_to_section_range(SECTIONED_NOTE.clone(), "End", "Start"),
+            Err("end heading appears before start heading")
+        );
+        assert_eq!(
+            reduce_to_section_range(SECTIONED_NOTE.clone(), "Missing", "End"),
+            Err("start heading not found")
+        );
+        assert_eq!(
+            reduce_to_section_range(SECTIONED_NOTE.clone(), "Start", "Missing"),
+            Err("end heading not found")
+        );
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        let mut attempts = 0_usize;
+        let result = retry_transient_errors(3, || {
+            attempts = attempts.saturating_add(1);
+            match attempts {
+                1 => Err(std::io::Error::from(ErrorKind::Interrupted)),
+                2 => Err(std::io::Error::from(ErrorKind::WouldBlock)),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_retry_transient_errors_gives_up() {
+        let mut attempts = 0_usize;
+        let result: std::io::Result<()> = retry_transient_errors(2, || {
+            attempts = attempts.saturating_add(1);
+            Err(std::io::Error::from(ErrorKind::TimedOut))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(
+            attempts, 3,
+            "operation should be tried once plus the number of retries"
+        );
+    }
+
+    #[test]
+    fn test_retry_transient_errors_permanent_error() {
+        let mut attempts = 0_usize;
+        let result: std::io::Result<()> = retry_transient_errors(3, || {
+            attempts = attempts.saturating_add(1);
+            Err(std::io::Error::from(ErrorKind::PermissionDenied))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(attempts, 1, "permanent errors should not be retried");
+    }
 }
@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// `LinkGraph` holds the links between the files of a vault which were found during an export.
+///
+/// Each link goes from the note containing the link to the file (a note or an attachment) it
+/// refers to, identified by their paths within the vault. Only links which could be resolved to
+/// a file are included.
+///
+/// See [`Exporter::collect_link_graph`][crate::Exporter::collect_link_graph].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkGraph {
+    links: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl LinkGraph {
+    /// Create a new, empty `LinkGraph`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return whether the graph doesn't contain any links.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Return all links in the graph as `(source, target)` pairs, sorted by source.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Path)> {
+        self.links.iter().flat_map(|(source, targets)| {
+            targets
+                .iter()
+                .map(move |target| (source.as_path(), target.as_path()))
+        })
+    }
+
+    /// Return the files which `note` links to, in sorted order.
+    #[must_use]
+    pub fn links_from(&self, note: &Path) -> Vec<&Path> {
+        self.links
+            .get(note)
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    /// Return the notes which link to `file` (its backlinks), in sorted order.
+    #[must_use]
+    pub fn links_to(&self, file: &Path) -> Vec<&Path> {
+        self.links
+            .iter()
+            .filter(|(_, targets)| targets.contains(file))
+            .map(|(source, _)| source.as_path())
+            .collect()
+    }
+
+    /// Return all backlinks in the graph, mapping each file which is linked to onto the notes
+    /// which link to it.
+    #[must_use]
+    pub fn backlinks(&self) -> BTreeMap<&Path, BTreeSet<&Path>> {
+        let mut backlinks: BTreeMap<&Path, BTreeSet<&Path>> = BTreeMap::new();
+        for (source, target) in self.iter() {
+            backlinks.entry(target).or_default().insert(source);
+        }
+        backlinks
+    }
+
+    /// Add a link from `source` to `target` to the graph.
+    pub(crate) fn insert(&mut self, source: &Path, target: &Path) {
+        self.links
+            .entry(source.to_path_buf())
+            .or_default()
+            .insert(target.to_path_buf());
+    }
+
+    /// Add all links from `other` to the graph.
+    pub(crate) fn extend(&mut self, other: Self) {
+        for (source, targets) in other.links {
+            self.links.entry(source).or_default().extend(targets);
+        }
+    }
+}
@@ -1,10 +1,38 @@
-use std::env;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs};
 
-use eyre::{eyre, Result};
+use eyre::{eyre, Result, WrapErr};
 use gumdrop::Options;
-use obsidian_export::postprocessors::{filter_by_tags, softbreaks_to_hardbreaks};
-use obsidian_export::{ExportError, Exporter, FrontmatterStrategy, WalkOptions};
+use obsidian_export::encoding_rs::Encoding;
+use obsidian_export::ignore::overrides::{Override, OverrideBuilder};
+use obsidian_export::postprocessors::{
+    autolink_urls,
+    callouts_to_html,
+    filter_by_tags_with_hierarchy,
+    highlights_to_html,
+    inline_footnotes,
+    smartypants,
+    softbreaks_to_hardbreaks,
+    strip_body,
+};
+use obsidian_export::{
+    serde_yaml,
+    EncodingFallback,
+    ExportError,
+    Exporter,
+    Frontmatter,
+    FrontmatterFallback,
+    FrontmatterFormat,
+    FrontmatterStrategy,
+    MissingEmbedStyle,
+    OverwritePolicy,
+    WalkOptions,
+    PULLDOWN_CMARK_TO_CMARK_VERSION,
+    PULLDOWN_CMARK_VERSION,
+    SERDE_YAML_VERSION,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -17,14 +45,89 @@ struct Opts {
     #[options(help = "Display version information")]
     version: bool,
 
-    #[options(help = "Read notes from this source", free, required)]
+    #[options(
+        no_short,
+        help = "Display version information (including that of key dependencies) as JSON"
+    )]
+    version_json: bool,
+
+    #[options(
+        help = "Read notes from this source (- to read a note from stdin)",
+        free,
+        required
+    )]
     source: Option<PathBuf>,
 
-    #[options(help = "Write notes to this destination", free, required)]
+    #[options(
+        help = "Write notes to this destination (- to write a note to stdout)",
+        free,
+        required
+    )]
     destination: Option<PathBuf>,
 
-    #[options(no_short, help = "Only export notes under this sub-path")]
-    start_at: Option<PathBuf>,
+    #[options(
+        no_short,
+        help = "Resolve links and embeds against this vault when reading from stdin or writing to stdout"
+    )]
+    vault: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Only export notes under this sub-path (may be given multiple times)"
+    )]
+    start_at: Vec<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Only export this note and the files it links to, directly or indirectly (may be given multiple times)"
+    )]
+    reachable_from: Vec<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Copy attachments outside of --start-at to this directory (relative to the destination)"
+    )]
+    external_attachments_dir: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Prefix links to notes and attachments with this base URL or path"
+    )]
+    link_base: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Export all files directly into the destination, joining their directories into the filename with this separator"
+    )]
+    flatten_output: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Lowercase the names of all exported files and directories",
+        default = "false"
+    )]
+    lowercase_output: bool,
+
+    #[options(
+        no_short,
+        help = "When exporting a single file, resolve links and embeds to the files next to it",
+        default = "false"
+    )]
+    resolve_siblings: bool,
+
+    #[options(
+        no_short,
+        help = "Warn about links to headings which don't exist in the linked note",
+        default = "false"
+    )]
+    validate_section_links: bool,
+
+    #[options(
+        no_short,
+        help = "Fail the export when notes link to headings which don't exist in the linked note",
+        default = "false"
+    )]
+    strict_section_links: bool,
 
     #[options(
         help = "Frontmatter strategy (one of: always, never, auto)",
@@ -35,6 +138,65 @@ struct Opts {
     )]
     frontmatter_strategy: FrontmatterStrategy,
 
+    #[options(
+        help = "Format to write frontmatter in (one of: yaml, toml)",
+        no_short,
+        parse(try_from_str = "frontmatter_format_from_str"),
+        default = "yaml"
+    )]
+    frontmatter_format: FrontmatterFormat,
+
+    #[options(
+        help = "How to handle frontmatter which isn't a mapping (one of: error, ignore, wrap:<key>)",
+        no_short,
+        parse(try_from_str = "frontmatter_fallback_from_str"),
+        default = "error"
+    )]
+    frontmatter_fallback: FrontmatterFallback,
+
+    #[options(
+        no_short,
+        help = "Recognize frontmatter with indented --- fences at the start of notes",
+        default = "false"
+    )]
+    lenient_frontmatter: bool,
+
+    #[options(
+        no_short,
+        help = "Let notes control their export through export, export-destination and export-frontmatter frontmatter keys",
+        default = "false"
+    )]
+    frontmatter_directives: bool,
+
+    #[options(
+        no_short,
+        help = "Sort the keys of frontmatter alphabetically",
+        default = "false"
+    )]
+    sort_frontmatter_keys: bool,
+
+    #[options(
+        help = "How to export embeds of missing notes (one of: remove, literal, emphasis)",
+        no_short,
+        parse(try_from_str = "missing_embed_style_from_str"),
+        default = "remove"
+    )]
+    missing_embeds: MissingEmbedStyle,
+
+    #[options(
+        no_short,
+        help = "Read default frontmatter, to add to every note which doesn't override it, from this YAML file"
+    )]
+    frontmatter_defaults: Option<PathBuf>,
+
+    #[options(
+        help = "How to handle notes which aren't valid UTF-8 (one of: error, lossy, or the name of an encoding such as windows-1252)",
+        no_short,
+        parse(try_from_str = "encoding_fallback_from_str"),
+        default = "error"
+    )]
+    encoding_fallback: EncodingFallback,
+
     #[options(
         no_short,
         help = "Read ignore patterns from files with this name",
@@ -42,21 +204,160 @@ struct Opts {
     )]
     ignore_file: String,
 
+    #[options(
+        no_short,
+        help = "Read additional ignore patterns, which apply to the entire vault, from this file"
+    )]
+    ignore_from: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Only export files matching this glob pattern (relative to the vault, may be repeated)",
+        parse(from_str = "glob_arg_from_str")
+    )]
+    include: Vec<GlobArg>,
+
+    #[options(
+        no_short,
+        help = "Don't export files matching this glob pattern (relative to the vault, may be repeated), unless a later --include matches them again",
+        parse(from_str = "glob_arg_from_str")
+    )]
+    exclude: Vec<GlobArg>,
+
+    #[options(
+        no_short,
+        help = "Treat files with this extension as notes (default: md)"
+    )]
+    markdown_extension: Vec<String>,
+
+    #[options(
+        no_short,
+        help = "Don't export attachments with these (comma-separated) extensions"
+    )]
+    skip_extensions: Vec<String>,
+
     #[options(no_short, help = "Exclude files with this tag from the export")]
     skip_tags: Vec<String>,
 
     #[options(no_short, help = "Export only files with this tag")]
     only_tags: Vec<String>,
 
+    #[options(
+        no_short,
+        help = "Match tags hierarchically, so that --skip-tags/--only-tags foo also matches foo/bar",
+        default = "false"
+    )]
+    hierarchical_tags: bool,
+
     #[options(no_short, help = "Export hidden files", default = "false")]
     hidden: bool,
 
+    #[options(
+        no_short,
+        help = "Export Obsidian's .obsidian and .trash directories (with --hidden)",
+        default = "false"
+    )]
+    include_system_dirs: bool,
+
+    #[options(
+        no_short,
+        help = "Only export files up to this many directories deep (1 = only the top-level directory)"
+    )]
+    max_depth: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Write a note listing the backlinks of all notes to this path (relative to the destination)"
+    )]
+    backlinks_index: Option<PathBuf>,
+
     #[options(no_short, help = "Disable git integration", default = "false")]
     no_git: bool,
 
     #[options(no_short, help = "Don't process embeds recursively", default = "false")]
     no_recursive_embeds: bool,
 
+    #[options(
+        no_short,
+        help = "Replace repeated embeds of the same note with a link to that note",
+        default = "false"
+    )]
+    deduplicate_embeds: bool,
+
+    #[options(
+        no_short,
+        help = "Demote headings of embedded notes by one level for each level of embedding",
+        default = "false"
+    )]
+    demote_embedded_headings: bool,
+
+    #[options(
+        no_short,
+        help = "Resolve references to folders to their folder note (Folder/Folder.md or the folder's index note)",
+        default = "false"
+    )]
+    folder_notes: bool,
+
+    #[options(
+        no_short,
+        help = "Filename of index notes used with --folder-notes",
+        default = "index.md"
+    )]
+    folder_note_index: String,
+
+    #[options(
+        no_short,
+        help = "Resolve date references like [[2024-01-15]] to daily notes in this folder first (relative to the vault)"
+    )]
+    daily_note_folder: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Keep [[wikilinks]] and ![[embeds]] as-is instead of converting them",
+        default = "false"
+    )]
+    preserve_wikilinks: bool,
+
+    #[options(
+        no_short,
+        help = "Convert Windows-style (CRLF) line endings to Unix-style (LF) line endings before processing notes",
+        default = "false"
+    )]
+    normalize_line_endings: bool,
+
+    #[options(
+        no_short,
+        help = "Print the files which would be exported (and their destinations) without exporting them",
+        default = "false"
+    )]
+    list_files: bool,
+
+    #[options(
+        no_short,
+        help = "Retry writing files this many times after transient IO errors",
+        default = "0"
+    )]
+    io_retries: usize,
+
+    #[options(
+        no_short,
+        help = "Replace embeds with links once the embedded content of a note exceeds this many bytes"
+    )]
+    max_embed_bytes: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Replace embeds with links once a note has embedded this many notes"
+    )]
+    max_embeds_per_note: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Rewrite wikilinks kept by --preserve-wikilinks to the full path of the file they refer to",
+        default = "false"
+    )]
+    resolve_wikilinks: bool,
+
     #[options(
         no_short,
         help = "Preserve the mtime of exported files",
@@ -64,12 +365,95 @@ struct Opts {
     )]
     preserve_mtime: bool,
 
+    #[options(
+        no_short,
+        help = "Remove files from the destination which weren't written by this export",
+        default = "false"
+    )]
+    clean: bool,
+
+    #[options(
+        no_short,
+        help = "What to do with files which already exist in the destination (one of: overwrite, skip, error)",
+        parse(try_from_str = "overwrite_policy_from_str"),
+        default = "overwrite"
+    )]
+    overwrite: OverwritePolicy,
+
     #[options(
         no_short,
         help = "Convert soft line breaks to hard line breaks. This mimics Obsidian's 'Strict line breaks' setting",
         default = "false"
     )]
     hard_linebreaks: bool,
+
+    #[options(
+        no_short,
+        help = "Export only the frontmatter of notes, leaving out their content",
+        default = "false"
+    )]
+    frontmatter_only: bool,
+
+    #[options(
+        no_short,
+        help = "Convert inline footnotes (^[text]) to regular footnotes",
+        default = "false"
+    )]
+    inline_footnotes: bool,
+
+    #[options(
+        no_short,
+        help = "Convert highlights (==text==) to <mark>text</mark>",
+        default = "false"
+    )]
+    highlights: bool,
+
+    #[options(
+        no_short,
+        help = "Convert callouts (> [!note] Title) to HTML",
+        default = "false"
+    )]
+    callouts: bool,
+
+    #[options(
+        no_short,
+        help = "Turn bare URLs (https://example.com) into links",
+        default = "false"
+    )]
+    autolink_urls: bool,
+
+    #[options(
+        no_short,
+        help = "Convert straight quotes, dashes and ellipses into their typographic forms",
+        default = "false"
+    )]
+    smartypants: bool,
+
+    #[options(
+        no_short,
+        help = "Render canvas files as markdown instead of copying them",
+        default = "false"
+    )]
+    render_canvas: bool,
+}
+
+// A glob pattern given to --include or --exclude, along with its position among all of these
+// patterns on the command line, as later patterns take precedence over earlier ones.
+#[derive(Debug)]
+struct GlobArg {
+    position: usize,
+    pattern: String,
+}
+
+// The number of --include and --exclude patterns parsed so far. Gumdrop parses arguments in the
+// order they're given, so this determines their position.
+static GLOB_ARGS_PARSED: AtomicUsize = AtomicUsize::new(0);
+
+fn glob_arg_from_str(input: &str) -> GlobArg {
+    GlobArg {
+        position: GLOB_ARGS_PARSED.fetch_add(1, Ordering::Relaxed),
+        pattern: input.to_owned(),
+    }
 }
 
 fn frontmatter_strategy_from_str(input: &str) -> Result<FrontmatterStrategy> {
@@ -81,6 +465,157 @@ fn frontmatter_strategy_from_str(input: &str) -> Result<FrontmatterStrategy> {
     }
 }
 
+fn frontmatter_format_from_str(input: &str) -> Result<FrontmatterFormat> {
+    match input {
+        "yaml" => Ok(FrontmatterFormat::Yaml),
+        "toml" => Ok(FrontmatterFormat::Toml),
+        _ => Err(eyre!("must be one of: yaml, toml")),
+    }
+}
+
+fn frontmatter_fallback_from_str(input: &str) -> Result<FrontmatterFallback> {
+    match input {
+        "error" => Ok(FrontmatterFallback::Error),
+        "ignore" => Ok(FrontmatterFallback::Ignore),
+        _ => match input.strip_prefix("wrap:") {
+            Some(key) if !key.is_empty() => Ok(FrontmatterFallback::WrapInKey(key.to_owned())),
+            _ => Err(eyre!("must be one of: error, ignore, wrap:<key>")),
+        },
+    }
+}
+
+fn missing_embed_style_from_str(input: &str) -> Result<MissingEmbedStyle> {
+    match input {
+        "remove" => Ok(MissingEmbedStyle::Remove),
+        "literal" => Ok(MissingEmbedStyle::Literal),
+        "emphasis" => Ok(MissingEmbedStyle::Emphasis),
+        _ => Err(eyre!("must be one of: remove, literal, emphasis")),
+    }
+}
+
+fn overwrite_policy_from_str(input: &str) -> Result<OverwritePolicy> {
+    match input {
+        "overwrite" => Ok(OverwritePolicy::Overwrite),
+        "skip" => Ok(OverwritePolicy::Skip),
+        "error" => Ok(OverwritePolicy::Error),
+        _ => Err(eyre!("must be one of: overwrite, skip, error")),
+    }
+}
+
+fn frontmatter_from_file(path: &Path) -> Result<Frontmatter> {
+    let content = fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read '{}'", path.display()))?;
+    serde_yaml::from_str(&content)
+        .wrap_err_with(|| format!("failed to decode YAML frontmatter in '{}'", path.display()))
+}
+
+fn encoding_fallback_from_str(input: &str) -> Result<EncodingFallback> {
+    match input {
+        "error" => Ok(EncodingFallback::Error),
+        "lossy" => Ok(EncodingFallback::Lossy),
+        _ => Encoding::for_label(input.as_bytes())
+            .map(EncodingFallback::Encoding)
+            .ok_or_else(|| eyre!("must be one of: error, lossy, or a known encoding name")),
+    }
+}
+
+fn split_comma_separated(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+// Export a single note, reading it from stdin when `source` is `-` and writing it to stdout when
+// `destination` is `-`.
+fn export_piped(
+    exporter: &mut Exporter<'_>,
+    source: &Path,
+    destination: &Path,
+    vault: Option<&Path>,
+) -> Result<()> {
+    let note = if source == Path::new("-") {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .wrap_err("failed to read note from stdin")?;
+        let path = vault.unwrap_or_else(|| Path::new("")).join("stdin.md");
+        exporter.export_str(&path, content)?
+    } else {
+        if !source.is_file() {
+            return Err(eyre!("only a single note can be written to stdout"));
+        }
+        let mut note = None;
+        exporter.start_at(source.to_path_buf());
+        exporter.export_to(|_, contents| {
+            note = Some(contents.to_vec());
+            Ok(())
+        })?;
+        note
+    };
+
+    let note = note.unwrap_or_default();
+    match destination == Path::new("-") {
+        true => io::stdout()
+            .write_all(&note)
+            .wrap_err("failed to write note to stdout"),
+        false => fs::write(destination, note)
+            .wrap_err_with(|| format!("failed to write to '{}'", destination.display())),
+    }
+}
+
+// Describe the version of obsidian-export and that of its key dependencies as JSON.
+fn version_json() -> serde_json::Value {
+    serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": VERSION,
+        "pulldown_cmark": PULLDOWN_CMARK_VERSION,
+        "pulldown_cmark_to_cmark": PULLDOWN_CMARK_TO_CMARK_VERSION,
+        "serde_yaml": SERDE_YAML_VERSION,
+    })
+}
+
+// Build the overrides for the --include and --exclude glob patterns, relative to the vault at
+// `root`. Patterns are added in the order they were given, so that the last matching pattern
+// decides whether a file is exported.
+fn glob_overrides(
+    root: &Path,
+    include: &[GlobArg],
+    exclude: &[GlobArg],
+) -> Result<Option<Override>> {
+    if include.is_empty() && exclude.is_empty() {
+        return Ok(None);
+    }
+    let vault = match root.is_file() {
+        true => root.parent().unwrap_or(root),
+        false => root,
+    };
+    let mut globs: Vec<(&GlobArg, bool)> = include
+        .iter()
+        .map(|glob| (glob, true))
+        .chain(exclude.iter().map(|glob| (glob, false)))
+        .collect();
+    globs.sort_by_key(|(glob, _)| glob.position);
+
+    let mut builder = OverrideBuilder::new(vault);
+    for (glob, is_include) in globs {
+        let pattern = &glob.pattern;
+        if is_include {
+            builder
+                .add(pattern)
+                .wrap_err_with(|| format!("invalid --include pattern '{pattern}'"))?;
+        } else {
+            builder
+                .add(&format!("!{pattern}"))
+                .wrap_err_with(|| format!("invalid --exclude pattern '{pattern}'"))?;
+        }
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[allow(clippy::too_many_lines)]
 fn main() {
     // Due to the use of free arguments in Opts, we must bypass Gumdrop to determine whether the
     // version flag was specified. Without this, "missing required free argument" would get printed
@@ -89,33 +624,138 @@ fn main() {
         println!("obsidian-export {VERSION}");
         std::process::exit(0);
     }
+    if env::args().any(|arg| arg == "--version-json") {
+        println!("{}", version_json());
+        std::process::exit(0);
+    }
 
-    let args = Opts::parse_args_default_or_exit();
-    let root = args.source.unwrap();
-    let destination = args.destination.unwrap();
+    let mut args = Opts::parse_args_default_or_exit();
+    let source = args.source.take().unwrap();
+    let destination = args.destination.take().unwrap();
+    let vault = args.vault.take();
 
+    let root = vault.clone().unwrap_or_else(|| source.clone());
+    let overrides = glob_overrides(&root, &args.include, &args.exclude).unwrap_or_else(|err| {
+        eprintln!("Error: {err:?}");
+        std::process::exit(1);
+    });
     let walk_options = WalkOptions {
         ignore_filename: &args.ignore_file,
+        global_ignore_path: args.ignore_from.as_deref(),
         ignore_hidden: !args.hidden,
         honor_gitignore: !args.no_git,
+        exclude_obsidian_system_dirs: !args.include_system_dirs,
+        max_depth: args.max_depth,
+        overrides,
         ..Default::default()
     };
-
-    let mut exporter = Exporter::new(root, destination);
+    let mut exporter = Exporter::new(root, destination.clone());
     exporter.frontmatter_strategy(args.frontmatter_strategy);
     exporter.process_embeds_recursively(!args.no_recursive_embeds);
+    exporter.deduplicate_embeds(args.deduplicate_embeds);
+    exporter.demote_embedded_headings(args.demote_embedded_headings);
+    exporter.missing_embed_style(args.missing_embeds);
+    exporter.max_embed_bytes(args.max_embed_bytes);
+    exporter.max_embeds_per_note(args.max_embeds_per_note);
+    exporter.frontmatter_format(args.frontmatter_format);
+    exporter.sort_frontmatter_keys(args.sort_frontmatter_keys);
+    exporter.frontmatter_fallback(args.frontmatter_fallback);
+    exporter.lenient_frontmatter(args.lenient_frontmatter);
+    exporter.honor_frontmatter_directives(args.frontmatter_directives);
+    exporter.encoding_fallback(args.encoding_fallback);
+
+    if let Some(path) = args.frontmatter_defaults {
+        match frontmatter_from_file(&path) {
+            Ok(defaults) => exporter.default_frontmatter(defaults),
+            Err(err) => {
+                eprintln!("Error: {err:?}");
+                std::process::exit(1);
+            }
+        };
+    }
+    exporter.folder_notes(args.folder_notes);
+    exporter.folder_note_index(args.folder_note_index);
+    exporter.daily_note_folder(args.daily_note_folder);
+    exporter.preserve_wikilinks(args.preserve_wikilinks);
+    exporter.io_retries(args.io_retries);
+    exporter.backlinks_index(args.backlinks_index);
+    exporter.external_attachments_dir(args.external_attachments_dir);
+    exporter.render_canvas(args.render_canvas);
+    exporter.skip_attachment_extensions(split_comma_separated(&args.skip_extensions));
+    if !args.markdown_extension.is_empty() {
+        exporter.markdown_extensions(args.markdown_extension);
+    }
+    exporter.normalize_line_endings(args.normalize_line_endings);
+    exporter.resolve_preserved_wikilinks(args.resolve_wikilinks);
     exporter.preserve_mtime(args.preserve_mtime);
+    exporter.clean_destination(args.clean);
+    exporter.overwrite_policy(args.overwrite);
     exporter.walk_options(walk_options);
 
     if args.hard_linebreaks {
         exporter.add_postprocessor(&softbreaks_to_hardbreaks);
     }
+    if args.inline_footnotes {
+        exporter.add_postprocessor(&inline_footnotes);
+    }
+    if args.frontmatter_only {
+        exporter.add_postprocessor(&strip_body);
+    }
+    let highlights_postprocessor = highlights_to_html("mark".to_owned());
+    if args.highlights {
+        exporter.add_postprocessor(&highlights_postprocessor);
+    }
+    if args.callouts {
+        exporter.add_postprocessor(&callouts_to_html);
+    }
+    if args.autolink_urls {
+        exporter.add_postprocessor(&autolink_urls);
+    }
+    // Runs after autolink_urls so that URLs are turned into links before their text is changed.
+    if args.smartypants {
+        exporter.add_postprocessor(&smartypants);
+    }
 
-    let tags_postprocessor = filter_by_tags(args.skip_tags, args.only_tags);
+    let tags_postprocessor =
+        filter_by_tags_with_hierarchy(args.skip_tags, args.only_tags, args.hierarchical_tags);
     exporter.add_postprocessor(&tags_postprocessor);
 
-    if let Some(path) = args.start_at {
-        exporter.start_at(path);
+    if !args.start_at.is_empty() {
+        exporter.start_at_many(args.start_at);
+    }
+    exporter.reachable_from(args.reachable_from);
+
+    if let Some(link_base) = args.link_base {
+        exporter.link_base(link_base);
+    }
+    if let Some(separator) = args.flatten_output {
+        exporter.flatten_output(separator);
+    }
+    exporter.lowercase_output(args.lowercase_output);
+    exporter.resolve_siblings(args.resolve_siblings);
+    exporter.validate_section_links(args.validate_section_links);
+    exporter.strict_section_links(args.strict_section_links);
+
+    if args.list_files {
+        match exporter.files_to_export() {
+            Ok(files) => {
+                for (src, dest) in files {
+                    println!("{} -> {}", src.display(), dest.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {:?}", eyre!(err));
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if source == Path::new("-") || destination == Path::new("-") {
+        if let Err(err) = export_piped(&mut exporter, &source, &destination, vault.as_deref()) {
+            eprintln!("Error: {err:?}");
+            std::process::exit(1);
+        }
+        return;
     }
 
     #[allow(clippy::pattern_type_mismatch)]
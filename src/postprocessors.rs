@@ -1,9 +1,28 @@
 //! A collection of officially maintained [postprocessors][crate::Postprocessor].
 
-use pulldown_cmark::Event;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, LinkType, Tag, TagEnd};
+use regex::Regex;
 use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use slug::slugify;
 
-use super::{Context, MarkdownEvents, PostprocessorResult};
+use super::{
+    lookup_filename_in_vault,
+    lookup_filename_in_vault_with_extensions,
+    relative_link_to_file,
+    render_markdown,
+    Context,
+    MarkdownEvents,
+    PostprocessorResult,
+    PERCENTENCODE_CHARS,
+};
 
 /// This postprocessor converts all soft line breaks to hard line breaks. Enabling this mimics
 /// Obsidian's _'Strict line breaks'_ setting.
@@ -19,14 +38,940 @@ pub fn softbreaks_to_hardbreaks(
     PostprocessorResult::Continue
 }
 
+/// This postprocessor removes the entire body of notes, leaving only their frontmatter.
+///
+/// This is useful to build a metadata-only copy of a vault. Combine it with
+/// [`FrontmatterStrategy::Always`][crate::FrontmatterStrategy::Always] to make sure a frontmatter
+/// block is written even for notes without any frontmatter.
+pub fn strip_body(_context: &mut Context, events: &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    events.clear();
+    PostprocessorResult::Continue
+}
+
+/// This postprocessor resolves regular markdown links to other files in the vault (for example
+/// `[text](Other%20Note.md)`) the same way `[[wikilinks]]` are resolved.
+///
+/// Link destinations are first looked up relative to the note containing the link. When no such
+/// file exists, the destination is matched against `vault_contents` using the same rules as
+/// `[[wikilinks]]` instead. Matching links are rewritten to be relative to the exported note.
+///
+/// Links to external resources (`https://...`, `mailto:...`, etc.), links to sections within the
+/// same note (`#heading`) and links to files which can't be found are left unchanged.
+///
+/// `vault_contents` is expected to contain all files in the vault, as returned by
+/// [`vault_contents`][crate::vault_contents].
+///
+/// This postprocessor may also be added as an embed postprocessor
+/// ([`Exporter::add_embed_postprocessor`][crate::Exporter::add_embed_postprocessor]), in which case
+/// links within embedded notes are resolved relative to the embedded note rather than the note it's
+/// embedded into.
+pub fn resolve_markdown_links(
+    vault_contents: Vec<PathBuf>,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        for event in events.iter_mut() {
+            if let Event::Start(Tag::Link { dest_url, .. }) = event {
+                if let Some(link) = resolve_markdown_link(dest_url, context, &vault_contents) {
+                    *dest_url = CowStr::from(link);
+                }
+            }
+        }
+        PostprocessorResult::Continue
+    }
+}
+
+fn resolve_markdown_link(
+    dest_url: &str,
+    context: &Context,
+    vault_contents: &[PathBuf],
+) -> Option<String> {
+    if dest_url.is_empty() || dest_url.starts_with('#') || has_url_scheme(dest_url) {
+        return None;
+    }
+    let (path, section) = match dest_url.split_once('#') {
+        Some((path, section)) => (path, Some(section)),
+        None => (dest_url, None),
+    };
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+
+    let relative_to_note = context
+        .current_file()
+        .parent()
+        .map(|parent| normalize_path(&parent.join(path.as_ref())));
+    // Links usually include the extension of the file, so any file (including attachments) with a
+    // matching name is preferred over a note which would match when adding a `.md` extension.
+    let no_extensions: &[&str] = &[];
+    let target_file = relative_to_note
+        .and_then(|candidate| vault_contents.iter().find(|file| **file == candidate))
+        .or_else(|| lookup_filename_in_vault_with_extensions(&path, vault_contents, no_extensions))
+        .or_else(|| lookup_filename_in_vault(&path, vault_contents))?;
+
+    let mut link = relative_link_to_file(target_file, context, false);
+    if let Some(section) = section {
+        link.push('#');
+        link.push_str(section);
+    }
+    Some(link)
+}
+
+/// This postprocessor prefixes links to other notes and attachments with `prefix`, producing the
+/// same links as [`Exporter::link_base`][crate::Exporter::link_base].
+///
+/// Links in exported notes are relative to the note they appear in. Each link is first resolved
+/// against the directory of the note within the vault, which gives the path of the linked file
+/// relative to the root of the vault. The prefix is then inserted in front of that path, separated
+/// by a single `/`. For example, with a prefix of `/notes`, a link to `../Other.md` in
+/// `sub/Note.md` becomes `/notes/Other.md`. Links within embedded notes are resolved against the
+/// directory of the note they're embedded into, as that's what they're relative to.
+///
+/// Regular markdown links in the source notes are prefixed as well. Links to external resources,
+/// links to sections within the same note (`#heading`) and absolute links (starting with `/`) are
+/// left unchanged. Files are assumed to be exported to the same path relative to the destination
+/// as they have in the vault, which isn't the case when exporting a subdirectory of the vault or
+/// with [`Exporter::flatten_output`][crate::Exporter::flatten_output].
+pub fn prefix_internal_links(
+    prefix: String,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let root_file = context.root_file();
+        let note_dir = root_file
+            .strip_prefix(context.vault_root())
+            .unwrap_or(root_file)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        for event in events.iter_mut() {
+            if let Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) = event {
+                if let Some(link) = prefixed_link(dest_url, &note_dir, &prefix) {
+                    *dest_url = CowStr::from(link);
+                }
+            }
+        }
+        PostprocessorResult::Continue
+    }
+}
+
+// Return `dest_url` (relative to `note_dir`) prefixed with `prefix`, or `None` for links which
+// aren't prefixed.
+fn prefixed_link(dest_url: &str, note_dir: &Path, prefix: &str) -> Option<String> {
+    if dest_url.is_empty() || dest_url.starts_with(['#', '/']) || has_url_scheme(dest_url) {
+        return None;
+    }
+    let (path, section) = match dest_url.split_once('#') {
+        Some((path, section)) => (path, Some(section)),
+        None => (dest_url, None),
+    };
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let components: Vec<_> = normalize_path(&note_dir.join(path.as_ref()))
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    let mut link = format!(
+        "{}/{}",
+        prefix.trim_end_matches('/'),
+        utf8_percent_encode(&components.join("/"), PERCENTENCODE_CHARS)
+    );
+    if let Some(section) = section {
+        link.push('#');
+        link.push_str(section);
+    }
+    Some(link)
+}
+
+pub(crate) fn has_url_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Lexically normalize `path`, resolving any `.` and `..` components without touching the
+/// filesystem.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// This postprocessor checks that links to sections within the same note (such as those created
+/// by `[[#Section]]`) point to a heading which actually exists in the note.
+///
+/// A warning is printed for each link whose anchor doesn't match the slug of any heading in the
+/// note. When `strict` is true, such notes are skipped entirely as well.
+///
+/// Only links relative to the note itself are checked, so links to sections within the same note
+/// aren't recognized when [`Exporter::link_base`][crate::Exporter::link_base] is set.
+pub fn validate_internal_anchors(
+    strict: bool,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let anchors: HashSet<String> = heading_anchors(events)
+            .into_iter()
+            .map(|(_, anchor)| anchor)
+            .collect();
+        // Links to the note itself are lowercased when the exporter lowercases its output.
+        let self_links = [false, true]
+            .map(|lowercase| relative_link_to_file(context.current_file(), context, lowercase));
+
+        let mut valid = true;
+        for event in events.iter() {
+            let Event::Start(Tag::Link { dest_url, .. }) = event else {
+                continue;
+            };
+            let Some((path, anchor)) = dest_url.split_once('#') else {
+                continue;
+            };
+            if (path.is_empty() || self_links.iter().any(|link| link == path))
+                && !anchors.contains(anchor)
+            {
+                eprintln!(
+                    "Warning: Link to a section which doesn't exist\n\tLink: '{}'\n\tSource: '{}'\n",
+                    dest_url,
+                    context.current_file().display(),
+                );
+                valid = false;
+            }
+        }
+
+        if valid || !strict {
+            PostprocessorResult::Continue
+        } else {
+            PostprocessorResult::StopAndSkipNote
+        }
+    }
+}
+
+// Collect the anchors of all headings in `events`, along with the position of each heading.
+// Headings without an explicit id get the same slug as links to sections generated by
+// `[[Note#Section]]`. When an earlier heading has the same anchor already, a number is appended
+// to keep anchors unique (`notes`, `notes-1`, `notes-2` and so on).
+pub(crate) fn heading_anchors(events: &[Event<'_>]) -> Vec<(usize, String)> {
+    let mut anchors = vec![];
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut heading: Option<(usize, String)> = None;
+    for (idx, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Heading { id: Some(id), .. }) => {
+                let count = seen.entry(id.to_string()).or_default();
+                *count = count.saturating_add(1);
+                anchors.push((idx, id.to_string()));
+            }
+            Event::Start(Tag::Heading { id: None, .. }) => heading = Some((idx, String::new())),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading_text)) = heading.as_mut() {
+                    heading_text.push_str(text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((start, text)) = heading.take() {
+                    let slug = slugify(text);
+                    let count = seen.entry(slug.clone()).or_default();
+                    let anchor = match *count {
+                        0 => slug,
+                        n => format!("{slug}-{n}"),
+                    };
+                    *count = count.saturating_add(1);
+                    anchors.push((start, anchor));
+                }
+            }
+            _ => {}
+        }
+    }
+    anchors
+}
+
+/// Find the extent of the blockquote which starts at `start_idx` in `events`.
+///
+/// Returns the range of indices covering all events of the blockquote, from its
+/// `Event::Start(Tag::BlockQuote(_))` up to and including the matching `Event::End`. Content such
+/// as lists, code blocks and other blockquotes nested within the blockquote is included in the
+/// range. This is useful when rewriting blockquotes, such as Obsidian callouts (`> [!note]`), as a
+/// whole.
+///
+/// Returns `None` when the event at `start_idx` doesn't start a blockquote, or when the
+/// blockquote isn't closed.
+#[must_use]
+pub fn blockquote_span(events: &[Event<'_>], start_idx: usize) -> Option<Range<usize>> {
+    if !matches!(
+        events.get(start_idx),
+        Some(Event::Start(Tag::BlockQuote(_)))
+    ) {
+        return None;
+    }
+
+    let mut depth = 0_usize;
+    for (idx, event) in events.iter().enumerate().skip(start_idx) {
+        match event {
+            Event::Start(Tag::BlockQuote(_)) => depth = depth.saturating_add(1),
+            Event::End(TagEnd::BlockQuote(_)) => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return Some(start_idx..idx.saturating_add(1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// This postprocessor converts Obsidian's callouts (`> [!note] Title`) into HTML.
+///
+/// Each callout becomes a `<div class="callout">` with a `data-callout` attribute holding the
+/// (lowercase) callout type, containing the title in a `<div class="callout-title">` followed by
+/// the body in a `<div class="callout-content">`. The body remains Markdown, so it's still
+/// converted by the static site generator processing the exported notes. Callouts without a title
+/// get their type as title, like in Obsidian.
+///
+/// Foldable callouts (`[!note]-` and `[!note]+`) get a `data-callout-fold` attribute of
+/// `collapsed` or `expanded` respectively, and metadata such as `[!note|wide]` ends up in a
+/// `data-callout-metadata` attribute.
+///
+/// The title runs until the end of the first line of the callout. Inline formatting which starts
+/// on the first line is kept in the title as a whole, even when it continues onto the following
+/// lines. Blockquotes which aren't callouts are left untouched.
+pub fn callouts_to_html(
+    _context: &mut Context,
+    events: &mut MarkdownEvents<'_>,
+) -> PostprocessorResult {
+    let mut idx = 0;
+    while idx < events.len() {
+        let Some(span) = blockquote_span(events, idx) else {
+            idx = idx.saturating_add(1);
+            continue;
+        };
+        let Some(callout) = events.get(span.clone()).and_then(parse_callout) else {
+            idx = idx.saturating_add(1);
+            continue;
+        };
+
+        let mut header = callout.opening_html();
+        if callout.body_starts_mid_paragraph {
+            header.push(Event::Start(Tag::Paragraph));
+        }
+        let closing = html_block("</div>\n</div>\n".to_owned());
+        events.splice(span.end.saturating_sub(1)..span.end, closing);
+        let header_len = header.len();
+        events.splice(
+            span.start..span.start.saturating_add(callout.body_idx),
+            header,
+        );
+        // Continue with the body of the callout, which may contain nested callouts.
+        idx = span.start.saturating_add(header_len);
+    }
+    PostprocessorResult::Continue
+}
+
+// A callout, as found by `parse_callout`.
+struct Callout {
+    kind: String,
+    metadata: Option<String>,
+    fold: Option<&'static str>,
+    title: String,
+    // The index of the first event of the body, relative to the start of the blockquote.
+    body_idx: usize,
+    // Whether the body starts with the remainder of the paragraph containing the title.
+    body_starts_mid_paragraph: bool,
+}
+
+impl Callout {
+    fn opening_html(&self) -> MarkdownEvents<'static> {
+        let mut html = format!(
+            "<div class=\"callout\" data-callout=\"{}\"",
+            escape_html(&self.kind)
+        );
+        if let Some(fold) = self.fold {
+            let _ = write!(html, " data-callout-fold=\"{fold}\"");
+        }
+        if let Some(metadata) = &self.metadata {
+            let _ = write!(html, " data-callout-metadata=\"{}\"", escape_html(metadata));
+        }
+        let _ = write!(
+            html,
+            ">\n<div class=\"callout-title\">{}</div>\n<div class=\"callout-content\">\n",
+            self.title
+        );
+        html_block(html)
+    }
+}
+
+// Parse the events of a blockquote (as returned by `blockquote_span`) as a callout, returning
+// `None` if the blockquote isn't a callout.
+fn parse_callout(events: &[Event<'_>]) -> Option<Callout> {
+    if events.get(1) != Some(&Event::Start(Tag::Paragraph)) {
+        return None;
+    }
+    // The callout marker may be split across several text events, such as `[`, `!note` and `]`.
+    let text_len = events
+        .iter()
+        .skip(2)
+        .take_while(|event| matches!(event, Event::Text(_)))
+        .count();
+    let text =
+        events
+            .get(2..text_len.saturating_add(2))?
+            .iter()
+            .fold(String::new(), |mut text, event| {
+                if let Event::Text(part) = event {
+                    text.push_str(part);
+                }
+                text
+            });
+
+    let (marker, rest) = text.strip_prefix("[!")?.split_once(']')?;
+    let (kind, metadata) = match marker.split_once('|') {
+        Some((kind, metadata)) => (kind, Some(metadata.trim().to_owned())),
+        None => (marker, None),
+    };
+    if kind.is_empty()
+        || !kind
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let (fold, rest) = match rest.chars().next() {
+        Some('-') => (Some("collapsed"), rest.get(1..).unwrap_or_default()),
+        Some('+') => (Some("expanded"), rest.get(1..).unwrap_or_default()),
+        _ => (None, rest),
+    };
+
+    let mut title_events = Vec::new();
+    let rest = rest.trim_start();
+    if !rest.is_empty() {
+        title_events.push(Event::Text(CowStr::from(rest.to_owned())));
+    }
+    let mut body_idx = text_len.saturating_add(2);
+    let mut depth = 0_usize;
+    let mut body_starts_mid_paragraph = false;
+    for event in events.iter().skip(body_idx) {
+        body_idx = body_idx.saturating_add(1);
+        match event {
+            Event::End(TagEnd::Paragraph) if depth == 0 => break,
+            Event::SoftBreak | Event::HardBreak if depth == 0 => {
+                body_starts_mid_paragraph = true;
+                break;
+            }
+            Event::Start(_) => depth = depth.saturating_add(1),
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        title_events.push(event.clone());
+    }
+
+    let title = if title_events.is_empty() {
+        let mut chars = kind.chars();
+        let default_title: String = chars.next().map_or_else(String::new, |first| {
+            first.to_uppercase().chain(chars).collect()
+        });
+        escape_html(&default_title)
+    } else {
+        let mut title = String::new();
+        html::push_html(&mut title, title_events.into_iter());
+        title
+    };
+
+    Some(Callout {
+        kind: kind.to_lowercase(),
+        metadata,
+        fold,
+        title,
+        body_idx,
+        body_starts_mid_paragraph,
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::new();
+    html::push_html(
+        &mut escaped,
+        std::iter::once(Event::Text(CowStr::Borrowed(text))),
+    );
+    escaped
+}
+
+fn html_block(html: String) -> MarkdownEvents<'static> {
+    vec![
+        Event::Start(Tag::HtmlBlock),
+        Event::Html(CowStr::from(html)),
+        Event::End(TagEnd::HtmlBlock),
+    ]
+}
+
+/// This postprocessor converts Obsidian's inline footnotes (`^[footnote text]`) into regular
+/// footnotes, which are otherwise exported as literal text.
+///
+/// Each inline footnote is replaced with a numbered footnote reference (`[^1]`), and its text is
+/// moved to a footnote definition at the end of the note. Footnotes are numbered in the order in
+/// which they appear, skipping any numbers which are already used by other footnotes in the note.
+pub fn inline_footnotes(
+    _context: &mut Context,
+    events: &mut MarkdownEvents<'_>,
+) -> PostprocessorResult {
+    let mut used_labels: HashSet<String> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::FootnoteReference(label) | Event::Start(Tag::FootnoteDefinition(label)) => {
+                Some(label.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    let mut number = 0_usize;
+    let mut definitions = Vec::new();
+
+    let mut idx = 0;
+    while idx < events.len() {
+        let Some(close_idx) = inline_footnote_end(events, idx) else {
+            idx = idx.saturating_add(1);
+            continue;
+        };
+        let label = loop {
+            number = number.saturating_add(1);
+            if used_labels.insert(number.to_string()) {
+                break number.to_string();
+            }
+        };
+
+        // The footnote consists of the text ending in `^`, the opening bracket, the footnote's
+        // content and finally the closing bracket.
+        let mut footnote: Vec<_> = events.drain(idx..=close_idx).collect();
+        footnote.pop();
+        let content = footnote.split_off(2);
+        let mut replacement = Vec::with_capacity(2);
+        if let Some(Event::Text(text)) = footnote.first() {
+            let text = text.strip_suffix('^').unwrap_or(text);
+            if !text.is_empty() {
+                replacement.push(Event::Text(CowStr::from(text.to_owned())));
+            }
+        }
+        replacement.push(Event::FootnoteReference(CowStr::from(label.clone())));
+        let replacement_len = replacement.len();
+        events.splice(idx..idx, replacement);
+        idx = idx.saturating_add(replacement_len);
+
+        definitions.push(Event::Start(Tag::FootnoteDefinition(CowStr::from(label))));
+        definitions.push(Event::Start(Tag::Paragraph));
+        definitions.extend(content);
+        definitions.push(Event::End(TagEnd::Paragraph));
+        definitions.push(Event::End(TagEnd::FootnoteDefinition));
+    }
+    events.extend(definitions);
+    PostprocessorResult::Continue
+}
+
+// Return the index of the closing bracket of the inline footnote starting at `start_idx`, if
+// there is one.
+fn inline_footnote_end(events: &[Event<'_>], start_idx: usize) -> Option<usize> {
+    let Some(Event::Text(preceding_text)) = events.get(start_idx) else {
+        return None;
+    };
+    if !preceding_text.ends_with('^') {
+        return None;
+    }
+    let content_idx = start_idx.saturating_add(2);
+    let opening_bracket = events.get(start_idx.saturating_add(1));
+    if !matches!(opening_bracket, Some(Event::Text(bracket)) if bracket.as_ref() == "[") {
+        return None;
+    }
+
+    // Brackets within the footnote's text are emitted as separate text events as well, so these
+    // need to be balanced to find the closing bracket of the footnote itself.
+    let mut depth = 0_usize;
+    for (idx, event) in events.iter().enumerate().skip(content_idx) {
+        match event {
+            Event::Text(bracket) if bracket.as_ref() == "[" => depth = depth.saturating_add(1),
+            Event::Text(bracket) if bracket.as_ref() == "]" => match depth.checked_sub(1) {
+                Some(new_depth) => depth = new_depth,
+                None => return Some(idx),
+            },
+            // Inline footnotes can't extend beyond the paragraph (or other block) they're in.
+            _ if is_block_boundary(event) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// This postprocessor converts Obsidian's highlights (`==highlighted text==`) into HTML.
+///
+/// The highlighted text is wrapped in `element`, so using `mark` results in
+/// `<mark>highlighted text</mark>`.
+///
+/// Like in Obsidian, the opening `==` must not be followed by whitespace and the closing `==` must
+/// not be preceded by whitespace, so text such as `a == b` is left alone. Highlights can't span
+/// multiple paragraphs (or other blocks), and text in code spans and code blocks is never changed.
+pub fn highlights_to_html(
+    element: String,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |_context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let tags = (format!("<{element}>"), format!("</{element}>"));
+        let mut result = Vec::with_capacity(events.len());
+        // The position of the opening tag of the current highlight within `result`, if any.
+        let mut open_idx = None;
+        let mut in_code_block = false;
+
+        for event in events.drain(..) {
+            match event {
+                Event::Text(text) if !in_code_block && text.contains("==") => {
+                    push_highlighted_text(&text, &tags, &mut result, &mut open_idx);
+                    continue;
+                }
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                _ => {}
+            }
+            if is_block_boundary(&event) {
+                unopen_highlight(&mut result, &mut open_idx);
+            }
+            result.push(event);
+        }
+        unopen_highlight(&mut result, &mut open_idx);
+        *events = result;
+        PostprocessorResult::Continue
+    }
+}
+
+// Push `text` onto `events`, replacing any `==` highlight markers with the opening or closing tag
+// from `tags`.
+fn push_highlighted_text(
+    text: &str,
+    tags: &(String, String),
+    events: &mut MarkdownEvents<'_>,
+    open_idx: &mut Option<usize>,
+) {
+    let mut last = 0;
+    for (idx, marker) in text.match_indices("==") {
+        let before = text
+            .get(..idx)
+            .and_then(|before| before.chars().next_back());
+        let after = text
+            .get(idx.saturating_add(marker.len())..)
+            .and_then(|after| after.chars().next());
+        // Markers which are part of a longer run of `=` characters aren't markers at all.
+        if before == Some('=') || after == Some('=') {
+            continue;
+        }
+        let is_marker = match open_idx {
+            None => !after.is_some_and(char::is_whitespace),
+            Some(_) => !before.is_some_and(char::is_whitespace),
+        };
+        if !is_marker {
+            continue;
+        }
+
+        if let Some(preceding) = text
+            .get(last..idx)
+            .filter(|preceding| !preceding.is_empty())
+        {
+            events.push(Event::Text(CowStr::from(preceding.to_owned())));
+        }
+        if open_idx.take().is_some() {
+            events.push(Event::InlineHtml(CowStr::from(tags.1.clone())));
+        } else {
+            *open_idx = Some(events.len());
+            events.push(Event::InlineHtml(CowStr::from(tags.0.clone())));
+        }
+        last = idx.saturating_add(marker.len());
+    }
+    if let Some(rest) = text.get(last..).filter(|rest| !rest.is_empty()) {
+        events.push(Event::Text(CowStr::from(rest.to_owned())));
+    }
+}
+
+// Turn the opening tag of a highlight which was never closed back into regular text.
+fn unopen_highlight(events: &mut MarkdownEvents<'_>, open_idx: &mut Option<usize>) {
+    if let Some(event) = open_idx.take().and_then(|idx| events.get_mut(idx)) {
+        *event = Event::Text(CowStr::Borrowed("=="));
+    }
+}
+
+// Return whether `event` starts or ends a block (such as a paragraph), as opposed to inline
+// content.
+pub(crate) const fn is_block_boundary(event: &Event<'_>) -> bool {
+    match event {
+        Event::Start(
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link { .. } | Tag::Image { .. },
+        )
+        | Event::End(
+            TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Link
+            | TagEnd::Image,
+        ) => false,
+        Event::Start(_) | Event::End(_) | Event::Rule => true,
+        _ => false,
+    }
+}
+
+static BARE_URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:https?|ftp)://[^\s<>]+").unwrap());
+
+/// This postprocessor turns bare URLs in text, such as `https://example.com`, into links.
+///
+/// Obsidian links these URLs automatically, but without this postprocessor they're exported as
+/// plain text. URLs which are already part of a link, as well as URLs in code, are left alone.
+///
+/// Punctuation at the end of a URL, such as the period at the end of a sentence, isn't considered
+/// part of the URL. Neither is a closing parenthesis without a matching opening parenthesis in
+/// the URL, so that `(see https://example.com)` works as expected.
+pub fn autolink_urls(
+    _context: &mut Context,
+    events: &mut MarkdownEvents<'_>,
+) -> PostprocessorResult {
+    let mut result = Vec::with_capacity(events.len());
+    let mut link_depth = 0_usize;
+    let mut in_code_block = false;
+    // Text may be split across several consecutive text events, even within a single URL.
+    let mut text = String::new();
+
+    for event in events.drain(..) {
+        match &event {
+            Event::Text(part) if link_depth == 0 && !in_code_block => {
+                text.push_str(part);
+                continue;
+            }
+            Event::Start(Tag::Link { .. } | Tag::Image { .. }) => {
+                link_depth = link_depth.saturating_add(1);
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                link_depth = link_depth.saturating_sub(1);
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            _ => {}
+        }
+        push_autolinked_text(&text, &mut result);
+        text.clear();
+        result.push(event);
+    }
+    push_autolinked_text(&text, &mut result);
+    *events = result;
+    PostprocessorResult::Continue
+}
+
+// Push `text` onto `events`, turning any bare URLs in it into links.
+fn push_autolinked_text(text: &str, events: &mut MarkdownEvents<'_>) {
+    let mut last = 0;
+    for found in BARE_URL_RE.find_iter(text) {
+        let url = trim_url(found.as_str());
+        if let Some(preceding) = text
+            .get(last..found.start())
+            .filter(|preceding| !preceding.is_empty())
+        {
+            events.push(Event::Text(CowStr::from(preceding.to_owned())));
+        }
+        events.push(Event::Start(Tag::Link {
+            link_type: LinkType::Autolink,
+            dest_url: CowStr::from(url.to_owned()),
+            title: CowStr::Borrowed(""),
+            id: CowStr::Borrowed(""),
+        }));
+        events.push(Event::Text(CowStr::from(url.to_owned())));
+        events.push(Event::End(TagEnd::Link));
+        last = found.start().saturating_add(url.len());
+    }
+    if let Some(rest) = text.get(last..).filter(|rest| !rest.is_empty()) {
+        events.push(Event::Text(CowStr::from(rest.to_owned())));
+    }
+}
+
+// Strip trailing punctuation which most likely isn't part of `url` from it.
+fn trim_url(mut url: &str) -> &str {
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '"', '*', '_']);
+        let unbalanced_paren =
+            trimmed.ends_with(')') && trimmed.matches(')').count() > trimmed.matches('(').count();
+        let next = match unbalanced_paren {
+            true => trimmed
+                .get(..trimmed.len().saturating_sub(1))
+                .unwrap_or(trimmed),
+            false => trimmed,
+        };
+        if next == url {
+            return url;
+        }
+        url = next;
+    }
+}
+
+/// This postprocessor applies typographic substitutions to the text of notes, like `SmartyPants`.
+///
+/// Straight quotes are converted into curly quotes (`"text"` becomes `“text”` and `it's` becomes
+/// `it’s`), `---` into an em dash (`—`), `--` into an en dash (`–`) and `...` into an ellipsis
+/// (`…`). Whether a quote opens or closes is decided by the character before it: quotes at the
+/// start of a block or after whitespace or opening punctuation open, all others close.
+///
+/// Text in code spans, code blocks and HTML is never changed.
+pub fn smartypants(_context: &mut Context, events: &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    let mut in_code_block = false;
+    // The last character of the text preceding the current event within the same block.
+    let mut previous = None;
+    for event in events.iter_mut() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                let educated = educate_text(text, previous);
+                previous = educated.chars().next_back();
+                *text = CowStr::from(educated);
+                continue;
+            }
+            Event::Code(code) => {
+                previous = code.chars().next_back();
+                continue;
+            }
+            _ => {}
+        }
+        if is_block_boundary(event) || matches!(event, Event::SoftBreak | Event::HardBreak) {
+            previous = None;
+        }
+    }
+    PostprocessorResult::Continue
+}
+
+// Apply the substitutions of the smartypants postprocessor to `text`, which follows the character
+// `previous` (if any).
+fn educate_text(text: &str, mut previous: Option<char>) -> String {
+    let text = text
+        .replace("---", "\u{2014}")
+        .replace("--", "\u{2013}")
+        .replace("...", "\u{2026}");
+    let mut educated = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        let opens = previous.map_or(true, |prev| {
+            prev.is_whitespace() || "([{\u{2014}\u{2013}\u{201c}\u{2018}".contains(prev)
+        });
+        let educated_char = match char {
+            '"' if opens => '\u{201c}',
+            '"' => '\u{201d}',
+            // An apostrophe in front of a number abbreviates a year, like in '90s.
+            '\'' if opens && !chars.peek().is_some_and(char::is_ascii_digit) => '\u{2018}',
+            '\'' => '\u{2019}',
+            _ => char,
+        };
+        educated.push(educated_char);
+        previous = Some(educated_char);
+    }
+    educated
+}
+
+/// Available strategies for adding a title to notes, see [`ensure_title`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TitleStrategy {
+    /// Set the `title` key in the note's frontmatter, unless it's already present.
+    Frontmatter,
+    /// Insert a level 1 heading at the top of the note, unless the note already has one.
+    Heading,
+}
+
+/// This postprocessor gives each note a title based on its filename (without extension).
+///
+/// Depending on `strategy`, the title is either added to the frontmatter or inserted as a heading
+/// (see [`TitleStrategy`]). Notes which already have a title are left untouched.
+///
+/// When `deslugify` is true, dashes and underscores in the filename are replaced with spaces, so
+/// that a note named `my-note.md` gets the title `my note`.
+pub fn ensure_title(
+    strategy: TitleStrategy,
+    deslugify: bool,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let Some(stem) = context.current_file().file_stem() else {
+            return PostprocessorResult::Continue;
+        };
+        let mut title = stem.to_string_lossy().into_owned();
+        if deslugify {
+            title = title.replace(['-', '_'], " ");
+        }
+
+        match strategy {
+            TitleStrategy::Frontmatter => {
+                let key = Value::String("title".to_owned());
+                if !context.frontmatter.contains_key(&key) {
+                    context.frontmatter.insert(key, Value::String(title));
+                }
+            }
+            TitleStrategy::Heading => {
+                let has_title = events.iter().any(|event| {
+                    matches!(
+                        event,
+                        Event::Start(Tag::Heading {
+                            level: HeadingLevel::H1,
+                            ..
+                        })
+                    )
+                });
+                if !has_title {
+                    events.splice(
+                        0..0,
+                        [
+                            Event::Start(Tag::Heading {
+                                level: HeadingLevel::H1,
+                                id: None,
+                                classes: vec![],
+                                attrs: vec![],
+                            }),
+                            Event::Text(CowStr::from(title)),
+                            Event::End(TagEnd::Heading(HeadingLevel::H1)),
+                        ],
+                    );
+                }
+            }
+        }
+        PostprocessorResult::Continue
+    }
+}
+
 pub fn filter_by_tags(
     skip_tags: Vec<String>,
     only_tags: Vec<String>,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    filter_by_tags_with_hierarchy(skip_tags, only_tags, false)
+}
+
+/// Like [`filter_by_tags`], but with the option to match tags hierarchically.
+///
+/// When `hierarchical` is true, specifying a tag also matches all of its nested tags, the same
+/// way Obsidian does. For example, `project` matches notes tagged `project/active` (but not notes
+/// tagged `projects`).
+pub fn filter_by_tags_with_hierarchy(
+    skip_tags: Vec<String>,
+    only_tags: Vec<String>,
+    hierarchical: bool,
 ) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
     move |context: &mut Context, _events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
         match context.frontmatter.get("tags") {
-            None => filter_by_tags_(&[], &skip_tags, &only_tags),
-            Some(Value::Sequence(tags)) => filter_by_tags_(tags, &skip_tags, &only_tags),
+            None => filter_by_tags_(&[], &skip_tags, &only_tags, hierarchical),
+            Some(Value::Sequence(tags)) => {
+                filter_by_tags_(tags, &skip_tags, &only_tags, hierarchical)
+            }
+            Some(Value::String(tags)) => {
+                let tags: Vec<Value> = tags
+                    .split(|char: char| char.is_whitespace() || char == ',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| Value::String(tag.to_owned()))
+                    .collect();
+                filter_by_tags_(&tags, &skip_tags, &only_tags, hierarchical)
+            }
+            Some(Value::Number(tag)) => {
+                let tags = [Value::String(tag.to_string())];
+                filter_by_tags_(&tags, &skip_tags, &only_tags, hierarchical)
+            }
             _ => PostprocessorResult::Continue,
         }
     }
@@ -36,14 +981,19 @@ fn filter_by_tags_(
     tags: &[Value],
     skip_tags: &[String],
     only_tags: &[String],
+    hierarchical: bool,
 ) -> PostprocessorResult {
-    let skip = skip_tags
-        .iter()
-        .any(|tag| tags.contains(&Value::String(tag.to_string())));
-    let include = only_tags.is_empty()
-        || only_tags
-            .iter()
-            .any(|tag| tags.contains(&Value::String(tag.to_string())));
+    let has_tag = |filter_tag: &String| {
+        tags.iter().filter_map(Value::as_str).any(|tag| {
+            tag == filter_tag
+                || (hierarchical
+                    && tag
+                        .strip_prefix(filter_tag.as_str())
+                        .is_some_and(|rest| rest.starts_with('/')))
+        })
+    };
+    let skip = skip_tags.iter().any(has_tag);
+    let include = only_tags.is_empty() || only_tags.iter().any(has_tag);
 
     if skip || !include {
         PostprocessorResult::StopAndSkipNote
@@ -52,6 +1002,407 @@ fn filter_by_tags_(
     }
 }
 
+/// This postprocessor moves the value of frontmatter key `from` to the key `to`.
+///
+/// This is useful when a static site generator expects values under a different key than
+/// Obsidian uses, such as Jekyll's `categories` instead of `tags`.
+///
+/// Notes without a `from` key are left untouched. When `to` already exists, its value is replaced
+/// unless `merge` is true, in which case the value of `from` is appended to the sequence at `to`
+/// (skipping any values which are already present). When merging isn't possible because `to`
+/// isn't a sequence, a warning is printed and the frontmatter is left as-is.
+pub fn remap_frontmatter_key(
+    from: String,
+    to: String,
+    merge: bool,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, _events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let Some(value) = context.frontmatter.get(from.as_str()).cloned() else {
+            return PostprocessorResult::Continue;
+        };
+
+        match context.frontmatter.get_mut(to.as_str()) {
+            Some(Value::Sequence(existing)) if merge => {
+                let values = match value {
+                    Value::Sequence(values) => values,
+                    value => vec![value],
+                };
+                for item in values {
+                    if !existing.contains(&item) {
+                        existing.push(item);
+                    }
+                }
+            }
+            Some(_) if merge => {
+                eprintln!(
+                    "Warning: Unable to merge frontmatter key '{}' into '{}' which is not a sequence\n\tSource: '{}'\n",
+                    from,
+                    to,
+                    context.current_file().display(),
+                );
+                return PostprocessorResult::Continue;
+            }
+            _ => {
+                context.frontmatter.insert(Value::String(to.clone()), value);
+            }
+        }
+        if from != to {
+            context.frontmatter.remove(from.as_str());
+        }
+        PostprocessorResult::Continue
+    }
+}
+
+/// Available strategies for placeholders which don't match a frontmatter key, see
+/// [`interpolate_frontmatter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnknownPlaceholderStrategy {
+    /// Leave the placeholder as-is.
+    #[default]
+    Keep,
+    /// Print a warning and skip the note.
+    SkipNote,
+}
+
+/// This postprocessor replaces placeholders in the text of notes with the values of the
+/// corresponding frontmatter keys, such as `{{title}}` with the note's `title`.
+///
+/// Placeholders consist of a key enclosed in the delimiters `open` and `close` (commonly `{{` and
+/// `}}`), with optional whitespace around the key. When placeholders are nested, like in
+/// `{{{title}}}`, the innermost one is replaced. Only string, number and boolean values are
+/// substituted: placeholders referring to other values are left as-is, with a warning. What
+/// happens to placeholders without a matching frontmatter key is determined by `unknown`.
+///
+/// Placeholders in code spans and code blocks are never replaced. Neither are placeholders which
+/// the Markdown parser splits up, which means escaping a character within the opening delimiter,
+/// like `{\{title}}`, keeps the placeholder as-is.
+///
+/// # Panics
+///
+/// Panics when `open` or `close` is empty.
+pub fn interpolate_frontmatter(
+    open: String,
+    close: String,
+    unknown: UnknownPlaceholderStrategy,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    assert!(
+        !open.is_empty() && !close.is_empty(),
+        "placeholder delimiters must not be empty"
+    );
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let mut in_code_block = false;
+        for event in events.iter_mut() {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                Event::Text(text) if !in_code_block && text.contains(open.as_str()) => {
+                    match interpolate_text(text, &open, &close, context, unknown) {
+                        Some(interpolated) => *text = CowStr::from(interpolated),
+                        None => return PostprocessorResult::StopAndSkipNote,
+                    }
+                }
+                _ => {}
+            }
+        }
+        PostprocessorResult::Continue
+    }
+}
+
+// Replace the placeholders delimited by `open` and `close` in `text` with values from the
+// frontmatter of `context`. Returns `None` when the note should be skipped.
+fn interpolate_text(
+    text: &str,
+    open: &str,
+    close: &str,
+    context: &Context,
+    unknown: UnknownPlaceholderStrategy,
+) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(close_idx) = rest.find(close) {
+        let (before, after) = rest.split_at(close_idx);
+        let after = after.get(close.len()..).unwrap_or_default();
+        let Some(open_idx) = before.rfind(open) else {
+            result.push_str(before);
+            result.push_str(close);
+            rest = after;
+            continue;
+        };
+        let (preceding, placeholder) = before.split_at(open_idx);
+        let key = placeholder.get(open.len()..).unwrap_or_default().trim();
+        result.push_str(preceding);
+        match context.frontmatter.get(key) {
+            Some(Value::String(value)) => result.push_str(value),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => {
+                let value = serde_yaml::to_string(value).expect("scalar values always serialize");
+                result.push_str(value.trim_end());
+            }
+            Some(_) => {
+                eprintln!(
+                    "Warning: Frontmatter key '{}' doesn't contain a string, number or boolean, so it can't be interpolated\n\tSource: '{}'\n",
+                    key,
+                    context.current_file().display(),
+                );
+                result.push_str(placeholder);
+                result.push_str(close);
+            }
+            None if unknown == UnknownPlaceholderStrategy::SkipNote => {
+                eprintln!(
+                    "Warning: Skipping note with placeholder '{}' which doesn't match a frontmatter key\n\tSource: '{}'\n",
+                    key,
+                    context.current_file().display(),
+                );
+                return None;
+            }
+            None => {
+                result.push_str(placeholder);
+                result.push_str(close);
+            }
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+/// This postprocessor copies the text of the first paragraph of a note into its frontmatter under
+/// `key`, which is useful to provide a description of a page for search engines.
+///
+/// Inline formatting is stripped (links are reduced to their link text) and the text is truncated
+/// to at most `max_len` characters, breaking on a word boundary where possible. Frontmatter which
+/// already contains `key` is left unchanged, as are notes without any paragraphs.
+pub fn extract_description(
+    key: String,
+    max_len: usize,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        if context.frontmatter.contains_key(key.as_str()) {
+            return PostprocessorResult::Continue;
+        }
+        let Some(start) = events
+            .iter()
+            .position(|event| matches!(event, Event::Start(Tag::Paragraph)))
+        else {
+            return PostprocessorResult::Continue;
+        };
+
+        let mut text = String::new();
+        for event in events.iter().skip(start) {
+            match event {
+                Event::Text(content) | Event::Code(content) => text.push_str(content),
+                Event::SoftBreak | Event::HardBreak => text.push(' '),
+                Event::End(TagEnd::Paragraph) => break,
+                _ => {}
+            }
+        }
+        let description = truncate_on_word_boundary(&text, max_len);
+        if !description.is_empty() {
+            context
+                .frontmatter
+                .insert(Value::String(key.clone()), Value::String(description));
+        }
+        PostprocessorResult::Continue
+    }
+}
+
+// Collapse the whitespace in `text` and truncate it to at most `max_len` characters, preferably
+// on a word boundary.
+fn truncate_on_word_boundary(text: &str, max_len: usize) -> String {
+    let mut truncated = String::new();
+    for word in text.split_whitespace() {
+        let separator_len = usize::from(!truncated.is_empty());
+        let len = truncated
+            .chars()
+            .count()
+            .saturating_add(separator_len)
+            .saturating_add(word.chars().count());
+        if len > max_len {
+            if truncated.is_empty() {
+                truncated = word.chars().take(max_len).collect();
+            }
+            break;
+        }
+        if separator_len > 0 {
+            truncated.push(' ');
+        }
+        truncated.push_str(word);
+    }
+    truncated
+}
+
+/// This postprocessor adds a SHA-256 hash of a note's rendered content to its frontmatter, stored
+/// as a hexadecimal string under `key`.
+///
+/// This makes it possible to detect which notes changed between exports, for example for cache
+/// busting on static hosting. The hash only covers the note's body (rendered with the default
+/// [`pulldown_cmark_to_cmark::Options`]), not its frontmatter.
+///
+/// Because the hash covers the content as it is when this postprocessor runs, it should be added
+/// after all other postprocessors which modify the content of notes.
+pub fn add_content_hash(
+    key: String,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let digest = Sha256::digest(render_markdown(events, None).as_bytes());
+        let hash = digest.iter().fold(String::new(), |mut hash, byte| {
+            write!(hash, "{byte:02x}").expect("formatting to string not expected to fail");
+            hash
+        });
+        context
+            .frontmatter
+            .insert(Value::String(key.clone()), Value::String(hash));
+        PostprocessorResult::Continue
+    }
+}
+
+// The signifiers of the Obsidian Tasks plugin, which are followed by a date.
+const TASK_DATE_SIGNIFIERS: [(&str, &str); 9] = [
+    ("📅", "due"),
+    ("📆", "due"),
+    ("🗓", "due"),
+    ("⏳", "scheduled"),
+    ("⌛", "scheduled"),
+    ("🛫", "start"),
+    ("➕", "created"),
+    ("✅", "done"),
+    ("❌", "cancelled"),
+];
+
+// The priority signifiers of the Obsidian Tasks plugin.
+const TASK_PRIORITIES: [(&str, &str); 5] = [
+    ("🔺", "highest"),
+    ("⏫", "high"),
+    ("🔼", "medium"),
+    ("🔽", "low"),
+    ("⏬", "lowest"),
+];
+
+static TASK_METADATA_RE: LazyLock<Regex> = LazyLock::new(|| {
+    let signifiers = "📅📆🗓⏳⌛🛫➕✅❌🔺⏫🔼🔽⏬🔁";
+    Regex::new(&format!(
+        r"\s*(?:(?P<date_signifier>[📅📆🗓⏳⌛🛫➕✅❌])\x{{FE0F}}?\s*(?P<date>\d{{4}}-\d{{2}}-\d{{2}})|(?P<priority>[🔺⏫🔼🔽⏬])\x{{FE0F}}?|🔁\x{{FE0F}}?\s*(?P<recurrence>[^{signifiers}]*[^{signifiers}\s]))"
+    ))
+    .unwrap()
+});
+
+/// Available styles for the task metadata handled by [`clean_task_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TaskMetadataStyle {
+    /// Remove the metadata from tasks.
+    Remove,
+    /// Replace the metadata with a plain text description at the end of the task, such as
+    /// `(due 2024-01-01, high priority)`.
+    Text,
+}
+
+/// This postprocessor cleans up the metadata which the [Obsidian Tasks] plugin adds to tasks
+/// (`- [ ] Do thing 📅 2024-01-01 ⏫`), which is noise outside of Obsidian.
+///
+/// Dates (due, scheduled, start, created, done and cancelled), priorities and recurrence rules
+/// (`🔁 every week`) are either removed or replaced with plain text, depending on `style`. Text
+/// which isn't part of a task is left alone.
+///
+/// When `due_dates_key` is set, the due dates of all tasks in the note are also collected in a
+/// list under this key in the note's frontmatter, sorted and without duplicates.
+///
+/// [Obsidian Tasks]: https://publish.obsidian.md/tasks/Reference/Task+Formats/Tasks+Emoji+Format
+pub fn clean_task_metadata(
+    style: TaskMetadataStyle,
+    due_dates_key: Option<String>,
+) -> impl Fn(&mut Context, &mut MarkdownEvents<'_>) -> PostprocessorResult {
+    move |context: &mut Context, events: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let mut result = Vec::with_capacity(events.len());
+        let mut due_dates = Vec::new();
+        let mut in_task = false;
+        // The text of a task may be split across several consecutive text events.
+        let mut text = String::new();
+
+        for event in events.drain(..) {
+            match &event {
+                Event::TaskListMarker(_) => in_task = true,
+                Event::Text(part) if in_task => {
+                    text.push_str(part);
+                    continue;
+                }
+                _ if is_block_boundary(&event) => in_task = false,
+                _ => {}
+            }
+            if !text.is_empty() {
+                let cleaned = clean_task_text(&text, style, &mut due_dates);
+                result.push(Event::Text(CowStr::from(cleaned)));
+                text.clear();
+            }
+            result.push(event);
+        }
+        if !text.is_empty() {
+            let cleaned = clean_task_text(&text, style, &mut due_dates);
+            result.push(Event::Text(CowStr::from(cleaned)));
+        }
+        *events = result;
+
+        if let Some(key) = &due_dates_key {
+            // Dates are formatted as YYYY-MM-DD, so sorting them as text sorts them by date.
+            due_dates.sort_unstable();
+            due_dates.dedup();
+            if !due_dates.is_empty() {
+                context.frontmatter.insert(
+                    Value::String(key.clone()),
+                    Value::Sequence(due_dates.into_iter().map(Value::String).collect()),
+                );
+            }
+        }
+        PostprocessorResult::Continue
+    }
+}
+
+// Remove the task metadata from `text`, describing it at the end when `style` is
+// `TaskMetadataStyle::Text`. Due dates are added to `due_dates`.
+fn clean_task_text(text: &str, style: TaskMetadataStyle, due_dates: &mut Vec<String>) -> String {
+    let mut descriptions = Vec::new();
+    let mut cleaned = TASK_METADATA_RE
+        .replace_all(text, |captures: &regex::Captures<'_>| {
+            descriptions.extend(describe_task_metadata(captures, due_dates));
+            ""
+        })
+        .into_owned();
+
+    if style == TaskMetadataStyle::Text && !descriptions.is_empty() {
+        let _ = write!(cleaned, " ({})", descriptions.join(", "));
+    }
+    cleaned
+}
+
+// Describe the task metadata matched by `TASK_METADATA_RE`, adding it to `due_dates` when it's a
+// due date.
+fn describe_task_metadata(
+    captures: &regex::Captures<'_>,
+    due_dates: &mut Vec<String>,
+) -> Option<String> {
+    if let (Some(signifier), Some(date)) = (captures.name("date_signifier"), captures.name("date"))
+    {
+        let kind = TASK_DATE_SIGNIFIERS
+            .iter()
+            .find(|(emoji, _)| *emoji == signifier.as_str())
+            .map_or("due", |(_, kind)| kind);
+        if kind == "due" {
+            due_dates.push(date.as_str().to_owned());
+        }
+        return Some(format!("{kind} {}", date.as_str()));
+    }
+    if let Some(signifier) = captures.name("priority") {
+        let priority = TASK_PRIORITIES
+            .iter()
+            .find(|(emoji, _)| *emoji == signifier.as_str())
+            .map_or("normal", |(_, priority)| priority);
+        return Some(format!("{priority} priority"));
+    }
+    captures
+        .name("recurrence")
+        .map(|recurrence| recurrence.as_str().to_owned())
+}
+
 #[test]
 fn test_filter_tags() {
     let tags = vec![
@@ -60,48 +1411,203 @@ fn test_filter_tags() {
     ];
     let empty_tags = vec![];
     assert_eq!(
-        filter_by_tags_(&empty_tags, &[], &[]),
+        filter_by_tags_(&empty_tags, &[], &[], false),
         PostprocessorResult::Continue,
         "When no exclusion & inclusion are specified, files without tags are included"
     );
     assert_eq!(
-        filter_by_tags_(&tags, &[], &[]),
+        filter_by_tags_(&tags, &[], &[], false),
         PostprocessorResult::Continue,
         "When no exclusion & inclusion are specified, files with tags are included"
     );
     assert_eq!(
-        filter_by_tags_(&tags, &["exclude".into()], &[]),
+        filter_by_tags_(&tags, &["exclude".into()], &[], false),
         PostprocessorResult::Continue,
         "When exclusion tags don't match files with tags are included"
     );
     assert_eq!(
-        filter_by_tags_(&empty_tags, &["exclude".into()], &[]),
+        filter_by_tags_(&empty_tags, &["exclude".into()], &[], false),
         PostprocessorResult::Continue,
         "When exclusion tags don't match files without tags are included"
     );
     assert_eq!(
-        filter_by_tags_(&tags, &[], &["publish".into()]),
+        filter_by_tags_(&tags, &[], &["publish".into()], false),
         PostprocessorResult::Continue,
         "When exclusion tags don't match files with tags are included"
     );
     assert_eq!(
-        filter_by_tags_(&empty_tags, &[], &["include".into()]),
+        filter_by_tags_(&empty_tags, &[], &["include".into()], false),
         PostprocessorResult::StopAndSkipNote,
         "When inclusion tags are specified files without tags are excluded"
     );
     assert_eq!(
-        filter_by_tags_(&tags, &[], &["include".into()]),
+        filter_by_tags_(&tags, &[], &["include".into()], false),
         PostprocessorResult::StopAndSkipNote,
         "When exclusion tags don't match files with tags are exluded"
     );
     assert_eq!(
-        filter_by_tags_(&tags, &["skip".into()], &["skip".into()]),
+        filter_by_tags_(&tags, &["skip".into()], &["skip".into()], false),
         PostprocessorResult::StopAndSkipNote,
         "When both inclusion and exclusion tags are the same exclusion wins"
     );
     assert_eq!(
-        filter_by_tags_(&tags, &["skip".into()], &["publish".into()]),
+        filter_by_tags_(&tags, &["skip".into()], &["publish".into()], false),
         PostprocessorResult::StopAndSkipNote,
         "When both inclusion and exclusion tags match exclusion wins"
     );
+
+    let nested_tags = vec![Value::String("project/active".into())];
+    assert_eq!(
+        filter_by_tags_(&nested_tags, &["project".into()], &[], false),
+        PostprocessorResult::Continue,
+        "When matching exactly, parent tags don't match nested tags"
+    );
+    assert_eq!(
+        filter_by_tags_(&nested_tags, &["project".into()], &[], true),
+        PostprocessorResult::StopAndSkipNote,
+        "When matching hierarchically, parent tags match nested tags"
+    );
+    assert_eq!(
+        filter_by_tags_(&nested_tags, &["project/active".into()], &[], true),
+        PostprocessorResult::StopAndSkipNote,
+        "When matching hierarchically, exact tags still match"
+    );
+    assert_eq!(
+        filter_by_tags_(
+            &[Value::String("projects".into())],
+            &["project".into()],
+            &[],
+            true
+        ),
+        PostprocessorResult::Continue,
+        "When matching hierarchically, tags which merely share a prefix don't match"
+    );
+    assert_eq!(
+        filter_by_tags_(
+            &[Value::String("project".into())],
+            &["project/active".into()],
+            &[],
+            true
+        ),
+        PostprocessorResult::Continue,
+        "When matching hierarchically, nested tags don't match parent tags"
+    );
+    assert_eq!(
+        filter_by_tags_(&nested_tags, &[], &["project".into()], true),
+        PostprocessorResult::Continue,
+        "When matching hierarchically, inclusion tags match nested tags"
+    );
+}
+
+#[test]
+fn test_remap_frontmatter_key() {
+    let remap = |frontmatter: &str, merge: bool| {
+        let mut context = Context::new(PathBuf::from("note.md"), PathBuf::from("note.md"));
+        context.frontmatter = serde_yaml::from_str(frontmatter).unwrap();
+        let postprocessor = remap_frontmatter_key("tags".into(), "categories".into(), merge);
+        assert_eq!(
+            postprocessor(&mut context, &mut vec![]),
+            PostprocessorResult::Continue
+        );
+        serde_yaml::to_string(&context.frontmatter).unwrap()
+    };
+
+    assert_eq!(
+        remap("title: Note", false),
+        "title: Note\n",
+        "Notes without the source key are left untouched"
+    );
+    assert_eq!(
+        remap("tags: [a, b]", false),
+        "categories:\n- a\n- b\n",
+        "The source key is moved to the destination key"
+    );
+    assert_eq!(
+        remap("tags: [a, b]\ncategories: [c]", false),
+        "categories:\n- a\n- b\n",
+        "Without merge, an existing destination key is overwritten"
+    );
+    assert_eq!(
+        remap("tags: [a, b]\ncategories: [b, c]", true),
+        "categories:\n- b\n- c\n- a\n",
+        "With merge, values are appended to an existing sequence"
+    );
+    assert_eq!(
+        remap("tags: a\ncategories: [c]", true),
+        "categories:\n- c\n- a\n",
+        "With merge, single values are appended to an existing sequence"
+    );
+    assert_eq!(
+        remap("tags: [a]", true),
+        "categories:\n- a\n",
+        "With merge, a missing destination key is created"
+    );
+    assert_eq!(
+        remap("tags: [a]\ncategories: c", true),
+        "tags:\n- a\ncategories: c\n",
+        "With merge, frontmatter is left untouched when the destination isn't a sequence"
+    );
+}
+
+#[test]
+fn test_blockquote_span() {
+    use pulldown_cmark::Parser;
+
+    let markdown = "Before.
+
+> [!note] A callout
+> ```rust
+> > not a nested blockquote
+> ```
+>
+> - A list
+>   - Nested item
+>
+> > A nested blockquote
+
+After.
+";
+    let events: Vec<Event<'_>> = Parser::new(markdown).collect();
+    let start = events
+        .iter()
+        .position(|event| matches!(event, Event::Start(Tag::BlockQuote(_))))
+        .unwrap();
+
+    let span = blockquote_span(&events, start).unwrap();
+    assert_eq!(span.start, start);
+    assert!(matches!(
+        events.get(span.end.saturating_sub(1)),
+        Some(Event::End(TagEnd::BlockQuote(_)))
+    ));
+    assert_eq!(
+        events.get(span.end),
+        Some(&Event::Start(Tag::Paragraph)),
+        "The span should end right before the paragraph following the callout"
+    );
+    assert!(events
+        .get(span.clone())
+        .unwrap()
+        .iter()
+        .any(|event| matches!(event, Event::Start(Tag::CodeBlock(_)))));
+    assert!(events
+        .get(span.clone())
+        .unwrap()
+        .iter()
+        .any(|event| matches!(event, Event::Start(Tag::List(_)))));
+
+    let nested_start = events
+        .iter()
+        .enumerate()
+        .skip(start.saturating_add(1))
+        .find(|(_, event)| matches!(event, Event::Start(Tag::BlockQuote(_))))
+        .map(|(idx, _)| idx)
+        .unwrap();
+    let nested_span = blockquote_span(&events, nested_start).unwrap();
+    assert!(nested_span.start > span.start && nested_span.end < span.end);
+
+    assert_eq!(blockquote_span(&events, 0), None);
+    assert_eq!(
+        blockquote_span(events.get(..span.end.saturating_sub(1)).unwrap(), start),
+        None
+    );
 }
@@ -1,44 +1,73 @@
 use std::fmt;
 use std::sync::LazyLock;
 
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
 use regex::Regex;
 
 static OBSIDIAN_NOTE_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"^(?P<file>[^#|]+)??(#(?P<section>.+?))??(\|(?P<label>.+?))??$").unwrap()
 });
 
+/// The marker which separates the start and the end of a range of sections, as in
+/// `![[note#start#..#end]]`.
+pub const SECTION_RANGE_MARKER: &str = "#..#";
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
 /// `ObsidianNoteReference` represents the structure of a `[[note]]` or `![[embed]]` reference.
 pub struct ObsidianNoteReference<'a> {
     /// The file (note name or partial path) being referenced.
     /// This will be None in the case that the reference is to a section within the same document
     pub file: Option<&'a str>,
-    /// If specific, a specific section/heading being referenced.
+    /// If specific, a specific section/heading being referenced. This may be a heading path
+    /// (`note#parent#heading`), naming the headings the referenced heading is nested under.
     pub section: Option<&'a str>,
+    /// If specific, the heading which ends a range of sections starting at `section`
+    /// (`![[note#start#..#end]]`). Ranges are only meaningful for embeds; links to a range link
+    /// to its start.
+    pub section_end: Option<&'a str>,
     /// If specific, the custom label/text which was specified.
     pub label: Option<&'a str>,
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 /// `RefParserState` enumerates all the possible parsing states [`RefParser`] may enter.
 pub enum RefParserState {
+    /// No reference has been started.
     NoState,
+    /// The first `[` of a reference was found.
     ExpectSecondOpenBracket,
+    /// The opening `[[` of a reference was found.
     ExpectRefText,
+    /// Text of the reference was found, which may be followed by more text or the closing `]]`.
     ExpectRefTextOrCloseBracket,
+    /// The first `]` of the closing `]]` was found.
     ExpectFinalCloseBracket,
+    /// The reference was completed or turned out not to be a reference, so the parser should be
+    /// reset before feeding it more events.
     Resetting,
 }
 
 /// `RefType` indicates whether a note reference is a link (`[[note]]`) or embed (`![[embed]]`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum RefType {
+    /// A link to a note or file (`[[note]]`).
     Link,
+    /// An embedded note or file (`![[note]]`).
     Embed,
 }
 
 /// `RefParser` holds state which is used to parse Obsidian `WikiLinks` (`[[note]]`, `![[embed]]`).
+///
+/// Events from a [`pulldown_cmark::Parser`] are fed to it through [`RefParser::feed`]. See
+/// [`extract_references`] to find all references in a note at once.
+#[non_exhaustive]
 pub struct RefParser {
+    /// The current state of the parser.
     pub state: RefParserState,
+    /// The type of the reference being parsed, once it has been started.
     pub ref_type: Option<RefType>,
     // References sometimes come in through multiple events. One example of this is when notes
     // start with an underscore (_), presumably because this is also the literal which starts
@@ -49,7 +78,15 @@ pub struct RefParser {
     pub ref_text: String,
 }
 
+impl Default for RefParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RefParser {
+    /// Create a new `RefParser`, which hasn't started parsing a reference.
+    #[must_use]
     pub const fn new() -> Self {
         Self {
             state: RefParserState::NoState,
@@ -58,10 +95,95 @@ impl RefParser {
         }
     }
 
+    /// Advance the parser with the next Markdown `event` of a note.
+    ///
+    /// Returns true when `event` completes a reference, which is then described by `ref_type` and
+    /// `ref_text`. When `event` can't be part of a reference, the parser transitions to
+    /// [`RefParserState::Resetting`], or stays in [`RefParserState::NoState`] when no reference
+    /// was started. In both of those cases, the events fed since the last reset aren't part of a
+    /// reference.
+    pub fn feed(&mut self, event: &Event<'_>) -> bool {
+        match (&self.state, event) {
+            (RefParserState::NoState, Event::Text(CowStr::Borrowed("!["))) => {
+                self.ref_type = Some(RefType::Embed);
+                self.transition(RefParserState::ExpectSecondOpenBracket);
+            }
+            (RefParserState::NoState, Event::Text(CowStr::Borrowed("["))) => {
+                self.ref_type = Some(RefType::Link);
+                self.transition(RefParserState::ExpectSecondOpenBracket);
+            }
+            (RefParserState::NoState, _) => {}
+            (RefParserState::ExpectSecondOpenBracket, Event::Text(CowStr::Borrowed("["))) => {
+                self.transition(RefParserState::ExpectRefText);
+            }
+            (RefParserState::ExpectRefText, Event::Text(CowStr::Borrowed("]"))) => {
+                self.transition(RefParserState::Resetting);
+            }
+            (RefParserState::ExpectRefTextOrCloseBracket, Event::Text(CowStr::Borrowed("]"))) => {
+                self.transition(RefParserState::ExpectFinalCloseBracket);
+            }
+            (
+                RefParserState::ExpectRefText | RefParserState::ExpectRefTextOrCloseBracket,
+                Event::Text(text),
+            ) => {
+                self.ref_text.push_str(text);
+                self.transition(RefParserState::ExpectRefTextOrCloseBracket);
+            }
+            (
+                RefParserState::ExpectRefText | RefParserState::ExpectRefTextOrCloseBracket,
+                Event::Code(code),
+            ) => {
+                self.push_code(code);
+                self.transition(RefParserState::ExpectRefTextOrCloseBracket);
+            }
+            (
+                RefParserState::ExpectRefText | RefParserState::ExpectRefTextOrCloseBracket,
+                Event::InlineMath(math),
+            ) => {
+                self.push_math(math);
+                self.transition(RefParserState::ExpectRefTextOrCloseBracket);
+            }
+            (RefParserState::ExpectFinalCloseBracket, Event::Text(CowStr::Borrowed("]"))) => {
+                return true;
+            }
+            _ => self.transition(RefParserState::Resetting),
+        }
+        false
+    }
+
+    /// Move the parser to `new_state`.
     pub fn transition(&mut self, new_state: RefParserState) {
         self.state = new_state;
     }
 
+    /// Append the inline code `code` to `ref_text`, wrapped in backticks as it was written.
+    pub fn push_code(&mut self, code: &str) {
+        // Code containing backticks must have been delimited by a longer run of backticks.
+        let longest_run = code
+            .split(|char| char != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        let fence = "`".repeat(longest_run.saturating_add(1));
+        let padding = match code.starts_with('`') || code.ends_with('`') {
+            true => " ",
+            false => "",
+        };
+        self.ref_text.push_str(&fence);
+        self.ref_text.push_str(padding);
+        self.ref_text.push_str(code);
+        self.ref_text.push_str(padding);
+        self.ref_text.push_str(&fence);
+    }
+
+    /// Append the inline math `math` to `ref_text`, wrapped in dollar signs as it was written.
+    pub fn push_math(&mut self, math: &str) {
+        self.ref_text.push('$');
+        self.ref_text.push_str(math);
+        self.ref_text.push('$');
+    }
+
+    /// Reset the parser, discarding the reference parsed so far.
     pub fn reset(&mut self) {
         self.state = RefParserState::NoState;
         self.ref_type = None;
@@ -69,22 +191,115 @@ impl RefParser {
     }
 }
 
+/// Extract all references (`[[note]]` links and `![[note]]` embeds) from the note with `content`.
+///
+/// This parses `content` the same way as [`Exporter`][crate::Exporter] does, so references in
+/// code and in the frontmatter are left out. References are returned in the order they appear in,
+/// without resolving them to files in the vault.
+///
+/// # Example
+///
+/// ```
+/// # use obsidian_export::{extract_references, RefType};
+/// let references = extract_references("See [[Note#Heading|a label]].\n\n![[image.png]]\n");
+/// let (ref_type, reference) = references.first().unwrap();
+/// assert_eq!(*ref_type, RefType::Link);
+/// assert_eq!(reference.file, Some("Note"));
+/// assert_eq!(reference.section, Some("Heading"));
+/// assert_eq!(reference.label, Some("a label"));
+/// assert_eq!(references.get(1).unwrap().0, RefType::Embed);
+/// ```
+#[must_use]
+#[allow(clippy::module_name_repetitions)]
+pub fn extract_references(content: &str) -> Vec<(RefType, ObsidianNoteReference<'_>)> {
+    extract_reference_texts(content)
+        .into_iter()
+        .map(|(ref_type, text)| (ref_type, ObsidianNoteReference::from_str(text)))
+        .collect()
+}
+
+/// Like [`extract_references`], but return the text between the brackets of each reference (like
+/// `note#section|label`) as written in `content`, rather than parsing it.
+pub fn extract_reference_texts(content: &str) -> Vec<(RefType, &str)> {
+    let parser_options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_MATH
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut ref_parser = RefParser::new();
+    let mut references = vec![];
+    // References are returned as slices of `content`, which span the events of the reference text.
+    let mut ref_text_range: Option<(usize, usize)> = None;
+    let mut in_frontmatter = false;
+    for (event, range) in Parser::new_ext(content, parser_options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => in_frontmatter = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_frontmatter = false,
+            _ if in_frontmatter => {}
+            _ => {
+                if ref_parser.state == RefParserState::Resetting {
+                    ref_parser.reset();
+                    ref_text_range = None;
+                }
+                if ref_parser.feed(&event) {
+                    let ref_text = ref_text_range.and_then(|(start, end)| content.get(start..end));
+                    if let (Some(ref_type), Some(ref_text)) = (ref_parser.ref_type, ref_text) {
+                        references.push((ref_type, ref_text));
+                    }
+                    ref_parser.transition(RefParserState::Resetting);
+                    continue;
+                }
+                if ref_parser.state == RefParserState::ExpectRefTextOrCloseBracket {
+                    let start = ref_text_range.map_or(range.start, |(start, _)| start);
+                    ref_text_range = Some((start, range.end));
+                }
+            }
+        }
+    }
+    references
+}
+
 impl<'a> ObsidianNoteReference<'a> {
+    /// Parse the text between the brackets of a reference, like `note#section|label`.
+    // This can't implement `FromStr`, because the reference borrows from `text`.
+    #[must_use]
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(text: &str) -> ObsidianNoteReference<'_> {
         let captures = OBSIDIAN_NOTE_LINK_RE
             .captures(text)
             .expect("note link regex didn't match - bad input?");
-        let file = captures.name("file").map(|v| v.as_str().trim());
+        // Within tables, the pipe in front of the label is escaped (`[[note\|label]]`), leaving a
+        // backslash at the end of the part before it in the text of references taken straight
+        // from the source of a note.
+        let pipe = captures
+            .name("label")
+            .map(|label| label.start().saturating_sub(1));
+        let before_label = |part: regex::Match<'_>| {
+            let escaped = Some(part.end()) == pipe && part.as_str().ends_with('\\');
+            let end = part.end().saturating_sub(usize::from(escaped));
+            text.get(part.start()..end).unwrap_or_default()
+        };
+        let file = captures.name("file").map(|v| before_label(v).trim());
         let label = captures.name("label").map(|v| v.as_str());
-        let section = captures.name("section").map(|v| v.as_str().trim());
+        let section = captures.name("section").map(before_label);
+        let (section, section_end) = match section.map(|v| v.split_once(SECTION_RANGE_MARKER)) {
+            Some(Some((start, end))) => (Some(start.trim()), Some(end.trim())),
+            _ => (section.map(str::trim), None),
+        };
 
         ObsidianNoteReference {
             file,
             section,
+            section_end,
             label,
         }
     }
 
+    /// Return the text a link to this reference is displayed with: its label, or the referenced
+    /// file and section when it has no label.
+    #[must_use]
     pub fn display(&self) -> String {
         format!("{self}")
     }
@@ -110,6 +325,63 @@ impl<'a> fmt::Display for ObsidianNoteReference<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn extract_references_from_note() {
+        let content = "---\nrelated: \"[[In frontmatter]]\"\n---\n# Note\n\nSee [[Other note]], [[Other note#Some heading|a label]] and [[#Local]].\n\n![[image.png]] ![[Note#Start#..#End]] [[Label with `code`]]\n\n| Table |\n| --- |\n| [[Note\\|alias]] |\n\n`[[In code]]`\n\n```\n[[In a code block]]\n```\n";
+        let reference = |file, section, section_end, label| ObsidianNoteReference {
+            file,
+            section,
+            section_end,
+            label,
+        };
+        assert_eq!(
+            extract_references(content),
+            vec![
+                (
+                    RefType::Link,
+                    reference(Some("Other note"), None, None, None)
+                ),
+                (
+                    RefType::Link,
+                    reference(
+                        Some("Other note"),
+                        Some("Some heading"),
+                        None,
+                        Some("a label")
+                    )
+                ),
+                (RefType::Link, reference(None, Some("Local"), None, None)),
+                (
+                    RefType::Embed,
+                    reference(Some("image.png"), None, None, None)
+                ),
+                (
+                    RefType::Embed,
+                    reference(Some("Note"), Some("Start"), Some("End"), None)
+                ),
+                (
+                    RefType::Link,
+                    reference(Some("Label with `code`"), None, None, None)
+                ),
+                (
+                    RefType::Link,
+                    reference(Some("Note"), None, None, Some("alias"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn push_inline_code_and_math() {
+        let mut parser = RefParser::new();
+        parser.ref_text.push_str("Note|");
+        parser.push_code("code");
+        parser.push_code("a`b");
+        parser.push_code("`");
+        parser.push_math("x^2");
+        assert_eq!(parser.ref_text, "Note|`code```a`b```` ` ``$x^2$");
+    }
+
     #[test]
     fn parse_note_refs_from_strings() {
         assert_eq!(
@@ -118,6 +390,7 @@ mod tests {
                 file: Some("Just a note"),
                 label: None,
                 section: None,
+                section_end: None,
             }
         );
         assert_eq!(
@@ -126,6 +399,7 @@ mod tests {
                 file: Some("A note?"),
                 label: None,
                 section: None,
+                section_end: None,
             }
         );
         assert_eq!(
@@ -134,6 +408,7 @@ mod tests {
                 file: Some("Note"),
                 label: None,
                 section: Some("with heading"),
+                section_end: None,
             }
         );
         assert_eq!(
@@ -142,6 +417,25 @@ mod tests {
                 file: Some("Note"),
                 label: Some("Label"),
                 section: Some("Heading"),
+                section_end: None,
+            }
+        );
+        assert_eq!(
+            ObsidianNoteReference::from_str("Note#Parent#Heading|Label"),
+            ObsidianNoteReference {
+                file: Some("Note"),
+                label: Some("Label"),
+                section: Some("Parent#Heading"),
+                section_end: None,
+            }
+        );
+        assert_eq!(
+            ObsidianNoteReference::from_str("Note#Start #..# End|Label"),
+            ObsidianNoteReference {
+                file: Some("Note"),
+                label: Some("Label"),
+                section: Some("Start"),
+                section_end: Some("End"),
             }
         );
         assert_eq!(
@@ -150,6 +444,7 @@ mod tests {
                 file: None,
                 label: Some("Label"),
                 section: Some("Heading"),
+                section_end: None,
             }
         );
     }
@@ -162,6 +457,7 @@ mod tests {
                 file: Some("Note"),
                 label: None,
                 section: None,
+                section_end: None,
             }
             .display()
         );
@@ -171,6 +467,7 @@ mod tests {
                 file: Some("Note"),
                 label: None,
                 section: Some("Heading"),
+                section_end: None,
             }
             .display()
         );
@@ -180,6 +477,7 @@ mod tests {
                 file: None,
                 label: None,
                 section: Some("Heading"),
+                section_end: None,
             }
             .display()
         );
@@ -189,6 +487,7 @@ mod tests {
                 file: Some("Note"),
                 label: Some("Label"),
                 section: Some("Heading"),
+                section_end: None,
             }
             .display()
         );
@@ -198,6 +497,7 @@ mod tests {
                 file: None,
                 label: Some("Label"),
                 section: Some("Heading"),
+                section_end: None,
             }
             .display()
         );
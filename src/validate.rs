@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use crate::references::{extract_reference_texts, ObsidianNoteReference, RefType};
+use crate::{
+    encoding,
+    lookup_filename_in_vault,
+    section_heading,
+    vault_contents,
+    EncodingFallback,
+    Result,
+    WalkOptions,
+};
+
+/// A link or embed which doesn't resolve to a file (or section) in the vault, as returned by
+/// [`validate_vault`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BrokenLink {
+    /// The path of the note containing the link.
+    pub source: PathBuf,
+    /// The text of the reference, between the brackets of the link (`note#section|label`).
+    pub reference: String,
+    /// Whether the reference is a link (`[[note]]`) or an embed (`![[note]]`).
+    pub kind: RefType,
+    /// Why the reference doesn't resolve.
+    pub reason: BrokenLinkReason,
+}
+
+/// The reasons a [`BrokenLink`] doesn't resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BrokenLinkReason {
+    /// No file in the vault matches the reference.
+    MissingFile,
+    /// The referenced note exists, but doesn't contain the referenced heading or block.
+    MissingSection,
+}
+
+/// Find the links and embeds in the notes of the vault at `root` which don't resolve to a file in
+/// the vault, or to a section within the referenced note.
+///
+/// The vault is walked using `opts` and references are resolved the same way as during an
+/// export, but nothing is exported. Only wikilinks and embeds (`[[note]]`, `![[note]]`) are
+/// checked, not Markdown links. Notes which contain invalid UTF-8 are read lossily.
+///
+/// Broken links are returned in order of the path of the note containing them, followed by their
+/// position in the note.
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use obsidian_export::{validate_vault, WalkOptions};
+/// for link in validate_vault(Path::new("vault"), WalkOptions::default()).unwrap() {
+///     println!(
+///         "{}: {:?} ({:?})",
+///         link.source.display(),
+///         link.reference,
+///         link.reason
+///     );
+/// }
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub fn validate_vault(root: &Path, opts: WalkOptions<'_>) -> Result<Vec<BrokenLink>> {
+    let mut vault_contents = vault_contents(root, opts)?;
+    vault_contents.sort();
+    // The contents of the notes which are referenced, along with their sections.
+    let mut sections: HashMap<PathBuf, (String, Vec<String>)> = HashMap::new();
+    let mut broken_links = vec![];
+
+    for note in vault_contents.iter().filter(|path| is_note(path)) {
+        let content = encoding::read_to_string(note, EncodingFallback::Lossy)?;
+        for (kind, reference) in extract_reference_texts(&content) {
+            let note_ref = ObsidianNoteReference::from_str(reference);
+            let target = note_ref.file.map_or(Some(note), |file| {
+                lookup_filename_in_vault(file, &vault_contents)
+            });
+            let reason = match target {
+                None if note_ref.file.is_some() => Some(BrokenLinkReason::MissingFile),
+                Some(target) if is_note(target) => {
+                    if !sections.contains_key(target) {
+                        let target_content =
+                            encoding::read_to_string(target, EncodingFallback::Lossy)?;
+                        let target_sections = note_sections(&target_content);
+                        sections.insert(target.clone(), (target_content, target_sections));
+                    }
+                    let Some((target_content, known)) = sections.get(target) else {
+                        continue;
+                    };
+                    let exists = |section: &&str| {
+                        known.contains(&section.to_lowercase())
+                            || (section.contains('#')
+                                && section_heading(target_content, section).is_some())
+                    };
+                    [note_ref.section, note_ref.section_end]
+                        .iter()
+                        .flatten()
+                        .any(|section| !exists(section))
+                        .then_some(BrokenLinkReason::MissingSection)
+                }
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                broken_links.push(BrokenLink {
+                    source: note.clone(),
+                    reference: reference.to_owned(),
+                    kind,
+                    reason,
+                });
+            }
+        }
+    }
+    Ok(broken_links)
+}
+
+fn is_note(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "md")
+}
+
+// Return the names of the sections which can be referenced in the note with `content`: the text
+// of its headings and its block identifiers (prefixed with `^`), all in lowercase.
+fn note_sections(content: &str) -> Vec<String> {
+    let mut sections = vec![];
+    let mut heading: Option<String> = None;
+    let mut in_frontmatter = false;
+    for event in Parser::new_ext(content, Options::ENABLE_YAML_STYLE_METADATA_BLOCKS) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => heading = Some(String::new()),
+            Event::Start(Tag::MetadataBlock(_)) => in_frontmatter = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_frontmatter = false,
+            Event::Text(_) if in_frontmatter => {}
+            Event::Text(text) | Event::Code(text) => match heading.as_mut() {
+                Some(heading) => heading.push_str(&text),
+                None => sections.extend(block_id(&text)),
+            },
+            Event::End(TagEnd::Heading(_)) => {
+                sections.extend(heading.take().map(|text| text.trim().to_lowercase()));
+            }
+            _ => {}
+        }
+    }
+    sections
+}
+
+// Return the block identifier at the end of `text` (like `^abc123`), if any.
+fn block_id(text: &str) -> Option<String> {
+    let id = text.split_whitespace().next_back()?;
+    let name = id.strip_prefix('^')?;
+    (!name.is_empty()
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-'))
+    .then(|| id.to_lowercase())
+}
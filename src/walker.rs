@@ -1,23 +1,35 @@
 use std::fmt;
 use std::path::{Path, PathBuf};
 
-use ignore::{DirEntry, Walk, WalkBuilder};
+use ignore::gitignore::GitignoreBuilder;
+use ignore::overrides::Override;
+use ignore::{DirEntry, Error as IgnoreError, Walk, WalkBuilder};
 use snafu::ResultExt;
 
-use crate::{ExportError, WalkDirSnafu};
+use crate::{ExportError, IgnoreFileSnafu, WalkDirSnafu};
 
 type Result<T, E = ExportError> = std::result::Result<T, E>;
 type FilterFn = dyn Fn(&DirEntry) -> bool + Send + Sync + 'static;
 
+/// Directories in which Obsidian stores its configuration and deleted files, rather than notes.
+const OBSIDIAN_SYSTEM_DIRS: &[&str] = &[".obsidian", ".trash"];
+
 /// `WalkOptions` specifies how an Obsidian vault directory is scanned for eligible files to export.
 #[derive(Clone)]
 #[allow(clippy::exhaustive_structs)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct WalkOptions<'a> {
     /// The filename for ignore files, following the
     /// [gitignore](https://git-scm.com/docs/gitignore) syntax.
     ///
     /// By default `.export-ignore` is used.
     pub ignore_filename: &'a str,
+    /// An optional path to an ignore file which applies to the entire vault, in addition to any
+    /// ignore files found within the vault itself.
+    ///
+    /// Patterns in this file follow the [gitignore](https://git-scm.com/docs/gitignore) syntax and
+    /// are matched relative to the root of the vault.
+    pub global_ignore_path: Option<&'a Path>,
     /// Whether to ignore hidden files.
     ///
     /// This is enabled by default.
@@ -27,6 +39,33 @@ pub struct WalkOptions<'a> {
     ///
     /// This is enabled by default.
     pub honor_gitignore: bool,
+    /// Whether to exclude the directories Obsidian uses for its own purposes (`.obsidian` and
+    /// `.trash`), even when hidden files are included.
+    ///
+    /// This is enabled by default.
+    pub exclude_obsidian_system_dirs: bool,
+    /// Whether to follow symbolic links.
+    ///
+    /// When enabled, symlinks to directories are walked as if they were regular directories and
+    /// symlinks which lead to a loop result in [`ExportError::SymlinkLoop`]. When disabled,
+    /// symlinks to directories are skipped, while symlinks to files are still exported.
+    ///
+    /// This is disabled by default.
+    pub follow_symlinks: bool,
+    /// The maximum depth of directories to descend into, relative to the root of the vault.
+    ///
+    /// A depth of `1` only includes files at the root of the vault itself, `2` also includes files
+    /// in directories directly below the root, and so on. By default, there's no limit.
+    pub max_depth: Option<usize>,
+    /// Optional glob patterns which further restrict the files to include, in addition to any
+    /// ignore files.
+    ///
+    /// Globs are matched relative to the root the [`Override`] was built for. When the overrides
+    /// contain any patterns to include, files which don't match any of them are excluded. Patterns
+    /// starting with `!` exclude the files matching them, and later patterns take precedence over
+    /// earlier ones. Unlike [`ignore::WalkBuilder::overrides`], these are applied after the other
+    /// rules, so they can't include files which are hidden or excluded by an ignore file.
+    pub overrides: Option<Override>,
     /// An optional custom filter function which is called for each directory entry to determine if
     /// it should be included or not.
     ///
@@ -42,8 +81,16 @@ impl<'a> fmt::Debug for WalkOptions<'a> {
         };
         f.debug_struct("WalkOptions")
             .field("ignore_filename", &self.ignore_filename)
+            .field("global_ignore_path", &self.global_ignore_path)
             .field("ignore_hidden", &self.ignore_hidden)
             .field("honor_gitignore", &self.honor_gitignore)
+            .field(
+                "exclude_obsidian_system_dirs",
+                &self.exclude_obsidian_system_dirs,
+            )
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("max_depth", &self.max_depth)
+            .field("overrides", &self.overrides)
             .field("filter_fn", &filter_fn_fmt)
             .finish()
     }
@@ -55,17 +102,24 @@ impl<'a> WalkOptions<'a> {
     pub fn new() -> Self {
         WalkOptions {
             ignore_filename: ".export-ignore",
+            global_ignore_path: None,
             ignore_hidden: true,
             honor_gitignore: true,
+            exclude_obsidian_system_dirs: true,
+            follow_symlinks: false,
+            max_depth: None,
+            overrides: None,
             filter_fn: None,
         }
     }
 
-    fn build_walker(self, path: &Path) -> Walk {
+    fn build_walker(self, path: &Path) -> Result<Walk> {
         let mut walker = WalkBuilder::new(path);
         walker
             .standard_filters(false)
             .parents(true)
+            .follow_links(self.follow_symlinks)
+            .max_depth(self.max_depth)
             .hidden(self.ignore_hidden)
             .add_custom_ignore_filename(self.ignore_filename)
             .require_git(true)
@@ -73,10 +127,36 @@ impl<'a> WalkOptions<'a> {
             .git_global(self.honor_gitignore)
             .git_exclude(self.honor_gitignore);
 
-        if let Some(filter) = self.filter_fn {
-            walker.filter_entry(filter);
-        }
-        walker.build()
+        let global_ignore = match self.global_ignore_path {
+            Some(ignore_path) => {
+                let mut builder = GitignoreBuilder::new(path);
+                if let Some(err) = builder.add(ignore_path) {
+                    return Err(err).context(IgnoreFileSnafu { path: ignore_path });
+                }
+                Some(
+                    builder
+                        .build()
+                        .context(IgnoreFileSnafu { path: ignore_path })?,
+                )
+            }
+            None => None,
+        };
+
+        let exclude_system_dirs = self.exclude_obsidian_system_dirs;
+        let overrides = self.overrides;
+        let filter = self.filter_fn;
+        walker.filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            !(exclude_system_dirs && is_dir && is_obsidian_system_dir(entry))
+                && global_ignore.as_ref().map_or(true, |ignore| {
+                    !ignore.matched(entry.path(), is_dir).is_ignore()
+                })
+                && overrides.as_ref().map_or(true, |globs| {
+                    !globs.matched(entry.path(), is_dir).is_ignore()
+                })
+                && filter.map_or(true, |filter_fn| filter_fn(entry))
+        });
+        Ok(walker.build())
     }
 }
 
@@ -86,17 +166,46 @@ impl<'a> Default for WalkOptions<'a> {
     }
 }
 
+fn is_obsidian_system_dir(entry: &DirEntry) -> bool {
+    OBSIDIAN_SYSTEM_DIRS
+        .iter()
+        .any(|dir| entry.file_name() == *dir)
+}
+
+// Return the path at which a symlink loop was found if `err` describes one.
+fn symlink_loop_path(err: &IgnoreError) -> Option<&Path> {
+    match err {
+        IgnoreError::Loop { child, .. } => Some(child),
+        IgnoreError::WithPath { err: inner, .. }
+        | IgnoreError::WithDepth { err: inner, .. }
+        | IgnoreError::WithLineNumber { err: inner, .. } => symlink_loop_path(inner),
+        _ => None,
+    }
+}
+
 /// `vault_contents` returns all of the files in an Obsidian vault located at `path` which would be
 /// exported when using the given [`WalkOptions`].
 pub fn vault_contents(root: &Path, opts: WalkOptions<'_>) -> Result<Vec<PathBuf>> {
     let mut contents = Vec::new();
-    let walker = opts.build_walker(root);
+    let walker = opts.build_walker(root)?;
     for entry in walker {
-        let entry = entry.context(WalkDirSnafu { path: root })?;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                if let Some(path) = symlink_loop_path(&err) {
+                    return Err(ExportError::SymlinkLoop {
+                        path: path.to_path_buf(),
+                    });
+                }
+                return Err(err).context(WalkDirSnafu { path: root });
+            }
+        };
         let path = entry.path();
         let metadata = entry.metadata().context(WalkDirSnafu { path })?;
 
-        if metadata.is_dir() {
+        // Without following symlinks, the metadata of a symlink to a directory doesn't describe a
+        // directory, so these need to be checked separately.
+        if metadata.is_dir() || (entry.path_is_symlink() && path.is_dir()) {
             continue;
         }
         contents.push(path.to_path_buf());
@@ -0,0 +1,263 @@
+use std::fs::{create_dir_all, write};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+// Run the obsidian-export binary with `args`, passing `stdin` to it and returning its stdout.
+fn run_piped(args: &[&str], stdin: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_obsidian-export"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_version_json() {
+    let output = Command::new(env!("CARGO_BIN_EXE_obsidian-export"))
+        .arg("--version-json")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let version: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let keys: Vec<&str> = version
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(
+        vec![
+            "name",
+            "pulldown_cmark",
+            "pulldown_cmark_to_cmark",
+            "serde_yaml",
+            "version"
+        ],
+        keys
+    );
+    let field = |name: &str| version.get(name).and_then(Value::as_str).unwrap();
+    assert_eq!("obsidian-export", field("name"));
+    assert_eq!(env!("CARGO_PKG_VERSION"), field("version"));
+
+    // The dependency versions are kept by hand, so make sure they match Cargo.toml.
+    let manifest: toml::Value = toml::from_str(include_str!("../Cargo.toml")).unwrap();
+    let dependency = |name: &str| {
+        manifest
+            .get("dependencies")
+            .and_then(|dependencies| dependencies.get(name))
+            .and_then(toml::Value::as_str)
+            .unwrap()
+    };
+    assert_eq!(dependency("pulldown-cmark"), field("pulldown_cmark"));
+    assert_eq!(
+        dependency("pulldown-cmark-to-cmark"),
+        field("pulldown_cmark_to_cmark")
+    );
+    assert_eq!(dependency("serde_yaml"), field("serde_yaml"));
+}
+
+#[test]
+fn test_stdin_to_stdout() {
+    assert_eq!(
+        "Some *markdown* with a link to *Note A*\n",
+        run_piped(&["-", "-"], "Some _markdown_ with a link to [[Note A]]")
+    );
+}
+
+#[test]
+fn test_stdin_to_stdout_with_vault() {
+    assert_eq!(
+        "A link to [Note A](Note%20A.md) and an embed:\n\nThis is note A.\n",
+        run_piped(
+            &["--vault", "tests/testdata/input/start-at", "-", "-"],
+            "A link to [[Note A]] and an embed:\n\n![[Note A]]\n"
+        )
+    );
+}
+
+#[test]
+fn test_stdin_section_link_to_itself() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_obsidian-export"))
+        .args(["--vault", "tests/testdata/input/start-at", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"# Sec\n\nSee [[#Sec]].\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    // The headings of the piped note are taken from its content, rather than read from a file.
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+    assert_eq!(
+        "# Sec\n\nSee [Sec](stdin.md#sec).\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_file_to_stdout() {
+    assert_eq!(
+        "This is note A.\n",
+        run_piped(&["tests/testdata/input/start-at/Note A.md", "-"], "")
+    );
+}
+
+#[test]
+fn test_include_and_exclude_globs() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    create_dir_all(vault.join("Templates/Nested")).unwrap();
+    write(vault.join("Note.md"), "A note.\n").unwrap();
+    write(vault.join("image.png"), "").unwrap();
+    write(vault.join("Templates/Daily.md"), "A template.\n").unwrap();
+    write(vault.join("Templates/Nested/Weekly.md"), "A template.\n").unwrap();
+    write(vault.join(".hidden.md"), "A hidden note.\n").unwrap();
+    write(vault.join("Ignored.md"), "An ignored note.\n").unwrap();
+    write(vault.join(".export-ignore"), "Ignored.md\n").unwrap();
+
+    let export = |name: &str, args: &[&str]| -> Vec<String> {
+        let dest = tmp_dir.path().join(name);
+        create_dir_all(&dest).unwrap();
+        let status = Command::new(env!("CARGO_BIN_EXE_obsidian-export"))
+            .args(args)
+            .arg(&vault)
+            .arg(&dest)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let mut files: Vec<String> = WalkDir::new(&dest)
+            .into_iter()
+            .map(Result::unwrap)
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(|entry| {
+                let path = entry.path().strip_prefix(&dest).unwrap();
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        files.sort();
+        files
+    };
+
+    assert_eq!(
+        vec!["Note.md", "image.png"],
+        export("excluded", &["--exclude", "Templates/**"])
+    );
+    assert_eq!(
+        vec!["Note.md"],
+        export(
+            "included",
+            &["--include", "**/*.md", "--exclude", "Templates/**"]
+        )
+    );
+    // A later include matches excluded files again.
+    assert_eq!(
+        vec!["Note.md", "Templates/Daily.md"],
+        export(
+            "reincluded",
+            &[
+                "--include",
+                "**/*.md",
+                "--exclude",
+                "Templates/**",
+                "--include",
+                "Templates/Daily.md"
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_list_files() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir_all(vault.join("Attachments")).unwrap();
+    create_dir_all(&dest).unwrap();
+    write(vault.join("Note.md"), "A note.\n").unwrap();
+    write(vault.join("Attachments/image.png"), "").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_obsidian-export"))
+        .arg("--list-files")
+        .arg(&vault)
+        .arg(&dest)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let line = |path: &Path| {
+        format!(
+            "{} -> {}\n",
+            vault.join(path).display(),
+            dest.join(path).display()
+        )
+    };
+    assert_eq!(
+        [
+            line(&Path::new("Attachments").join("image.png")),
+            line(Path::new("Note.md"))
+        ]
+        .concat(),
+        String::from_utf8(output.stdout).unwrap()
+    );
+    // Nothing is exported.
+    assert_eq!(0, WalkDir::new(&dest).min_depth(1).into_iter().count());
+}
+
+#[test]
+fn test_validate_section_links() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir_all(&vault).unwrap();
+    create_dir_all(&dest).unwrap();
+    write(vault.join("Other.md"), "# Existing heading\n").unwrap();
+    write(
+        vault.join("Note.md"),
+        "[[Other#Existing heading]] and [[Other#Missing heading]]\n",
+    )
+    .unwrap();
+
+    let export = |args: &[&str]| -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_obsidian-export"))
+            .args(args)
+            .arg(&vault)
+            .arg(&dest)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert_eq!("", export(&[]));
+    let stderr = export(&["--validate-section-links"]);
+    assert!(stderr.contains("Other > Missing heading"), "{}", stderr);
+    assert!(!stderr.contains("Existing heading"), "{}", stderr);
+    assert_eq!(
+        "[Other > Existing heading](Other.md#existing-heading) and [Other > Missing heading](Other.md#missing-heading)\n",
+        std::fs::read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
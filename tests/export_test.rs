@@ -1,13 +1,50 @@
 #![allow(clippy::shadow_unrelated)]
 
-use std::fs::{create_dir, read_to_string, set_permissions, File, Permissions};
+use std::collections::HashMap;
+use std::fs::{
+    create_dir,
+    create_dir_all,
+    read_to_string,
+    set_permissions,
+    write,
+    File,
+    Permissions,
+};
 use std::io::prelude::*;
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-
-use obsidian_export::{ExportError, Exporter, FrontmatterStrategy};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use obsidian_export::encoding_rs::WINDOWS_1252;
+use obsidian_export::serde_yaml::Value;
+use obsidian_export::{
+    pulldown_cmark_to_cmark,
+    validate_vault,
+    vault_contents,
+    BacklinkStyle,
+    BrokenLinkReason,
+    Context,
+    EmbedFrontmatterBehavior,
+    EncodingFallback,
+    ExportError,
+    ExportedNote,
+    Exporter,
+    Frontmatter,
+    FrontmatterFallback,
+    FrontmatterFormat,
+    FrontmatterLinkStyle,
+    FrontmatterStrategy,
+    IndexConfig,
+    LineEnding,
+    MissingEmbedStyle,
+    OverwritePolicy,
+    PostprocessorResult,
+    RefType,
+    WalkOptions,
+};
 use pretty_assertions::assert_eq;
+use pulldown_cmark::{Event, Options, Parser, Tag};
 use tempfile::TempDir;
 use walkdir::WalkDir;
 
@@ -50,6 +87,144 @@ fn test_main_variants_with_default_options() {
     }
 }
 
+#[test]
+fn test_export_to_sink() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let destination = tmp_dir.path().join("does-not-exist");
+
+    let mut files = HashMap::new();
+    Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/"),
+        destination.clone(),
+    )
+    .export_to(|path, contents| {
+        files.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    })
+    .expect("exporter returned error");
+
+    assert!(!destination.exists());
+    for entry in WalkDir::new("tests/testdata/expected/main-samples/") {
+        let entry = entry.unwrap();
+        if entry.metadata().unwrap().is_dir() {
+            continue;
+        };
+        let filename = PathBuf::from(entry.file_name());
+        let expected = read_to_string(entry.path()).unwrap();
+        let actual = files
+            .get(&filename)
+            .unwrap_or_else(|| panic!("{} was not passed to sink", filename.display()));
+        assert_eq!(
+            expected,
+            String::from_utf8_lossy(actual),
+            "{} does not have expected content",
+            filename.display()
+        );
+    }
+    assert_eq!(
+        std::fs::read("tests/testdata/input/main-samples/white.png").unwrap(),
+        *files.get(&PathBuf::from("white.png")).unwrap()
+    );
+}
+
+#[test]
+fn test_export_to_sink_error() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let err = Exporter::new(
+        PathBuf::from("tests/testdata/input/single-file/"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .export_to(|_path, _contents| Err(std::io::Error::other("sink is full")))
+    .expect_err("exporter should return sink error");
+
+    match err {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::WriteError { .. } => {}
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+fn test_files_to_export() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/start-at/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.start_at(PathBuf::from("tests/testdata/input/start-at/subdir/"));
+
+    let source = PathBuf::from("tests/testdata/input/start-at/subdir/");
+    assert_eq!(
+        exporter.files_to_export().expect("exporter returned error"),
+        vec![
+            (source.join("Note B.md"), tmp_dir.path().join("Note B.md")),
+            (source.join("Note C.md"), tmp_dir.path().join("Note C.md")),
+        ]
+    );
+    assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_markdown_extensions() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/markdown-extensions/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.markdown_extensions(vec!["md".into(), "markdown".into(), "mdown".into()]);
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        "Link to [Other](Other.mdown).\n\nEmbedded note.\n",
+        read_to_string(tmp_dir.path().join("Note.markdown")).unwrap()
+    );
+    assert!(exporter.is_markdown_file(&PathBuf::from("Note.markdown")));
+    assert!(!exporter.is_markdown_file(&PathBuf::from("Note.txt")));
+}
+
+#[test]
+fn test_markdown_extensions_default() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/markdown-extensions/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    assert!(exporter.is_markdown_file(&PathBuf::from("Note.md")));
+    assert!(!exporter.is_markdown_file(&PathBuf::from("Note.markdown")));
+}
+
+#[test]
+fn test_media_embeds() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    Exporter::new(
+        PathBuf::from("tests/testdata/input/media-embeds/"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .expect("exporter returned error");
+
+    let expected = read_to_string("tests/testdata/expected/media-embeds/Note.md").unwrap();
+    let actual = read_to_string(tmp_dir.path().join("Note.md")).unwrap();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_demote_embedded_headings() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/demote-headings/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.demote_embedded_headings(true);
+    exporter.run().expect("exporter returned error");
+
+    let expected = read_to_string("tests/testdata/expected/demote-headings/Root.md").unwrap();
+    let actual = read_to_string(tmp_dir.path().join("Root.md")).unwrap();
+    assert_eq!(expected, actual);
+}
+
 #[test]
 fn test_frontmatter_never() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
@@ -103,261 +278,1984 @@ fn test_frontmatter_always() {
 }
 
 #[test]
-fn test_exclude() {
+fn test_cmark_options() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-
-    Exporter::new(
+    let mut exporter = Exporter::new(
         PathBuf::from("tests/testdata/input/main-samples/"),
         tmp_dir.path().to_path_buf(),
+    );
+    exporter.cmark_options(pulldown_cmark_to_cmark::Options {
+        list_token: '-',
+        ..Default::default()
+    });
+    exporter.run().expect("exporter returned error");
+
+    let expected = read_to_string("tests/testdata/expected/main-samples/pure-markdown-examples.md")
+        .unwrap()
+        .replace("\n* ", "\n- ");
+    let actual = read_to_string(
+        tmp_dir
+            .path()
+            .join(PathBuf::from("pure-markdown-examples.md")),
     )
-    .run()
-    .expect("exporter returned error");
+    .unwrap();
+    assert_eq!(expected, actual);
+}
 
-    let excluded_note = tmp_dir.path().join(PathBuf::from("excluded-note.md"));
-    assert!(
-        !excluded_note.exists(),
-        "exluded-note.md was found in tmpdir, but should be absent due to .export-ignore rules"
+#[test]
+fn test_link_base() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/link-base/"),
+        tmp_dir.path().to_path_buf(),
     );
+    exporter.link_base("/notes/".to_owned());
+    exporter.run().expect("exporter returned error");
+
+    for filename in ["Note.md", "subdir/Nested note.md"] {
+        let expected =
+            read_to_string(PathBuf::from("tests/testdata/expected/link-base/").join(filename))
+                .unwrap();
+        let actual = read_to_string(tmp_dir.path().join(filename)).unwrap();
+        assert_eq!(
+            expected, actual,
+            "{filename} does not have expected content"
+        );
+    }
 }
 
 #[test]
-fn test_single_file_to_dir() {
+fn test_folder_notes() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-    Exporter::new(
-        PathBuf::from("tests/testdata/input/single-file/note.md"),
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/folder-notes/"),
         tmp_dir.path().to_path_buf(),
-    )
-    .run()
-    .unwrap();
-
-    assert_eq!(
-        read_to_string("tests/testdata/expected/single-file/note.md").unwrap(),
-        read_to_string(tmp_dir.path().join(PathBuf::from("note.md"))).unwrap(),
     );
+    exporter.folder_notes(true);
+    exporter.run().expect("exporter returned error");
+
+    let expected = "Links to [Folder](Folder/Folder.md), [Indexed](Indexed/index.md), [Both](Both.md) and *Docs*.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert_eq!(expected, actual);
 }
 
 #[test]
-fn test_single_file_to_file() {
+fn test_folder_notes_custom_index() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-    let dest = tmp_dir.path().join(PathBuf::from("export.md"));
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/folder-notes/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.folder_notes(true);
+    exporter.folder_note_index("README.md".to_owned());
+    exporter.run().expect("exporter returned error");
+
+    let expected = "Links to [Folder](Folder/Folder.md), *Indexed*, [Both](Both.md) and [Docs](Docs/README.md).\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
 
+#[test]
+fn test_folder_notes_disabled() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
     Exporter::new(
-        PathBuf::from("tests/testdata/input/single-file/note.md"),
-        dest.clone(),
+        PathBuf::from("tests/testdata/input/folder-notes/"),
+        tmp_dir.path().to_path_buf(),
     )
     .run()
-    .unwrap();
+    .expect("exporter returned error");
 
-    assert_eq!(
-        read_to_string("tests/testdata/expected/single-file/note.md").unwrap(),
-        read_to_string(&dest).unwrap(),
-    );
+    // Without folder notes, references to folders without a self-named note remain unresolved.
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert!(actual.contains("*Indexed*"));
+    assert!(actual.contains("*Docs*"));
 }
 
 #[test]
-fn test_start_at_subdir() {
+fn test_daily_note_folder() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
     let mut exporter = Exporter::new(
-        PathBuf::from("tests/testdata/input/start-at/"),
+        PathBuf::from("tests/testdata/input/daily-notes/"),
         tmp_dir.path().to_path_buf(),
     );
-    exporter.start_at(PathBuf::from("tests/testdata/input/start-at/subdir"));
-    exporter.run().unwrap();
+    exporter.daily_note_folder(Some(PathBuf::from("journal")));
+    exporter.run().expect("exporter returned error");
 
-    let expected = if cfg!(windows) {
-        read_to_string("tests/testdata/expected/start-at/subdir/Note B.md")
-            .unwrap()
-            .replace('/', "\\")
-    } else {
-        read_to_string("tests/testdata/expected/start-at/subdir/Note B.md").unwrap()
-    };
+    // Dates without a daily note in the folder, and other references, are resolved as usual.
+    let expected = "Links to [2024-01-15](journal/2024/2024-01-15.md), [2023-12-31](archive/2023-12-31.md) and [Meeting](journal/Meeting.md).\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
 
-    assert_eq!(
-        expected,
-        read_to_string(tmp_dir.path().join(PathBuf::from("Note B.md"))).unwrap(),
+#[test]
+fn test_preserve_wikilinks() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/preserve-wikilinks/"),
+        tmp_dir.path().to_path_buf(),
     );
+    exporter.preserve_wikilinks(true);
+    exporter.run().expect("exporter returned error");
+
+    let expected = read_to_string("tests/testdata/input/preserve-wikilinks/Note.md").unwrap();
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert_eq!(expected, actual);
 }
 
 #[test]
-fn test_start_at_file_within_subdir_destination_is_dir() {
+fn test_preserve_wikilinks_resolved() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
     let mut exporter = Exporter::new(
-        PathBuf::from("tests/testdata/input/start-at/"),
+        PathBuf::from("tests/testdata/input/preserve-wikilinks/"),
         tmp_dir.path().to_path_buf(),
     );
-    exporter.start_at(PathBuf::from(
-        "tests/testdata/input/start-at/subdir/Note B.md",
-    ));
-    exporter.run().unwrap();
+    exporter.preserve_wikilinks(true);
+    exporter.resolve_preserved_wikilinks(true);
+    exporter.run().expect("exporter returned error");
 
-    let expected = if cfg!(windows) {
-        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md")
-            .unwrap()
-            .replace('/', "\\")
-    } else {
-        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md").unwrap()
-    };
+    let expected = read_to_string("tests/testdata/expected/preserve-wikilinks/Note.md").unwrap();
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
 
-    assert_eq!(
-        expected,
-        read_to_string(tmp_dir.path().join(PathBuf::from("Note B.md"))).unwrap(),
+#[test]
+fn test_default_frontmatter() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/default-frontmatter/"),
+        tmp_dir.path().to_path_buf(),
     );
+    let mut defaults = Frontmatter::new();
+    defaults.insert(Value::String("layout".into()), Value::String("note".into()));
+    defaults.insert(Value::String("draft".into()), Value::Bool(false));
+    exporter.default_frontmatter(defaults);
+    exporter.run().expect("exporter returned error");
+
+    let expected = "---\ntitle: A note\ndraft: true\nlayout: note\n---\n\nThis note overrides the draft default.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("overrides.md"))).unwrap();
+    assert_eq!(expected, actual);
+
+    let expected =
+        "---\nlayout: note\ndraft: false\n---\n\nThis note has no frontmatter of its own.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("bare.md"))).unwrap();
+    assert_eq!(expected, actual);
 }
 
 #[test]
-fn test_start_at_file_within_subdir_destination_is_file() {
+fn test_inject_source_path() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-    let dest = tmp_dir.path().join(PathBuf::from("note.md"));
     let mut exporter = Exporter::new(
         PathBuf::from("tests/testdata/input/start-at/"),
-        dest.clone(),
+        tmp_dir.path().to_path_buf(),
     );
-    exporter.start_at(PathBuf::from(
-        "tests/testdata/input/start-at/subdir/Note B.md",
-    ));
-    exporter.run().unwrap();
+    exporter.start_at(PathBuf::from("tests/testdata/input/start-at/subdir"));
+    exporter.inject_source_path("source_path".to_owned());
+    exporter.run().expect("exporter returned error");
 
-    let expected = if cfg!(windows) {
-        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md")
-            .unwrap()
-            .replace('/', "\\")
-    } else {
-        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md").unwrap()
-    };
-    assert_eq!(expected, read_to_string(dest).unwrap(),);
+    let actual = read_to_string(tmp_dir.path().join("Note B.md")).unwrap();
+    assert!(
+        actual.starts_with("---\nsource_path: subdir/Note B.md\n---\n"),
+        "Unexpected frontmatter in:\n{}",
+        actual
+    );
 }
 
 #[test]
-fn test_not_existing_source() {
+fn test_toml_frontmatter() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-
-    let err = Exporter::new(
-        PathBuf::from("tests/testdata/no-such-file.md"),
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/toml-frontmatter/"),
         tmp_dir.path().to_path_buf(),
-    )
-    .run()
-    .unwrap_err();
+    );
+    exporter.frontmatter_format(FrontmatterFormat::Toml);
+    let err = exporter.run().unwrap_err();
 
     match err {
-        ExportError::PathDoesNotExist { .. } => {}
-        _ => panic!("Wrong error variant: {:?}", err),
+        ExportError::FileExportError { path, source } => {
+            assert_eq!(
+                path,
+                PathBuf::from("tests/testdata/input/toml-frontmatter/null.md")
+            );
+            match *source {
+                ExportError::FrontMatterTomlEncodeError { .. } => {}
+                _ => panic!("Wrong error variant for source, got: {:?}", source),
+            }
+        }
+        err => panic!("Wrong error variant: {:?}", err),
     }
+
+    exporter.start_at(PathBuf::from(
+        "tests/testdata/input/toml-frontmatter/note.md",
+    ));
+    exporter.run().expect("exporter returned error");
+
+    let expected = "+++\ntitle = \"A note\"\nweight = 2\ntags = [\"one\", \"two\"]\n+++\n\nThis note has TOML frontmatter when exported.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("note.md"))).unwrap();
+    assert_eq!(expected, actual);
 }
 
 #[test]
-fn test_not_existing_destination_with_source_dir() {
+fn test_lenient_frontmatter() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/lenient-frontmatter/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    // Leaving out frontmatter makes it clear whether it was recognized as such.
+    exporter.frontmatter_strategy(FrontmatterStrategy::Never);
+    exporter.run().expect("exporter returned error");
 
-    let err = Exporter::new(
-        PathBuf::from("tests/testdata/input/main-samples/"),
-        tmp_dir.path().to_path_buf().join("does-not-exist"),
-    )
-    .run()
-    .unwrap_err();
+    // Blank lines and comments before frontmatter are always fine.
+    let expected = "The frontmatter of this note follows a blank line.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("blank-line.md"))).unwrap();
+    assert_eq!(expected, actual);
+    let expected = "<!-- A comment -->\n\nThe frontmatter of this note follows a comment.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("comment.md"))).unwrap();
+    assert_eq!(expected, actual);
+
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("indented.md"))).unwrap();
+    assert!(
+        actual.contains("title: Indented"),
+        "Indented frontmatter isn't recognized by default"
+    );
+
+    exporter.lenient_frontmatter(true);
+    exporter.run().expect("exporter returned error");
+
+    let expected = "The frontmatter of this note has indented fences.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("indented.md"))).unwrap();
+    assert_eq!(expected, actual);
+    let expected = "The frontmatter of this note follows a blank line.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("blank-line.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_non_mapping_frontmatter_errors_by_default() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let err = Exporter::new(
+        PathBuf::from("tests/testdata/input/non-mapping-frontmatter/list.md"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .unwrap_err();
+
+    match err {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::FrontMatterDecodeError { .. } => {}
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+fn test_non_mapping_frontmatter_ignore() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/non-mapping-frontmatter/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.frontmatter_fallback(FrontmatterFallback::Ignore);
+    exporter.run().expect("exporter returned error");
+
+    let expected = "Note with a list as frontmatter.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("list.md"))).unwrap();
+    assert_eq!(expected, actual);
+
+    let expected = "Note with a string as frontmatter.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("scalar.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_non_mapping_frontmatter_wrap_in_key() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/non-mapping-frontmatter/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.frontmatter_fallback(FrontmatterFallback::WrapInKey("value".to_owned()));
+    exporter.run().expect("exporter returned error");
+
+    let expected = "---\nvalue:\n- foo\n- bar\n---\n\nNote with a list as frontmatter.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("list.md"))).unwrap();
+    assert_eq!(expected, actual);
+
+    let expected = "---\nvalue: just a string\n---\n\nNote with a string as frontmatter.\n";
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("scalar.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_exclude() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .expect("exporter returned error");
+
+    let excluded_note = tmp_dir.path().join(PathBuf::from("excluded-note.md"));
+    assert!(
+        !excluded_note.exists(),
+        "exluded-note.md was found in tmpdir, but should be absent due to .export-ignore rules"
+    );
+}
+
+#[test]
+fn test_global_ignore_file() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let ignore_file = tmp_dir.path().join("global-ignore");
+    let destination = tmp_dir.path().join("export");
+    create_dir(&destination).unwrap();
+
+    let mut file = File::create(&ignore_file).unwrap();
+    file.write_all(b"excluded-note.md\n/private\n").unwrap();
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/global-ignore/"),
+        destination.clone(),
+    );
+    exporter.walk_options(WalkOptions {
+        global_ignore_path: Some(&ignore_file),
+        ..Default::default()
+    });
+    exporter.run().expect("exporter returned error");
+
+    assert!(destination.join("note.md").exists());
+    assert!(
+        !destination.join("excluded-note.md").exists(),
+        "excluded-note.md was found in tmpdir, but should be absent due to global ignore rules"
+    );
+    assert!(
+        !destination.join("private").exists(),
+        "private/ was found in tmpdir, but should be absent due to global ignore rules"
+    );
+}
+
+#[test]
+fn test_exclude_obsidian_system_dirs() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = tmp_dir.path().join("vault");
+    create_dir(&source).unwrap();
+    create_dir(source.join(".obsidian")).unwrap();
+    create_dir(source.join(".trash")).unwrap();
+    write(source.join("note.md"), "A note.\n").unwrap();
+    write(source.join(".hidden-note.md"), "A hidden note.\n").unwrap();
+    write(source.join(".obsidian/app.json"), "{}\n").unwrap();
+    write(source.join(".trash/deleted.md"), "A deleted note.\n").unwrap();
+
+    for (exclude_system_dirs, expect_system_dirs) in [(true, false), (false, true)] {
+        let destination = tmp_dir.path().join(format!("export-{exclude_system_dirs}"));
+        create_dir(&destination).unwrap();
+
+        let mut exporter = Exporter::new(source.clone(), destination.clone());
+        exporter.walk_options(WalkOptions {
+            ignore_hidden: false,
+            exclude_obsidian_system_dirs: exclude_system_dirs,
+            ..Default::default()
+        });
+        exporter.run().expect("exporter returned error");
+
+        assert!(destination.join("note.md").exists());
+        assert!(destination.join(".hidden-note.md").exists());
+        assert_eq!(
+            expect_system_dirs,
+            destination.join(".obsidian/app.json").exists()
+        );
+        assert_eq!(
+            expect_system_dirs,
+            destination.join(".trash/deleted.md").exists()
+        );
+    }
+}
+
+#[test]
+fn test_walk_options_max_depth() {
+    let source = PathBuf::from("tests/testdata/input/start-at/");
+    let files = |max_depth| {
+        let mut files = vault_contents(
+            &source,
+            WalkOptions {
+                max_depth,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        files.sort();
+        files
+    };
+
+    assert_eq!(vec![source.join("Note A.md")], files(Some(1)));
+    assert_eq!(
+        vec![
+            source.join("Note A.md"),
+            source.join("subdir/Note B.md"),
+            source.join("subdir/Note C.md"),
+        ],
+        files(None)
+    );
+}
+
+#[test]
+fn test_max_depth_with_start_at() {
+    let exported = |max_depth, start_at: Option<&str>| {
+        let tmp_dir = TempDir::new().expect("failed to make tempdir");
+        let mut exporter = Exporter::new(
+            PathBuf::from("tests/testdata/input/start-at/"),
+            tmp_dir.path().to_path_buf(),
+        );
+        exporter.walk_options(WalkOptions {
+            max_depth,
+            ..Default::default()
+        });
+        if let Some(start_at) = start_at {
+            exporter.start_at(PathBuf::from("tests/testdata/input/start-at/").join(start_at));
+        }
+        exporter.run().unwrap();
+
+        let mut files: Vec<PathBuf> = WalkDir::new(tmp_dir.path())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(tmp_dir.path())
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        files.sort();
+        files
+    };
+
+    assert_eq!(vec![PathBuf::from("Note A.md")], exported(Some(1), None));
+    // The depth is relative to the root of the vault, not to the start_at path.
+    assert!(exported(Some(1), Some("subdir")).is_empty());
+    assert_eq!(
+        vec![PathBuf::from("Note B.md"), PathBuf::from("Note C.md")],
+        exported(Some(2), Some("subdir"))
+    );
+}
+
+#[test]
+fn test_reachable_from() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/reachable/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.reachable_from(vec![PathBuf::from(
+        "tests/testdata/input/reachable/Entry.md",
+    )]);
+    exporter.run().unwrap();
+
+    let mut files: Vec<PathBuf> = WalkDir::new(tmp_dir.path())
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.file_type().is_dir())
+        .map(|entry| {
+            entry
+                .path()
+                .strip_prefix(tmp_dir.path())
+                .unwrap()
+                .to_owned()
+        })
+        .collect();
+    files.sort();
+    assert_eq!(
+        vec![
+            PathBuf::from("Entry.md"),
+            PathBuf::from("Linked.md"),
+            PathBuf::from("notes/Deep note.md"),
+            PathBuf::from("picture.png"),
+        ],
+        files,
+        "Island.md only links to the entry note, so it shouldn't be exported"
+    );
+}
+
+#[test]
+fn test_reachable_from_missing_note() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/reachable/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.reachable_from(vec![
+        PathBuf::from("tests/testdata/input/reachable/Entry.md"),
+        PathBuf::from("tests/testdata/input/reachable/Entyr.md"),
+    ]);
+
+    match exporter.run() {
+        Err(ExportError::ReachableFromNotInVault { path }) => assert_eq!(
+            PathBuf::from("tests/testdata/input/reachable/Entyr.md"),
+            path
+        ),
+        other => panic!("expected ReachableFromNotInVault, got {:?}", other),
+    }
+    assert!(!tmp_dir.path().join("Entry.md").exists());
+}
+
+#[test]
+fn test_lowercase_output() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/lowercase/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.lowercase_output(true);
+    exporter.run().unwrap();
+
+    let mut files: Vec<PathBuf> = WalkDir::new(tmp_dir.path())
+        .min_depth(1)
+        .into_iter()
+        .map(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .strip_prefix(tmp_dir.path())
+                .unwrap()
+                .to_owned()
+        })
+        .collect();
+    files.sort();
+    assert_eq!(
+        vec![
+            PathBuf::from("attachments"),
+            PathBuf::from("attachments/image.png"),
+            PathBuf::from("café.md"),
+            PathBuf::from("folder"),
+            PathBuf::from("folder/sub note.md"),
+            PathBuf::from("other note.md"),
+        ],
+        files
+    );
+    assert_eq!(
+        "Links to [Other Note](../other%20note.md), [Café](../caf%C3%A9.md) and embeds ![Image.png](../attachments/image.png).\n",
+        read_to_string(tmp_dir.path().join("folder/sub note.md")).unwrap()
+    );
+    assert_eq!(
+        "Links back to [the note](folder/sub%20note.md#a-heading).\n",
+        read_to_string(tmp_dir.path().join("other note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_flatten_output() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/flatten/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.flatten_output("-".to_owned());
+    exporter.run().unwrap();
+
+    let mut files: Vec<PathBuf> = WalkDir::new(tmp_dir.path())
+        .min_depth(1)
+        .into_iter()
+        .map(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .strip_prefix(tmp_dir.path())
+                .unwrap()
+                .to_owned()
+        })
+        .collect();
+    files.sort();
+    assert_eq!(
+        vec![
+            PathBuf::from("a-b-Note.md"),
+            PathBuf::from("c-Other.md"),
+            PathBuf::from("c-white.png"),
+        ],
+        files
+    );
+    assert_eq!(
+        "Links to [Other](c-Other.md) and embeds ![white.png](c-white.png).\n",
+        read_to_string(tmp_dir.path().join("a-b-Note.md")).unwrap()
+    );
+    assert_eq!(
+        "Links back to [the note](a-b-Note.md).\n",
+        read_to_string(tmp_dir.path().join("c-Other.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_single_file_to_dir() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    Exporter::new(
+        PathBuf::from("tests/testdata/input/single-file/note.md"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .unwrap();
+
+    assert_eq!(
+        read_to_string("tests/testdata/expected/single-file/note.md").unwrap(),
+        read_to_string(tmp_dir.path().join(PathBuf::from("note.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_single_file_to_file() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let dest = tmp_dir.path().join(PathBuf::from("export.md"));
+
+    Exporter::new(
+        PathBuf::from("tests/testdata/input/single-file/note.md"),
+        dest.clone(),
+    )
+    .run()
+    .unwrap();
+
+    assert_eq!(
+        read_to_string("tests/testdata/expected/single-file/note.md").unwrap(),
+        read_to_string(&dest).unwrap(),
+    );
+}
+
+#[test]
+fn test_single_file_resolve_siblings() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir_all(vault.join("sub")).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Note.md"),
+        "[[Sibling]], [[Nested]]\n\n![[Sibling]]\n",
+    )
+    .unwrap();
+    write(vault.join("Sibling.md"), "Sibling text\n").unwrap();
+    write(vault.join("sub/Nested.md"), "Nested text\n").unwrap();
+
+    let mut exporter = Exporter::new(vault.join("Note.md"), dest.clone());
+    exporter.resolve_siblings(true);
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        "[Sibling](Sibling.md), *Nested*\n\nSibling text\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+    assert!(!dest.join("Sibling.md").exists());
+}
+
+#[test]
+fn test_destination_for() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = PathBuf::from("tests/testdata/input/start-at/");
+    let note = source.join("subdir/Note B.md");
+    let mut exporter = Exporter::new(source.clone(), tmp_dir.path().to_path_buf());
+
+    let destination = exporter.destination_for(&note).unwrap();
+    assert_eq!(tmp_dir.path().join("subdir/Note B.md"), destination);
+    exporter.run().unwrap();
+    assert!(destination.exists());
+
+    exporter.start_at(source.join("subdir"));
+    assert_eq!(
+        Some(tmp_dir.path().join("Note B.md")),
+        exporter.destination_for(&note)
+    );
+    assert_eq!(None, exporter.destination_for(&source.join("Note A.md")));
+}
+
+#[test]
+fn test_external_link_handler() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "[Plain](http://example.com/page), [secure](https://example.org), [mail](mailto:me@example.com) and [local](Other.md).\n",
+    )
+    .unwrap();
+
+    let handler = |url: &str| -> Option<String> {
+        url.strip_prefix("http://")
+            .map(|rest| format!("https://{rest}"))
+    };
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.external_link_handler(Some(&handler));
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "[Plain](https://example.com/page), [secure](https://example.org), [mail](mailto:me@example.com) and [local](Other.md).\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_honor_frontmatter_directives() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Skipped.md"),
+        "---\nexport: false\n---\n\nSkipped.\n",
+    )
+    .unwrap();
+    write(
+        vault.join("Moved.md"),
+        "---\nexport-destination: custom/path.md\ntags: [a]\n---\n\nMoved.\n",
+    )
+    .unwrap();
+    write(
+        vault.join("Bare.md"),
+        "---\nexport-frontmatter: never\ntags: [b]\n---\n\nBare.\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(vault, dest.clone());
+    exporter.honor_frontmatter_directives(true);
+    exporter.run().expect("exporter returned error");
+
+    assert!(!dest.join("Skipped.md").exists());
+    assert!(!dest.join("Moved.md").exists());
+    assert_eq!(
+        "---\ntags:\n- a\n---\n\nMoved.\n",
+        read_to_string(dest.join("custom/path.md")).unwrap()
+    );
+    assert_eq!("Bare.\n", read_to_string(dest.join("Bare.md")).unwrap());
+}
+
+#[test]
+fn test_frontmatter_directives_ignored_by_default() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(&src, "---\nexport: false\n---\n\nExported.\n").unwrap();
+
+    Exporter::new(src, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    assert_eq!(
+        "---\nexport: false\n---\n\nExported.\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_iter_notes() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    create_dir_all(vault.join("sub")).unwrap();
+    write(
+        vault.join("Note.md"),
+        "---\ntitle: Note\n---\n\n![[Other]]\n",
+    )
+    .unwrap();
+    write(vault.join("sub/Other.md"), "Other [[Note]].\n").unwrap();
+    write(vault.join("Skipped.md"), "Skipped.\n").unwrap();
+    write(vault.join("image.png"), "").unwrap();
+
+    // The destination doesn't need to exist.
+    let dest = tmp_dir.path().join("dest");
+    let mut exporter = Exporter::new(vault.clone(), dest.clone());
+    exporter.add_postprocessor(
+        &|ctx, _events| match ctx.current_file().ends_with("Skipped.md") {
+            true => PostprocessorResult::StopAndSkipNote,
+            false => PostprocessorResult::Continue,
+        },
+    );
+    let notes: HashMap<PathBuf, ExportedNote> = exporter
+        .iter_notes()
+        .unwrap()
+        .map(|note| {
+            let note = note.unwrap();
+            (note.source.clone(), note)
+        })
+        .collect();
+
+    assert_eq!(notes.len(), 2);
+    let note = notes.get(&vault.join("Note.md")).unwrap();
+    assert_eq!(note.destination, dest.join("Note.md"));
+    assert_eq!(
+        note.frontmatter.get("title"),
+        Some(&Value::String("Note".to_owned()))
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&note.contents),
+        "---\ntitle: Note\n---\n\nOther [Note](Note.md).\n"
+    );
+    let other = notes.get(&vault.join("sub/Other.md")).unwrap();
+    assert_eq!(other.destination, dest.join("sub/Other.md"));
+    assert_eq!(
+        String::from_utf8_lossy(&other.contents),
+        "Other [Note](../Note.md).\n"
+    );
+    assert!(!dest.exists());
+}
+
+#[test]
+fn test_iter_notes_matches_run() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = PathBuf::from("tests/testdata/input/main-samples/");
+    let mut exporter = Exporter::new(source.clone(), tmp_dir.path().to_path_buf());
+    exporter.generate_backlinks(Some(BacklinkStyle::AppendedSection("Backlinks".to_owned())));
+    exporter.run().expect("exporter returned error");
+
+    let notes: Vec<ExportedNote> = Exporter::new(source, tmp_dir.path().to_path_buf())
+        .generate_backlinks(Some(BacklinkStyle::AppendedSection("Backlinks".to_owned())))
+        .iter_notes()
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert!(!notes.is_empty());
+    for note in notes {
+        assert_eq!(
+            read_to_string(&note.destination).unwrap(),
+            String::from_utf8(note.contents).unwrap(),
+            "{} differs",
+            note.source.display()
+        );
+    }
+}
+
+#[test]
+fn test_many_and_long_references() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    let count = 10_000;
+    let long_reference = "word ".repeat(count);
+    let mut content = "[[Other]] [ [[ ]] [x] ![y] ".repeat(count);
+    content.push_str(&format!("[[{}]]\n", long_reference.trim_end()));
+    write(vault.join("Note.md"), content).unwrap();
+    write(vault.join("Other.md"), "Other.\n").unwrap();
+
+    Exporter::new(vault, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    let result = read_to_string(dest.join("Note.md")).unwrap();
+    assert_eq!(result.matches("[Other](Other.md)").count(), count);
+    assert!(
+        result.ends_with(&format!("*{}*\n", long_reference.trim_end())),
+        "{}",
+        result
+    );
+}
+
+#[test]
+fn test_references_with_inline_code_and_math() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(vault.join("Other.md"), "Other.\n").unwrap();
+    write(vault.join("Use `cargo`.md"), "Cargo.\n").unwrap();
+    write(
+        vault.join("Note.md"),
+        "[[Other|the `code` label]], [[Other|$x^2$ math]], [[Use `cargo`]], [[Other|`a]]b`]], [[Other|a [b c]] and [[Other|$a]$]].\n",
+    )
+    .unwrap();
+
+    Exporter::new(vault, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    let output = read_to_string(dest.join("Note.md")).unwrap();
+    assert_eq!(
+        "[the `code` label](Other.md), [$x^2$ math](Other.md), [Use `cargo`](Use%20`cargo`.md), [`a]]b`](Other.md), [a \\[b c](Other.md) and [$a]$](Other.md).\n",
+        output
+    );
+    // Brackets within the labels mustn't end the text of the links early.
+    let events: Vec<Event<'_>> = Parser::new_ext(&output, Options::ENABLE_MATH).collect();
+    let links: Vec<&str> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.as_ref()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            "Other.md",
+            "Other.md",
+            "Use%20`cargo`.md",
+            "Other.md",
+            "Other.md",
+            "Other.md"
+        ],
+        links
+    );
+    assert!(events.contains(&Event::Code("a]]b".into())));
+    assert!(events.contains(&Event::InlineMath("a]".into())));
+}
+
+#[test]
+fn test_section_links_use_explicit_heading_ids() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Other.md"),
+        "# Title {#custom-id}\n\n## Plain heading\n",
+    )
+    .unwrap();
+    write(
+        vault.join("Note.md"),
+        "[[Other#Title]], [[Other#title|Lowercase]], [[Other#Plain heading]] and [[#Local]].\n\n# Local {#local-id}\n",
+    )
+    .unwrap();
+
+    Exporter::new(vault, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    assert_eq!(
+        "[Other > Title](Other.md#custom-id), [Lowercase](Other.md#custom-id), [Other > Plain heading](Other.md#plain-heading) and [Local](Note.md#local-id).\n\n# Local {#local-id}\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_strict_section_links() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(vault.join("Other.md"), "# Existing\n").unwrap();
+    write(
+        vault.join("Note.md"),
+        "[[Other#Existing]], [[Other#Missing|label]] and [[Other#^block]].\n",
+    )
+    .unwrap();
+    write(vault.join("Another.md"), "[[Other#Also missing]]\n").unwrap();
+
+    let mut exporter = Exporter::new(vault.clone(), dest.clone());
+    exporter.strict_section_links(true);
+    match exporter.run() {
+        Err(ExportError::BrokenSectionLinks { links }) => {
+            let found: Vec<_> = links
+                .iter()
+                .map(|link| (link.source.clone(), link.reference.as_str(), link.reason))
+                .collect();
+            assert_eq!(
+                vec![
+                    (
+                        vault.join("Another.md"),
+                        "Other#Also missing",
+                        BrokenLinkReason::MissingSection
+                    ),
+                    (
+                        vault.join("Note.md"),
+                        "Other#Missing|label",
+                        BrokenLinkReason::MissingSection
+                    ),
+                ],
+                found
+            );
+        }
+        other => panic!("expected BrokenSectionLinks, got {:?}", other),
+    }
+    // Notes are exported regardless.
+    assert!(dest.join("Note.md").exists());
+
+    write(vault.join("Another.md"), "[[Other#Existing]]\n").unwrap();
+    write(vault.join("Note.md"), "[[Other#Existing]]\n").unwrap();
+    exporter.run().expect("exporter returned error");
+}
+
+#[test]
+fn test_code_block_fn() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "```js\nlet a = 1;\n```\n\n```\n```\n\n    let b = 2;\n",
+    )
+    .unwrap();
+
+    let handler = |lang: &str, code: &str| -> (String, String) {
+        let lang = if lang == "js" { "javascript" } else { lang };
+        (lang.to_owned(), code.replace("let", "const"))
+    };
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.code_block_fn(Some(&handler));
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "\n````javascript\nconst a = 1;\n````\n\n````\n````\n\n    let b = 2;\n    \n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_render_canvas() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/canvas/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.render_canvas(true);
+    exporter.run().unwrap();
+
+    assert!(!tmp_dir.path().join("Board.canvas").exists());
+    assert_eq!(
+        read_to_string("tests/testdata/expected/canvas/Board.md").unwrap(),
+        read_to_string(tmp_dir.path().join("Board.md")).unwrap(),
+    );
+    assert_eq!(
+        "See the [board](Board.md).\n",
+        read_to_string(tmp_dir.path().join("Overview.md")).unwrap(),
+    );
+}
+
+#[test]
+fn test_start_at_subdir() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/start-at/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.start_at(PathBuf::from("tests/testdata/input/start-at/subdir"));
+    exporter.run().unwrap();
+
+    let expected = if cfg!(windows) {
+        read_to_string("tests/testdata/expected/start-at/subdir/Note B.md")
+            .unwrap()
+            .replace('/', "\\")
+    } else {
+        read_to_string("tests/testdata/expected/start-at/subdir/Note B.md").unwrap()
+    };
+
+    assert_eq!(
+        expected,
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note B.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_start_at_many() {
+    let vault = TempDir::new().expect("failed to make tempdir");
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    for dir in ["projects/active", "reference", "other"] {
+        create_dir_all(vault.path().join(dir)).unwrap();
+    }
+    write(
+        vault.path().join("projects/active/Project.md"),
+        "[[Reference]] [[Other]]\n",
+    )
+    .unwrap();
+    write(vault.path().join("reference/Reference.md"), "[[Project]]\n").unwrap();
+    write(vault.path().join("other/Other.md"), "Other\n").unwrap();
+
+    let mut exporter = Exporter::new(vault.path().to_path_buf(), tmp_dir.path().to_path_buf());
+    exporter.start_at_many(vec![
+        vault.path().join("projects"),
+        vault.path().join("reference"),
+    ]);
+    exporter.run().unwrap();
+
+    let mut exported: Vec<_> = WalkDir::new(tmp_dir.path())
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.file_type().is_dir())
+        .map(|entry| {
+            entry
+                .path()
+                .strip_prefix(tmp_dir.path())
+                .unwrap()
+                .to_owned()
+        })
+        .collect();
+    exported.sort();
+    assert_eq!(
+        vec![
+            PathBuf::from("Reference.md"),
+            PathBuf::from("active/Project.md")
+        ],
+        exported
+    );
+    assert_eq!(
+        "[Reference](../Reference.md) [Other](../../other/Other.md)\n",
+        read_to_string(tmp_dir.path().join("active/Project.md")).unwrap()
+    );
+    assert_eq!(
+        "[Project](active/Project.md)\n",
+        read_to_string(tmp_dir.path().join("Reference.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_start_at_many_overlapping() {
+    let vault = TempDir::new().expect("failed to make tempdir");
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    create_dir_all(vault.path().join("a/b")).unwrap();
+    write(vault.path().join("a/Top.md"), "Top\n").unwrap();
+    write(vault.path().join("a/b/Nested.md"), "Nested\n").unwrap();
+
+    // Files are exported relative to the most specific path they're under, whichever order the
+    // paths are given in.
+    for paths in [["a", "a/b"], ["a/b", "a"]] {
+        let mut exporter = Exporter::new(vault.path().to_path_buf(), tmp_dir.path().to_path_buf());
+        exporter.start_at_many(paths.iter().map(|path| vault.path().join(path)).collect());
+        let destinations: Vec<PathBuf> = exporter
+            .files_to_export()
+            .unwrap()
+            .into_iter()
+            .map(|(_, dest)| dest.strip_prefix(tmp_dir.path()).unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            vec![PathBuf::from("Top.md"), PathBuf::from("Nested.md")],
+            destinations,
+            "{:?}",
+            paths
+        );
+    }
+}
+
+#[test]
+fn test_start_at_external_attachments() {
+    let vault = TempDir::new().expect("failed to make tempdir");
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    create_dir_all(vault.path().join("notes/subdir")).unwrap();
+    write(vault.path().join("image.png"), "image").unwrap();
+    write(
+        vault.path().join("notes/subdir/Note.md"),
+        "![[image.png]]\n\n[[image.png|Image]]\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(vault.path().to_path_buf(), tmp_dir.path().to_path_buf());
+    exporter.start_at(vault.path().join("notes"));
+    exporter.external_attachments_dir(Some(PathBuf::from("_attachments")));
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "image",
+        read_to_string(tmp_dir.path().join("_attachments/image.png")).unwrap()
+    );
+    assert_eq!(
+        "![image.png](../_attachments/image.png)\n\n[Image](../_attachments/image.png)\n",
+        read_to_string(tmp_dir.path().join("subdir/Note.md")).unwrap()
+    );
+    assert!(!tmp_dir.path().join("image.png").exists());
+}
+
+#[test]
+fn test_start_at_file_within_subdir_destination_is_dir() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/start-at/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.start_at(PathBuf::from(
+        "tests/testdata/input/start-at/subdir/Note B.md",
+    ));
+    exporter.run().unwrap();
+
+    let expected = if cfg!(windows) {
+        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md")
+            .unwrap()
+            .replace('/', "\\")
+    } else {
+        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md").unwrap()
+    };
+
+    assert_eq!(
+        expected,
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note B.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_start_at_file_within_subdir_destination_is_file() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let dest = tmp_dir.path().join(PathBuf::from("note.md"));
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/start-at/"),
+        dest.clone(),
+    );
+    exporter.start_at(PathBuf::from(
+        "tests/testdata/input/start-at/subdir/Note B.md",
+    ));
+    exporter.run().unwrap();
+
+    let expected = if cfg!(windows) {
+        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md")
+            .unwrap()
+            .replace('/', "\\")
+    } else {
+        read_to_string("tests/testdata/expected/start-at/single-file/Note B.md").unwrap()
+    };
+    assert_eq!(expected, read_to_string(dest).unwrap(),);
+}
+
+#[test]
+fn test_not_existing_source() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let err = Exporter::new(
+        PathBuf::from("tests/testdata/no-such-file.md"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .unwrap_err();
+
+    match err {
+        ExportError::PathDoesNotExist { .. } => {}
+        _ => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+fn test_not_existing_destination_with_source_dir() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let err = Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/"),
+        tmp_dir.path().to_path_buf().join("does-not-exist"),
+    )
+    .run()
+    .unwrap_err();
+
+    match err {
+        ExportError::PathDoesNotExist { .. } => {}
+        _ => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+// This test ensures that when source is a file, but destination points to a
+// regular file inside of a non-existent directory, an error is raised instead
+// of that directory path being created (like `mkdir -p`)
+fn test_not_existing_destination_with_source_file() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let err = Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/obsidian-wikilinks.md"),
+        tmp_dir.path().to_path_buf().join("subdir/does-not-exist"),
+    )
+    .run()
+    .unwrap_err();
+
+    match err {
+        ExportError::PathDoesNotExist { .. } => {}
+        _ => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_source_no_permissions() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source.md");
+    let dest = tmp_dir.path().to_path_buf().join("dest.md");
+
+    let mut file = File::create(&src).unwrap();
+    file.write_all(b"Foo").unwrap();
+    set_permissions(&src, Permissions::from_mode(0o000)).unwrap();
+
+    match Exporter::new(src, dest).run().unwrap_err() {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::ReadError { .. } => {}
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_dest_no_permissions() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source.md");
+    let dest = tmp_dir.path().to_path_buf().join("dest");
+
+    let mut file = File::create(&src).unwrap();
+    file.write_all(b"Foo").unwrap();
+
+    create_dir(&dest).unwrap();
+    set_permissions(&dest, Permissions::from_mode(0o555)).unwrap();
+
+    match Exporter::new(src, dest).run().unwrap_err() {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::WriteError { .. } => {}
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_symlink_loop() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source");
+    let dest = tmp_dir.path().to_path_buf().join("dest");
+    create_dir(&src).unwrap();
+    create_dir(src.join("subdir")).unwrap();
+    create_dir(&dest).unwrap();
+    write(src.join("note.md"), "A note.\n").unwrap();
+    std::os::unix::fs::symlink("..", src.join("subdir/loop")).unwrap();
+
+    // Symlinks to directories are skipped when symlinks aren't followed.
+    Exporter::new(src.clone(), dest.clone())
+        .run()
+        .expect("exporter returned error");
+    assert!(dest.join("note.md").exists());
+    assert!(!dest.join("subdir/loop").exists());
+
+    let mut exporter = Exporter::new(src, dest);
+    exporter.walk_options(WalkOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    });
+    match exporter.run().unwrap_err() {
+        ExportError::SymlinkLoop { path } => assert!(path.ends_with("subdir/loop")),
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+fn test_bom_and_crlf() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source");
+    let dest = tmp_dir.path().to_path_buf().join("dest");
+    create_dir(&src).unwrap();
+    create_dir(&dest).unwrap();
+
+    let mut file = File::create(src.join("Note.md")).unwrap();
+    file.write_all(b"\xEF\xBB\xBF---\r\ntitle: Note\r\n---\r\n\r\nLink to [[Other]].\r\n")
+        .unwrap();
+    let mut file = File::create(src.join("Other.md")).unwrap();
+    file.write_all(b"Other note.\r\n").unwrap();
+
+    Exporter::new(src, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    assert_eq!(
+        "---\ntitle: Note\n---\n\nLink to [Other](Other.md).\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_invalid_utf8_errors_by_default() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source.md");
+    let dest = tmp_dir.path().to_path_buf().join("dest.md");
+
+    let mut file = File::create(&src).unwrap();
+    file.write_all(b"Caf\xe9").unwrap();
+
+    match Exporter::new(src.clone(), dest).run().unwrap_err() {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::CharacterEncodingError { path, .. } => assert_eq!(src, path),
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+fn test_invalid_utf8_lossy() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source.md");
+    let dest = tmp_dir.path().to_path_buf().join("dest.md");
+
+    let mut file = File::create(&src).unwrap();
+    file.write_all(b"Caf\xe9").unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.encoding_fallback(EncodingFallback::Lossy);
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!("Caf\u{FFFD}\n", read_to_string(dest).unwrap());
+}
+
+#[test]
+fn test_invalid_utf8_legacy_encoding() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source.md");
+    let dest = tmp_dir.path().to_path_buf().join("dest.md");
+
+    let mut file = File::create(&src).unwrap();
+    file.write_all(b"Caf\xe9").unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.encoding_fallback(EncodingFallback::Encoding(WINDOWS_1252));
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!("Caf\u{E9}\n", read_to_string(dest).unwrap());
+}
+
+#[test]
+fn test_utf16_notes() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let text = "# Caf\u{E9}\n\nSome text.\n";
+
+    let mut little_endian = vec![0xFF, 0xFE];
+    little_endian.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    let mut big_endian = vec![0xFE, 0xFF];
+    big_endian.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+
+    for (name, bytes) in [("le", little_endian), ("be", big_endian)] {
+        let src = tmp_dir.path().join(format!("source-{name}.md"));
+        let dest = tmp_dir.path().join(format!("dest-{name}.md"));
+        write(&src, bytes).unwrap();
+
+        Exporter::new(src, dest.clone())
+            .run()
+            .expect("exporter returned error");
+
+        assert_eq!(text, read_to_string(dest).unwrap());
+    }
+}
+
+#[test]
+fn test_invalid_utf16() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().to_path_buf().join("source.md");
+    let dest = tmp_dir.path().to_path_buf().join("dest.md");
+
+    // An unpaired high surrogate (U+D800) following the UTF-16LE byte order mark.
+    write(&src, b"\xFF\xFEC\x00\x00\xD8").unwrap();
+
+    match Exporter::new(src.clone(), dest.clone()).run().unwrap_err() {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::Utf16EncodingError { path } => assert_eq!(src, path),
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.encoding_fallback(EncodingFallback::Lossy);
+    exporter.run().expect("exporter returned error");
+    assert_eq!("C\u{FFFD}\n", read_to_string(dest).unwrap());
+}
+
+#[test]
+fn test_infinite_recursion() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let err = Exporter::new(
+        PathBuf::from("tests/testdata/input/infinite-recursion/"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .unwrap_err();
+
+    match err {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::RecursionLimitExceeded { .. } => {}
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
+#[test]
+fn test_no_recursive_embeds() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/infinite-recursion/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.process_embeds_recursively(false);
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        read_to_string("tests/testdata/expected/infinite-recursion/Note A.md").unwrap(),
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note A.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_embed_cycle_prefix() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/infinite-recursion/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.process_embeds_recursively(false);
+    exporter.embed_cycle_prefix(Some("Cycle: ".to_owned()));
+    exporter.run().expect("exporter returned error");
+
+    let expected = read_to_string("tests/testdata/expected/infinite-recursion/Note A.md")
+        .unwrap()
+        .replace("→ ", "Cycle: ");
+    assert_eq!(
+        expected,
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note A.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_embed_cycle_prefix_none() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/infinite-recursion/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.process_embeds_recursively(false);
+    exporter.embed_cycle_prefix(None);
+    exporter.run().expect("exporter returned error");
+
+    let expected = read_to_string("tests/testdata/expected/infinite-recursion/Note A.md")
+        .unwrap()
+        .replace("→ ", "");
+    assert_eq!(
+        expected,
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note A.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_deduplicate_embeds() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/deduplicate-embeds/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.deduplicate_embeds(true);
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        read_to_string("tests/testdata/expected/deduplicate-embeds/Note.md").unwrap(),
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_max_embed_bytes() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/embed-size-limit/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.max_embed_bytes(Some(250));
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        read_to_string("tests/testdata/expected/embed-size-limit/Note.md").unwrap(),
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap(),
+    );
+}
+
+#[test]
+fn test_embed_section_by_heading_path() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Note.md"),
+        "![[Doc#Notes]]\n\n![[Doc#Chapter 2#Notes]]\n\n![[Doc#chapter 2 # summary # notes]]\n\n![[Doc#Chapter 1#Summary#Notes]]\n\n[[Doc#Chapter 2#Notes|link]]\n",
+    )
+    .unwrap();
+    write(
+        vault.join("Doc.md"),
+        "# Chapter 1\n\n## Notes\n\nFirst\n\n# Chapter 2\n\n## Notes\n\nSecond\n\n## Summary\n\n### Notes\n\nThird\n",
+    )
+    .unwrap();
+
+    Exporter::new(vault, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    assert_eq!(
+        "## Notes\n\nFirst\n\n## Notes\n\nSecond\n\n### Notes\n\nThird\n\n# Chapter 1\n\n## Notes\n\nFirst\n\n# Chapter 2\n\n## Notes\n\nSecond\n\n## Summary\n\n### Notes\n\nThird\n\n[link](Doc.md#notes-1)\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_embed_section_range() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Note.md"),
+        "![[Doc#Start#..#End]]\n\n![[Doc#Start#Middle]]\n\n[[Doc#Start#..#End|link]]\n",
+    )
+    .unwrap();
+    write(
+        vault.join("Doc.md"),
+        "# Intro\n\nA\n\n## Start\n\nB\n\n### Middle\n\nC\n\n## End\n\nD\n",
+    )
+    .unwrap();
+
+    Exporter::new(vault, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    // A second `#` without the range marker is a heading path, as in Obsidian.
+    assert_eq!(
+        "## Start\n\nB\n\n### Middle\n\nC\n\n### Middle\n\nC\n\n[link](Doc.md#start)\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_embed_section_with_nested_structures() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(vault.join("Note.md"), "![[Source#Section]]\n").unwrap();
+    write(
+        vault.join("Source.md"),
+        "# Section\n\n- Item\n  - Nested\n\n    # Nested heading\n\n| A | B |\n|---|---|\n| 1 | 2 |\n\n## Subsection\n\nText\n\n# Next\n\nNot included\n",
+    )
+    .unwrap();
+
+    Exporter::new(vault, dest.clone())
+        .run()
+        .expect("exporter returned error");
+
+    assert_eq!(
+        "# Section\n\n* Item\n  * Nested\n    \n    # Nested heading\n\n|A|B|\n|-|-|\n|1|2|\n\n## Subsection\n\nText\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_max_embeds_per_note() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
 
-    match err {
-        ExportError::PathDoesNotExist { .. } => {}
-        _ => panic!("Wrong error variant: {:?}", err),
-    }
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/embed-count-limit/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.max_embeds_per_note(Some(2));
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        read_to_string("tests/testdata/expected/embed-count-limit/Note.md").unwrap(),
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap(),
+    );
 }
 
 #[test]
-// This test ensures that when source is a file, but destination points to a
-// regular file inside of a non-existent directory, an error is raised instead
-// of that directory path being created (like `mkdir -p`)
-fn test_not_existing_destination_with_source_file() {
+fn test_skip_attachment_extensions() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
 
-    let err = Exporter::new(
-        PathBuf::from("tests/testdata/input/main-samples/obsidian-wikilinks.md"),
-        tmp_dir.path().to_path_buf().join("subdir/does-not-exist"),
-    )
-    .run()
-    .unwrap_err();
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/skip-attachments/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.skip_attachment_extensions(vec![".Canvas".to_owned()]);
+    exporter.run().expect("exporter returned error");
 
-    match err {
-        ExportError::PathDoesNotExist { .. } => {}
-        _ => panic!("Wrong error variant: {:?}", err),
-    }
+    assert_eq!(
+        read_to_string("tests/testdata/expected/skip-attachments/Note.md").unwrap(),
+        read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap(),
+    );
+    assert!(tmp_dir.path().join("white.png").exists());
+    assert!(!tmp_dir.path().join("Board.canvas").exists());
 }
 
-#[cfg(not(target_os = "windows"))]
 #[test]
-fn test_source_no_permissions() {
+fn test_link_graph() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-    let src = tmp_dir.path().to_path_buf().join("source.md");
-    let dest = tmp_dir.path().to_path_buf().join("dest.md");
+    let source = PathBuf::from("tests/testdata/input/link-graph/");
 
-    let mut file = File::create(&src).unwrap();
-    file.write_all(b"Foo").unwrap();
-    set_permissions(&src, Permissions::from_mode(0o000)).unwrap();
+    let mut exporter = Exporter::new(source.clone(), tmp_dir.path().to_path_buf());
+    exporter.collect_link_graph(true);
+    exporter.run().expect("exporter returned error");
 
-    match Exporter::new(src, dest).run().unwrap_err() {
-        ExportError::FileExportError { source, .. } => match *source {
-            ExportError::ReadError { .. } => {}
-            _ => panic!("Wrong error variant for source, got: {:?}", source),
-        },
-        err => panic!("Wrong error variant: {:?}", err),
-    }
+    let graph = exporter.link_graph().unwrap();
+    assert_eq!(
+        vec![
+            source.join("Note A.md").as_path(),
+            source.join("Note B.md").as_path()
+        ],
+        graph.links_to(&source.join("Note C.md"))
+    );
+    assert_eq!(
+        vec![
+            source.join("Note C.md").as_path(),
+            source.join("white.png").as_path()
+        ],
+        graph.links_from(&source.join("Note B.md"))
+    );
+    assert!(graph.links_from(&source.join("Note C.md")).is_empty());
+    assert!(!tmp_dir.path().join("_backlinks.md").exists());
 }
 
-#[cfg(not(target_os = "windows"))]
 #[test]
-fn test_dest_no_permissions() {
+fn test_validate_vault() {
+    let source = PathBuf::from("tests/testdata/input/broken-links/");
+    let broken_links = validate_vault(&source, WalkOptions::default()).unwrap();
+
+    let found: Vec<_> = broken_links
+        .iter()
+        .map(|link| {
+            (
+                link.source.strip_prefix(&source).unwrap().to_path_buf(),
+                link.reference.as_str(),
+                link.kind,
+                link.reason,
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                PathBuf::from("Note.md"),
+                "Missing note",
+                RefType::Link,
+                BrokenLinkReason::MissingFile
+            ),
+            (
+                PathBuf::from("Note.md"),
+                "existing#No such heading|a label",
+                RefType::Link,
+                BrokenLinkReason::MissingSection
+            ),
+            (
+                PathBuf::from("Note.md"),
+                "Gone.png",
+                RefType::Embed,
+                BrokenLinkReason::MissingFile
+            ),
+            (
+                PathBuf::from("Note.md"),
+                "#Nowhere",
+                RefType::Link,
+                BrokenLinkReason::MissingSection
+            ),
+            (
+                PathBuf::from("Note.md"),
+                "Existing#Details#Heading",
+                RefType::Link,
+                BrokenLinkReason::MissingSection
+            ),
+            (
+                PathBuf::from("sub/Nested.md"),
+                "Note#Elsewhere",
+                RefType::Link,
+                BrokenLinkReason::MissingSection
+            ),
+        ],
+        found
+    );
+}
+
+#[test]
+fn test_generate_backlinks() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
-    let src = tmp_dir.path().to_path_buf().join("source.md");
-    let dest = tmp_dir.path().to_path_buf().join("dest");
 
-    let mut file = File::create(&src).unwrap();
-    file.write_all(b"Foo").unwrap();
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/link-graph/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.generate_backlinks(Some(BacklinkStyle::AppendedSection("Backlinks".to_owned())));
+    exporter.run().expect("exporter returned error");
 
-    create_dir(&dest).unwrap();
-    set_permissions(&dest, Permissions::from_mode(0o555)).unwrap();
+    assert_eq!(
+        "# Heading\n\nLinks to [itself](Note%20C.md#heading) and *Missing*.\n\n## Backlinks\n\n* [Note A](Note%20A.md)\n* [Note B](Note%20B.md)\n",
+        read_to_string(tmp_dir.path().join("Note C.md")).unwrap(),
+    );
 
-    match Exporter::new(src, dest).run().unwrap_err() {
-        ExportError::FileExportError { source, .. } => match *source {
-            ExportError::WriteError { .. } => {}
-            _ => panic!("Wrong error variant for source, got: {:?}", source),
-        },
-        err => panic!("Wrong error variant: {:?}", err),
-    }
+    exporter.generate_backlinks(Some(BacklinkStyle::FrontmatterKey("backlinks".to_owned())));
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        "---\nbacklinks:\n- '[Note A](Note%20A.md)'\n- '[Note B](Note%20B.md)'\n---\n\n# Heading\n\nLinks to [itself](Note%20C.md#heading) and *Missing*.\n",
+        read_to_string(tmp_dir.path().join("Note C.md")).unwrap(),
+    );
+    // Nothing links to Note D, so it's left unchanged.
+    assert_eq!(
+        "Links back to [Note A](../Note%20A.md).\n",
+        read_to_string(tmp_dir.path().join("sub/Note D.md")).unwrap(),
+    );
 }
 
 #[test]
-fn test_infinite_recursion() {
+fn test_generated_backlinks_not_in_link_graph() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = PathBuf::from("tests/testdata/input/link-graph/");
 
-    let err = Exporter::new(
-        PathBuf::from("tests/testdata/input/infinite-recursion/"),
+    let mut exporter = Exporter::new(source.clone(), tmp_dir.path().to_path_buf());
+    exporter.collect_link_graph(true);
+    exporter.generate_backlinks(Some(BacklinkStyle::AppendedSection("Backlinks".to_owned())));
+    exporter.run().expect("exporter returned error");
+
+    // The generated backlinks sections link back to the linking notes, but those links aren't
+    // part of the notes themselves and so don't show up in the graph.
+    let graph = exporter.link_graph().unwrap();
+    assert!(graph.links_from(&source.join("Note C.md")).is_empty());
+    assert_eq!(
+        vec![
+            source.join("Note C.md").as_path(),
+            source.join("white.png").as_path()
+        ],
+        graph.links_from(&source.join("Note B.md"))
+    );
+    assert_eq!(
+        vec![source.join("sub/Note D.md").as_path()],
+        graph.links_to(&source.join("Note A.md"))
+    );
+}
+
+#[test]
+fn test_backlinks_index() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/link-graph/"),
         tmp_dir.path().to_path_buf(),
-    )
-    .run()
-    .unwrap_err();
+    );
+    exporter.backlinks_index(Some(PathBuf::from("_backlinks.md")));
+    exporter.run().expect("exporter returned error");
 
-    match err {
-        ExportError::FileExportError { source, .. } => match *source {
-            ExportError::RecursionLimitExceeded { .. } => {}
-            _ => panic!("Wrong error variant for source, got: {:?}", source),
-        },
-        err => panic!("Wrong error variant: {:?}", err),
-    }
+    assert_eq!(
+        read_to_string("tests/testdata/expected/link-graph/_backlinks.md").unwrap(),
+        read_to_string(tmp_dir.path().join("_backlinks.md")).unwrap(),
+    );
+    assert!(exporter.link_graph().is_some());
 }
 
 #[test]
-fn test_no_recursive_embeds() {
+fn test_emit_index() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
 
     let mut exporter = Exporter::new(
-        PathBuf::from("tests/testdata/input/infinite-recursion/"),
+        PathBuf::from("tests/testdata/input/link-graph/"),
         tmp_dir.path().to_path_buf(),
     );
-    exporter.process_embeds_recursively(false);
+    exporter.emit_index(Some(IndexConfig {
+        path: PathBuf::from("sub/index.md"),
+        title: "All notes".to_owned(),
+        nojekyll: true,
+    }));
     exporter.run().expect("exporter returned error");
 
     assert_eq!(
-        read_to_string("tests/testdata/expected/infinite-recursion/Note A.md").unwrap(),
-        read_to_string(tmp_dir.path().join(PathBuf::from("Note A.md"))).unwrap(),
+        "# All notes\n\n* [Note A](../Note%20A.md)\n* [Note B](../Note%20B.md)\n* [Note C](../Note%20C.md)\n* [Note D](Note%20D.md)\n",
+        read_to_string(tmp_dir.path().join("sub/index.md")).unwrap(),
+    );
+    assert_eq!(
+        "",
+        read_to_string(tmp_dir.path().join(".nojekyll")).unwrap()
+    );
+
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/link-graph/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.emit_index(Some(IndexConfig::default()));
+    exporter.link_base("/notes".to_owned());
+    exporter.run().expect("exporter returned error");
+
+    assert_eq!(
+        "# Index\n\n* [Note A](/notes/Note%20A.md)\n* [Note B](/notes/Note%20B.md)\n* [Note C](/notes/Note%20C.md)\n* [Note D](/notes/sub/Note%20D.md)\n",
+        read_to_string(tmp_dir.path().join("index.md")).unwrap(),
     );
+    assert!(!tmp_dir.path().join(".nojekyll").exists());
+}
+
+#[test]
+fn test_missing_embed_style() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    write(&src, "This embeds a missing note: ![[Missing Note]].\n").unwrap();
+
+    for (style, expected) in [
+        (None, "This embeds a missing note: .\n"),
+        (
+            Some(MissingEmbedStyle::Remove),
+            "This embeds a missing note: .\n",
+        ),
+        (
+            Some(MissingEmbedStyle::Literal),
+            "This embeds a missing note: ![[Missing Note]].\n",
+        ),
+        (
+            Some(MissingEmbedStyle::Emphasis),
+            "This embeds a missing note: *Missing Note*.\n",
+        ),
+    ] {
+        let dest = tmp_dir.path().join("dest.md");
+        let mut exporter = Exporter::new(src.clone(), dest.clone());
+        if let Some(style) = style {
+            exporter.missing_embed_style(style);
+        }
+        exporter.run().expect("exporter returned error");
+
+        assert_eq!(expected, read_to_string(dest).unwrap(), "{style:?}");
+    }
+}
+
+#[test]
+fn test_line_endings() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("Note.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "---\ntitle: Note\n---\nSome text\r\n\r\n- An item\r\n",
+    )
+    .unwrap();
+
+    let cases = [
+        (
+            LineEnding::Lf,
+            true,
+            "---\ntitle: Note\n---\n\nSome text\n\n* An item\n",
+        ),
+        (
+            LineEnding::CrLf,
+            true,
+            "---\r\ntitle: Note\r\n---\r\n\r\nSome text\r\n\r\n* An item\r\n",
+        ),
+        (
+            LineEnding::Lf,
+            false,
+            "---\ntitle: Note\n---\n\nSome text\n\n* An item",
+        ),
+        (
+            LineEnding::CrLf,
+            false,
+            "---\r\ntitle: Note\r\n---\r\n\r\nSome text\r\n\r\n* An item",
+        ),
+    ];
+    for (line_ending, trailing_newline, expected) in cases {
+        let mut exporter = Exporter::new(src.clone(), dest.clone());
+        exporter.line_ending(line_ending);
+        exporter.trailing_newline(trailing_newline);
+        exporter.run().expect("exporter returned error");
+
+        assert_eq!(
+            expected,
+            read_to_string(&dest).unwrap(),
+            "{line_ending:?}, {trailing_newline}"
+        );
+    }
 }
 
 #[test]
@@ -398,6 +2296,75 @@ fn test_no_preserve_mtime() {
     assert_ne!(src_meta.modified().unwrap(), dest_meta.modified().unwrap());
 }
 
+#[test]
+fn test_clean_destination() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir_all(vault.join("sub")).unwrap();
+    create_dir_all(dest.join(".git")).unwrap();
+    write(vault.join("Note.md"), "Note\n").unwrap();
+    write(vault.join("sub/Deleted.md"), "Deleted\n").unwrap();
+    write(dest.join(".git/HEAD"), "ref\n").unwrap();
+
+    let mut exporter = Exporter::new(vault.clone(), dest.clone());
+    exporter.clean_destination(true);
+    exporter.run().expect("exporter returned error");
+    assert!(dest.join("sub/Deleted.md").exists());
+
+    std::fs::remove_file(vault.join("sub/Deleted.md")).unwrap();
+    write(dest.join("Unrelated.txt"), "Unrelated\n").unwrap();
+    exporter.run().expect("exporter returned error");
+
+    assert!(dest.join("Note.md").exists());
+    assert!(!dest.join("sub").exists());
+    assert!(!dest.join("Unrelated.txt").exists());
+    assert!(dest.join(".git/HEAD").exists());
+}
+
+#[test]
+fn test_clean_destination_refuses_overlapping_vault() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    create_dir_all(&vault).unwrap();
+    write(vault.join("Note.md"), "Note\n").unwrap();
+    write(vault.join("image.png"), "Image\n").unwrap();
+
+    for (root, dest) in [
+        (vault.clone(), tmp_dir.path().to_path_buf()),
+        (vault.clone(), vault.clone()),
+        (tmp_dir.path().to_path_buf(), vault.join("export")),
+    ] {
+        create_dir_all(&dest).unwrap();
+        let mut exporter = Exporter::new(root, dest);
+        exporter.clean_destination(true);
+        let err = exporter.run().expect_err("export should fail");
+        assert!(
+            matches!(err, ExportError::CleanOverlapsVault { .. }),
+            "unexpected error: {:?}",
+            err
+        );
+        assert!(vault.join("Note.md").exists());
+        assert!(vault.join("image.png").exists());
+    }
+    assert!(!tmp_dir.path().join("Note.md").exists());
+}
+
+#[test]
+fn test_no_clean_destination() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    write(tmp_dir.path().join("Unrelated.txt"), "Unrelated\n").unwrap();
+
+    Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/"),
+        tmp_dir.path().to_path_buf(),
+    )
+    .run()
+    .expect("exporter returned error");
+
+    assert!(tmp_dir.path().join("Unrelated.txt").exists());
+}
+
 #[test]
 fn test_non_ascii_filenames() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
@@ -459,3 +2426,266 @@ fn test_same_filename_different_directories() {
     let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
     assert_eq!(expected, actual);
 }
+
+#[test]
+fn test_embed_frontmatter_behavior() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    create_dir(&vault).unwrap();
+    write(
+        vault.join("Note.md"),
+        "![[Titled]]\n\n## Section\n\n![[Untitled]]\n",
+    )
+    .unwrap();
+    write(
+        vault.join("Titled.md"),
+        "---\ntitle: A title\n---\n\nTitled content.\n",
+    )
+    .unwrap();
+    write(vault.join("Untitled.md"), "Untitled content.\n").unwrap();
+
+    for (behavior, expected) in [
+        (
+            EmbedFrontmatterBehavior::Drop,
+            "Titled content.\n\n## Section\n\nUntitled content.\n",
+        ),
+        (
+            EmbedFrontmatterBehavior::TitleHeading,
+            "# A title\n\nTitled content.\n\n## Section\n\n### Untitled\n\nUntitled content.\n",
+        ),
+        (
+            EmbedFrontmatterBehavior::Callout,
+            "> [!note] A title\n>\n> Titled content.\n\n## Section\n\n> [!note] Untitled\n>\n> Untitled content.\n",
+        ),
+    ] {
+        let dest = tmp_dir.path().join(format!("{behavior:?}"));
+        create_dir(&dest).unwrap();
+        let mut exporter = Exporter::new(vault.clone(), dest.clone());
+        exporter.embed_frontmatter_behavior(behavior);
+        exporter.run().expect("exporter returned error");
+
+        assert_eq!(
+            expected,
+            read_to_string(dest.join("Note.md")).unwrap(),
+            "{behavior:?}"
+        );
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_missing_attachment_handler() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Note.md"),
+        "[[report.pdf]], [[Missing note]] and [[Release 1.2]]\n\n![[missing.png]]![[Missing note]]![[broken.png]]\n",
+    )
+    .unwrap();
+    // A broken symlink is found in the vault, but doesn't exist on disk.
+    std::os::unix::fs::symlink("nowhere.png", vault.join("broken.png")).unwrap();
+
+    let missing = Mutex::new(vec![]);
+    let handler = |file: &str, context: &Context| {
+        missing.lock().unwrap().push((
+            file.to_owned(),
+            context.current_file().file_name().unwrap().to_owned(),
+        ));
+    };
+    let mut exporter = Exporter::new(vault.clone(), dest.clone());
+    // Only export the note, as the broken symlink itself can't be copied.
+    exporter.start_at(vault.join("Note.md"));
+    exporter.missing_attachment_handler(Some(&handler));
+    exporter.run().unwrap();
+
+    assert_eq!(
+        vec![
+            ("report.pdf".to_owned(), "Note.md".into()),
+            ("missing.png".to_owned(), "Note.md".into()),
+            ("broken.png".to_owned(), "Note.md".into()),
+        ],
+        missing.into_inner().unwrap()
+    );
+    assert_eq!(
+        "*report.pdf*, *Missing note* and *Release 1.2*\n\n\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_on_note_written() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let written = Mutex::new(vec![]);
+    let hook = |source: &Path, destination: &Path| {
+        assert!(destination.exists(), "{:?} was not written", destination);
+        written
+            .lock()
+            .unwrap()
+            .push((source.to_path_buf(), destination.to_path_buf()));
+    };
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.on_note_written(Some(&hook));
+    let mut expected = exporter.files_to_export().unwrap();
+    exporter.run().expect("exporter returned error");
+
+    let mut written = written.into_inner().unwrap();
+    written.sort();
+    expected.sort();
+    assert!(!written.is_empty());
+    assert_eq!(expected, written);
+}
+
+#[test]
+fn test_on_progress() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let events = Mutex::new(vec![]);
+    let handler = |done: usize, total: usize, file: &Path| {
+        events
+            .lock()
+            .unwrap()
+            .push((done, total, file.to_path_buf()));
+    };
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/main-samples/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.on_progress(Some(&handler));
+    let files = exporter.files_to_export().unwrap();
+    exporter.run().expect("exporter returned error");
+
+    let mut events = events.into_inner().unwrap();
+    events.sort();
+    assert!(!files.is_empty());
+    assert_eq!(files.len(), events.len());
+    for (index, (done, total, _)) in events.iter().enumerate() {
+        assert_eq!(index + 1, *done);
+        assert_eq!(files.len(), *total);
+    }
+    let mut processed: Vec<PathBuf> = events.into_iter().map(|(_, _, file)| file).collect();
+    let mut expected: Vec<PathBuf> = files.into_iter().map(|(src, _)| src).collect();
+    processed.sort();
+    expected.sort();
+    assert_eq!(expected, processed);
+}
+
+#[test]
+fn test_on_progress_with_external_attachments() {
+    let vault = TempDir::new().expect("failed to make tempdir");
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    create_dir_all(vault.path().join("notes")).unwrap();
+    write(vault.path().join("image.png"), "image").unwrap();
+    write(vault.path().join("notes/Note.md"), "![[image.png]]\n").unwrap();
+    write(vault.path().join("notes/Other.md"), "Other.\n").unwrap();
+
+    let events = Mutex::new(vec![]);
+    let handler = |done: usize, total: usize, _: &Path| {
+        events.lock().unwrap().push((done, total));
+    };
+    let mut exporter = Exporter::new(vault.path().to_path_buf(), tmp_dir.path().to_path_buf());
+    exporter.start_at(vault.path().join("notes"));
+    exporter.external_attachments_dir(Some(PathBuf::from("_attachments")));
+    exporter.on_progress(Some(&handler));
+    exporter.run().expect("exporter returned error");
+
+    assert!(tmp_dir.path().join("_attachments/image.png").exists());
+    let mut events = events.into_inner().unwrap();
+    events.sort_unstable();
+    assert_eq!(vec![(1, 2), (2, 2)], events);
+}
+
+#[test]
+fn test_overwrite_policy() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir(&vault).unwrap();
+    create_dir(&dest).unwrap();
+    write(vault.join("Note.md"), "Exported note\n").unwrap();
+    write(vault.join("image.png"), "Exported image\n").unwrap();
+    write(vault.join("New.md"), "New note\n").unwrap();
+
+    let mut exporter = Exporter::new(vault, dest.clone());
+    for policy in [OverwritePolicy::Skip, OverwritePolicy::Error] {
+        write(dest.join("Note.md"), "Manual edit\n").unwrap();
+        write(dest.join("image.png"), "Manual image\n").unwrap();
+        std::fs::remove_file(dest.join("New.md")).ok();
+
+        exporter.overwrite_policy(policy);
+        match (policy, exporter.run()) {
+            (OverwritePolicy::Skip, Ok(())) => {}
+            (OverwritePolicy::Error, Err(ExportError::FileExportError { source, .. })) => {
+                match *source {
+                    ExportError::DestinationExists { path } => {
+                        assert!(path == dest.join("Note.md") || path == dest.join("image.png"));
+                    }
+                    err => panic!("Wrong error variant: {:?}", err),
+                }
+                continue;
+            }
+            (policy, result) => panic!("Unexpected result for {:?}: {:?}", policy, result),
+        }
+        assert_eq!(
+            "Manual edit\n",
+            read_to_string(dest.join("Note.md")).unwrap()
+        );
+        assert_eq!(
+            "Manual image\n",
+            read_to_string(dest.join("image.png")).unwrap()
+        );
+        assert_eq!("New note\n", read_to_string(dest.join("New.md")).unwrap());
+    }
+
+    exporter.overwrite_policy(OverwritePolicy::Overwrite);
+    exporter.run().expect("exporter returned error");
+    assert_eq!(
+        "Exported note\n",
+        read_to_string(dest.join("Note.md")).unwrap()
+    );
+    assert_eq!(
+        "Exported image\n",
+        read_to_string(dest.join("image.png")).unwrap()
+    );
+}
+
+#[test]
+fn test_frontmatter_links() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let vault = tmp_dir.path().join("vault");
+    let dest = tmp_dir.path().join("dest");
+    create_dir_all(vault.join("sub")).unwrap();
+    create_dir(&dest).unwrap();
+    write(
+        vault.join("Note.md"),
+        "---\nrelated:\n- \"[[Note A]]\"\n- \"[[Note B#Some Section|B]]\"\n- Plain text\nparent: \"[[Note A]]\"\nmissing: \"[[Nope]]\"\n---\n\nBody.\n",
+    )
+    .unwrap();
+    write(vault.join("Note A.md"), "A\n").unwrap();
+    write(vault.join("sub/Note B.md"), "B\n").unwrap();
+
+    let mut exporter = Exporter::new(vault, dest.clone());
+    for (style, expected) in [
+        (
+            FrontmatterLinkStyle::MarkdownLink,
+            "---\nrelated:\n- '[Note A](Note%20A.md)'\n- '[B](sub/Note%20B.md#some-section)'\n- Plain text\nparent: '[Note A](Note%20A.md)'\nmissing: '[[Nope]]'\n---\n\nBody.\n",
+        ),
+        (
+            FrontmatterLinkStyle::Path,
+            "---\nrelated:\n- Note%20A.md\n- sub/Note%20B.md#some-section\n- Plain text\nparent: Note%20A.md\nmissing: '[[Nope]]'\n---\n\nBody.\n",
+        ),
+    ] {
+        exporter.frontmatter_links(Some(style));
+        exporter.run().unwrap();
+        assert_eq!(
+            expected,
+            read_to_string(dest.join("Note.md")).unwrap(),
+            "{:?}",
+            style
+        );
+    }
+}
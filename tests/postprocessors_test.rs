@@ -1,12 +1,41 @@
 use std::collections::HashSet;
-use std::fs::{read_to_string, remove_file};
+use std::fs::{read_to_string, remove_file, write};
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-use obsidian_export::postprocessors::{filter_by_tags, softbreaks_to_hardbreaks};
-use obsidian_export::{Context, Exporter, MarkdownEvents, PostprocessorResult};
+use obsidian_export::postprocessors::{
+    add_content_hash,
+    autolink_urls,
+    callouts_to_html,
+    clean_task_metadata,
+    ensure_title,
+    extract_description,
+    filter_by_tags,
+    highlights_to_html,
+    inline_footnotes,
+    interpolate_frontmatter,
+    prefix_internal_links,
+    resolve_markdown_links,
+    smartypants,
+    softbreaks_to_hardbreaks,
+    strip_body,
+    validate_internal_anchors,
+    TaskMetadataStyle,
+    TitleStrategy,
+    UnknownPlaceholderStrategy,
+};
+use obsidian_export::{
+    vault_contents,
+    Context,
+    ExportError,
+    Exporter,
+    FrontmatterStrategy,
+    MarkdownEvents,
+    PostprocessorResult,
+    WalkOptions,
+};
 use pretty_assertions::assert_eq;
-use pulldown_cmark::{CowStr, Event};
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
 use serde_yaml::Value;
 use tempfile::TempDir;
 use walkdir::WalkDir;
@@ -49,6 +78,46 @@ fn test_postprocessors() {
     assert_eq!(expected, actual);
 }
 
+struct ExportJob {
+    exporter: Exporter<'static>,
+}
+
+impl ExportJob {
+    fn new(destination: PathBuf) -> Self {
+        let replacement = String::from("bar");
+        let mut exporter = Exporter::new(
+            PathBuf::from("tests/testdata/input/postprocessors"),
+            destination,
+        );
+        exporter.add_postprocessor_arc(Arc::new(move |_ctx, events| {
+            for event in events.iter_mut() {
+                if let Event::Text(text) = event {
+                    *event = Event::Text(CowStr::from(text.replace("foo", &replacement)));
+                }
+            }
+            PostprocessorResult::Continue
+        }));
+        exporter.add_postprocessor_arc(Arc::new(append_frontmatter));
+        Self { exporter }
+    }
+}
+
+// Postprocessors added through add_postprocessor_arc are owned by the exporter, so it can be
+// stored and run after the scope the postprocessors were created in has ended.
+#[test]
+fn test_postprocessor_arc() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let job = ExportJob::new(tmp_dir.path().to_path_buf());
+    let mut exporter = job.exporter.clone();
+    drop(job);
+
+    exporter.run().unwrap();
+
+    let expected = read_to_string("tests/testdata/expected/postprocessors/Note.md").unwrap();
+    let actual = read_to_string(tmp_dir.path().join(PathBuf::from("Note.md"))).unwrap();
+    assert_eq!(expected, actual);
+}
+
 #[test]
 fn test_postprocessor_stophere() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
@@ -98,7 +167,9 @@ fn test_postprocessor_change_destination() {
     remove_file(&original_note_path).unwrap();
 
     exporter.add_postprocessor(&|ctx, _mdevents| {
-        ctx.destination.set_file_name("MovedNote.md");
+        if ctx.destination.ends_with("Note.md") {
+            ctx.destination.set_file_name("MovedNote.md");
+        }
         PostprocessorResult::Continue
     });
     exporter.run().unwrap();
@@ -108,6 +179,32 @@ fn test_postprocessor_change_destination() {
     assert!(new_note_path.exists());
 }
 
+#[test]
+fn test_postprocessor_destination_collision() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/postprocessors"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.add_postprocessor(&|ctx, _mdevents| {
+        ctx.destination.set_file_name("Collision.md");
+        PostprocessorResult::Continue
+    });
+
+    match exporter.run().unwrap_err() {
+        ExportError::FileExportError { source, .. } => match *source {
+            ExportError::DestinationCollision { path, a, b } => {
+                assert_eq!(tmp_dir.path().join("Collision.md"), path);
+                assert!(a < b);
+                assert!(a.starts_with("tests/testdata/input/postprocessors"));
+                assert!(b.starts_with("tests/testdata/input/postprocessors"));
+            }
+            _ => panic!("Wrong error variant for source, got: {:?}", source),
+        },
+        err => panic!("Wrong error variant: {:?}", err),
+    }
+}
+
 // Ensure postprocessor type definition has proper lifetimes to allow state
 // (here: `parents`) to be passed in. Otherwise, this fails with an error like:
 //     error[E0597]: `parents` does not live long enough
@@ -238,6 +335,636 @@ fn test_embed_postprocessors_context() {
     exporter.run().unwrap();
 }
 
+#[test]
+#[allow(clippy::significant_drop_tightening)]
+fn test_embed_postprocessors_parent_file() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/embed-chain"),
+        tmp_dir.path().to_path_buf(),
+    );
+
+    let parents: Mutex<Vec<(PathBuf, Option<PathBuf>)>> = Mutex::default();
+    let callback = |ctx: &mut Context, _mdevents: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        if ctx.root_file().ends_with("Root.md") {
+            parents
+                .lock()
+                .unwrap()
+                .push((ctx.current_file().clone(), ctx.parent_file().cloned()));
+        }
+        PostprocessorResult::Continue
+    };
+    exporter.add_postprocessor(&callback);
+    exporter.add_embed_postprocessor(&callback);
+    exporter.run().unwrap();
+
+    let source = PathBuf::from("tests/testdata/input/embed-chain");
+    let mut parents = parents.lock().unwrap();
+    parents.sort();
+    assert_eq!(
+        *parents,
+        vec![
+            (source.join("Leaf.md"), Some(source.join("Middle.md"))),
+            (source.join("Middle.md"), Some(source.join("Root.md"))),
+            (source.join("Root.md"), None),
+        ]
+    );
+}
+
+#[test]
+fn test_postprocessors_for_depth() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/embed-chain"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.add_postprocessor_for_depth(1..=1, &|_ctx, events| {
+        events.push(Event::Rule);
+        events.push(Event::Start(Tag::Paragraph));
+        events.push(Event::Text(CowStr::from("Footer")));
+        events.push(Event::End(TagEnd::Paragraph));
+        PostprocessorResult::Continue
+    });
+    exporter.add_postprocessor_for_depth(2.., &|ctx, events| {
+        events.insert(
+            0,
+            Event::Text(CowStr::from(format!("{}: ", ctx.note_depth()))),
+        );
+        PostprocessorResult::Continue
+    });
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "Root note.\n\n2: Middle note.\n\n3: Leaf note.\n\n---\n\nFooter\n",
+        read_to_string(tmp_dir.path().join("Root.md")).unwrap(),
+    );
+    assert_eq!(
+        "Leaf note.\n\n---\n\nFooter\n",
+        read_to_string(tmp_dir.path().join("Leaf.md")).unwrap(),
+    );
+}
+
+#[test]
+fn test_preprocessors() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/embed-chain"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.add_preprocessor(&|_ctx, text| *text = text.replace("note.", "note (root)."));
+    exporter.add_embed_preprocessor(&|_ctx, text| *text = text.replace("note.", "note (embed)."));
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "Root note (root).\n\nMiddle note (embed).\n\nLeaf note (embed).\n",
+        read_to_string(tmp_dir.path().join("Root.md")).unwrap()
+    );
+    assert_eq!(
+        "Middle note (root).\n\nLeaf note (embed).\n",
+        read_to_string(tmp_dir.path().join("Middle.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_postprocessors_source_content() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/embed-chain"),
+        tmp_dir.path().to_path_buf(),
+    );
+
+    let seen: Mutex<Vec<PathBuf>> = Mutex::default();
+    let callback = |ctx: &mut Context, _mdevents: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let expected = read_to_string(ctx.current_file()).unwrap();
+        assert_eq!(expected, ctx.source_content());
+        seen.lock().unwrap().push(ctx.current_file().clone());
+        PostprocessorResult::Continue
+    };
+    exporter.add_postprocessor(&callback);
+    exporter.add_embed_postprocessor(&callback);
+    exporter.run().unwrap();
+
+    // Root.md embeds Middle.md which embeds Leaf.md, so embedded notes are seen more than once.
+    assert_eq!(seen.lock().unwrap().len(), 6);
+}
+
+#[test]
+fn test_postprocessor_output_bytes() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/postprocessors/Note.md"),
+        tmp_dir.path().join("Note.json"),
+    );
+    exporter.frontmatter_strategy(FrontmatterStrategy::Always);
+    exporter.add_postprocessor(&append_frontmatter);
+    exporter.add_postprocessor(&|ctx, _events| {
+        ctx.set_output_bytes(b"{\"note\": true}".to_vec());
+        PostprocessorResult::Continue
+    });
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "{\"note\": true}",
+        read_to_string(tmp_dir.path().join("Note.json")).unwrap()
+    );
+}
+
+#[test]
+fn test_strip_body() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = tmp_dir.path().join("source");
+    let destination = tmp_dir.path().join("destination");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&destination).unwrap();
+    write(
+        source.join("With frontmatter.md"),
+        "---\ntitle: Note\n---\n\nSome text.\n",
+    )
+    .unwrap();
+    write(source.join("Without frontmatter.md"), "Some text.\n").unwrap();
+
+    let mut exporter = Exporter::new(source, destination.clone());
+    exporter.add_postprocessor(&strip_body);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "---\ntitle: Note\n---\n",
+        read_to_string(destination.join("With frontmatter.md")).unwrap()
+    );
+    assert_eq!(
+        "",
+        read_to_string(destination.join("Without frontmatter.md")).unwrap()
+    );
+}
+
+#[test]
+fn test_inline_footnotes() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "Some text^[An *inline* footnote.] and ^[Another [one].] as well as `^[code]`.[^1]\n\n[^1]: A regular footnote.\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&inline_footnotes);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "Some text[^2] and [^3] as well as `^[code]`.[^1]\n\n[^1]: A regular footnote.\n\n[^2]: An *inline* footnote.\n\n[^3]: Another \\[one\\].\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_inline_footnotes_single_and_code_blocks() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "A note^[With [[nested] brackets].]\n\n```\nNot a^[footnote]\n```\n\nUnclosed ^[footnote\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&inline_footnotes);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "A note[^1]\n\n````\nNot a^[footnote]\n````\n\nUnclosed ^\\[footnote\n\n[^1]: With \\[\\[nested\\] brackets\\].\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_highlights_to_html() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "Some ==highlighted text== and (==this==), but not `==code==` or a == b.\n\nNot ==across\n\nparagraphs==.\n\n```\n==code block==\n```\n",
+    )
+    .unwrap();
+
+    let postprocessor = highlights_to_html("mark".to_owned());
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "Some <mark>highlighted text</mark> and (<mark>this</mark>), but not `==code==` or a == b.\n\nNot ==across\n\nparagraphs==.\n\n````\n==code block==\n````\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_callouts_to_html() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "> [!Tip]- A **bold\n> title** with [a link](https://example.com)\n> The body.\n>\n> > [!note|wide]\n> > Nested.\n\n> [!info]\n\n> Just a quote.\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&callouts_to_html);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        concat!(
+            "<div class=\"callout\" data-callout=\"tip\" data-callout-fold=\"collapsed\">\n",
+            "<div class=\"callout-title\">A <strong>bold\ntitle</strong> with <a href=\"https://example.com\">a link</a></div>\n",
+            "<div class=\"callout-content\">\n\nThe body.\n\n",
+            "<div class=\"callout\" data-callout=\"note\" data-callout-metadata=\"wide\">\n",
+            "<div class=\"callout-title\">Note</div>\n",
+            "<div class=\"callout-content\">\n\nNested.\n\n</div>\n</div>\n\n</div>\n</div>\n\n",
+            "<div class=\"callout\" data-callout=\"info\">\n",
+            "<div class=\"callout-title\">Info</div>\n",
+            "<div class=\"callout-content\">\n\n</div>\n</div>\n\n",
+            " > \n > Just a quote.\n",
+        ),
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_autolink_urls() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "See https://example.com/a_path?q=1. Or (https://en.wikipedia.org/wiki/Rust_(programming_language)).\n\nAlready [linked https://example.com](https://example.com), <https://example.org> and `https://example.com/code`.\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&autolink_urls);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "See <https://example.com/a_path?q=1>. Or (<https://en.wikipedia.org/wiki/Rust_(programming_language)>).\n\nAlready [linked https://example.com](https://example.com), <https://example.org> and `https://example.com/code`.\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_smartypants() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "\"Hello,\" she said. It's *'quoted'*, (\"really\")... in the '90s -- or 1990--1999---maybe.\n\nCode `\"stays\" -- 'as-is'...` here.\n\n```\n\"code\" -- block\n```\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&smartypants);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "“Hello,” she said. It’s *‘quoted’*, (“really”)… in the ’90s – or 1990–1999—maybe.\n\nCode `\"stays\" -- 'as-is'...` here.\n\n````\n\"code\" -- block\n````\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_interpolate_frontmatter() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "---\ntitle: A note\nweight: 3\ntags: [one]\n---\n# {{title}}\n\n{{ weight }} {{{title}}} {{unknown}} {{tags}} }} {{\n\n`{{title}}`\n\n```\n{{title}}\n```\n",
+    )
+    .unwrap();
+
+    let mut exporter = Exporter::new(src.clone(), dest.clone());
+    exporter.frontmatter_strategy(FrontmatterStrategy::Never);
+    let interpolate = interpolate_frontmatter(
+        "{{".to_owned(),
+        "}}".to_owned(),
+        UnknownPlaceholderStrategy::Keep,
+    );
+    exporter.add_postprocessor(&interpolate);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "# A note\n\n3 {A note} {{unknown}} {{tags}} }} {{\n\n`{{title}}`\n\n````\n{{title}}\n````\n",
+        read_to_string(&dest).unwrap()
+    );
+
+    remove_file(&dest).unwrap();
+    let mut strict_exporter = Exporter::new(src, dest.clone());
+    let strict_interpolate = interpolate_frontmatter(
+        "{{".to_owned(),
+        "}}".to_owned(),
+        UnknownPlaceholderStrategy::SkipNote,
+    );
+    strict_exporter.add_postprocessor(&strict_interpolate);
+    strict_exporter.run().unwrap();
+    assert!(!dest.exists());
+}
+
+#[test]
+#[should_panic(expected = "placeholder delimiters must not be empty")]
+fn test_interpolate_frontmatter_empty_delimiter() {
+    let _ = interpolate_frontmatter(
+        "{{".to_owned(),
+        String::new(),
+        UnknownPlaceholderStrategy::Keep,
+    );
+}
+
+#[test]
+fn test_clean_task_metadata() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "- [ ] Do *the* thing 🔁 every week on Monday 🛫 2024-01-01 📅 2024-01-08 ⏫\n- [x] Done ✅ 2024-01-02\n- Not a task 📅 2024-01-09\n",
+    )
+    .unwrap();
+
+    let remove = clean_task_metadata(TaskMetadataStyle::Remove, Some("due".to_owned()));
+    let mut exporter = Exporter::new(src.clone(), dest.clone());
+    exporter.add_postprocessor(&remove);
+    exporter.run().unwrap();
+    assert_eq!(
+        "---\ndue:\n- 2024-01-08\n---\n\n* [ ] Do *the* thing\n* [x] Done\n* Not a task 📅 2024-01-09\n",
+        read_to_string(&dest).unwrap()
+    );
+
+    let describe = clean_task_metadata(TaskMetadataStyle::Text, None);
+    let mut text_exporter = Exporter::new(src, dest.clone());
+    text_exporter.add_postprocessor(&describe);
+    text_exporter.run().unwrap();
+    assert_eq!(
+        "* [ ] Do *the* thing (every week on Monday, start 2024-01-01, due 2024-01-08, high priority)\n* [x] Done (done 2024-01-02)\n* Not a task 📅 2024-01-09\n",
+        read_to_string(&dest).unwrap()
+    );
+}
+
+#[test]
+fn test_clean_task_metadata_due_dates() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "- [ ] A 📅 2024-03-01\n- [ ] B 📅 2024-01-08\n- [ ] C 📅 2024-03-01\n- [ ] D 📅 2024-01-08\n",
+    )
+    .unwrap();
+
+    let postprocessor = clean_task_metadata(TaskMetadataStyle::Remove, Some("due".to_owned()));
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+    assert_eq!(
+        "---\ndue:\n- 2024-01-08\n- 2024-03-01\n---\n\n* [ ] A\n* [ ] B\n* [ ] C\n* [ ] D\n",
+        read_to_string(&dest).unwrap()
+    );
+}
+
+#[test]
+fn test_extract_description() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "---\ntitle: Note\n---\nThe *first* paragraph, linking to [[Other note|another note]]\nover `two` lines.\n\nThe second paragraph.\n",
+    )
+    .unwrap();
+
+    let postprocessor = extract_description("description".to_owned(), 50);
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "---\ntitle: Note\ndescription: The first paragraph, linking to another note over\n---\n\nThe *first* paragraph, linking to *another note*\nover `two` lines.\n\nThe second paragraph.\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_extract_description_after_heading() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(&src, "# Heading\n\nSome text.\n").unwrap();
+
+    let postprocessor = extract_description("description".to_owned(), 50);
+    let mut exporter = Exporter::new(src.clone(), dest.clone());
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+    assert_eq!(
+        "---\ndescription: Some text.\n---\n\n# Heading\n\nSome text.\n",
+        read_to_string(&dest).unwrap()
+    );
+
+    // Notes without any paragraphs don't get a description.
+    write(&src, "# Heading\n").unwrap();
+    exporter.run().unwrap();
+    assert_eq!("# Heading\n", read_to_string(&dest).unwrap());
+}
+
+#[test]
+fn test_add_content_hash() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    let postprocessor = add_content_hash("content_hash".to_owned());
+    let export = |content: &str| -> String {
+        write(&src, content).unwrap();
+        let mut exporter = Exporter::new(src.clone(), dest.clone());
+        exporter.add_postprocessor(&postprocessor);
+        exporter.run().unwrap();
+
+        let exported = read_to_string(&dest).unwrap();
+        let frontmatter = exported.split("---\n").nth(1).unwrap();
+        let frontmatter: serde_yaml::Mapping = serde_yaml::from_str(frontmatter).unwrap();
+        frontmatter
+            .get("content_hash")
+            .and_then(Value::as_str)
+            .unwrap()
+            .to_owned()
+    };
+
+    let hash = export("Some *content*.\n");
+    assert_eq!(hash.len(), 64);
+    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(hash, export("Some *content*.\n"));
+    // Only the body of a note is included in the hash.
+    assert_eq!(hash, export("---\ntitle: Note\n---\nSome *content*.\n"));
+    assert_ne!(hash, export("Some other *content*.\n"));
+}
+
+#[test]
+fn test_normalize_line_endings() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(&src, "---\r\ntitle: Note\r\n---\r\n\r\nSome text.\r\n").unwrap();
+
+    let sources: Mutex<Vec<String>> = Mutex::default();
+    let callback = |ctx: &mut Context, _mdevents: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        sources
+            .lock()
+            .unwrap()
+            .push(ctx.source_content().to_owned());
+        PostprocessorResult::Continue
+    };
+
+    let mut exporter = Exporter::new(src.clone(), dest.clone());
+    exporter.add_postprocessor(&callback);
+    exporter.run().unwrap();
+
+    let mut normalizing_exporter = Exporter::new(src, dest);
+    normalizing_exporter.normalize_line_endings(true);
+    normalizing_exporter.add_postprocessor(&callback);
+    normalizing_exporter.run().unwrap();
+
+    assert_eq!(
+        *sources.lock().unwrap(),
+        vec![
+            "---\r\ntitle: Note\r\n---\r\n\r\nSome text.\r\n".to_owned(),
+            "---\ntitle: Note\n---\n\nSome text.\n".to_owned(),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_internal_anchors() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/internal-anchors/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    let postprocessor = validate_internal_anchors(false);
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+
+    assert!(tmp_dir.path().join("Invalid anchors.md").exists());
+    assert!(tmp_dir.path().join("Valid anchors.md").exists());
+}
+
+#[test]
+fn test_validate_internal_anchors_strict() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/internal-anchors/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    let postprocessor = validate_internal_anchors(true);
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+
+    assert!(!tmp_dir.path().join("Invalid anchors.md").exists());
+    assert_eq!(
+        read_to_string(tmp_dir.path().join("Valid anchors.md")).unwrap(),
+        "# Some `code` heading\n\nLinks to [Some code heading](Valid%20anchors.md#some-code-heading) and [Invalid anchors > Existing heading](Invalid%20anchors.md#existing-heading).\n"
+    );
+}
+
+#[test]
+fn test_ensure_title_frontmatter() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/titles/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    let postprocessor = ensure_title(TitleStrategy::Frontmatter, true);
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+
+    for (filename, expected) in [
+        (
+            "my-first_note.md",
+            "---\ntitle: my first note\n---\n\nSome text.\n",
+        ),
+        (
+            "With title.md",
+            "---\ntitle: Custom title\n---\n\nSome text.\n",
+        ),
+        (
+            "With frontmatter.md",
+            "---\ntags:\n- foo\ntitle: With frontmatter\n---\n\n## Not a title\n\nSome text.\n",
+        ),
+    ] {
+        let actual = read_to_string(tmp_dir.path().join(filename)).unwrap();
+        assert_eq!(
+            expected, actual,
+            "{filename} does not have expected content"
+        );
+    }
+}
+
+#[test]
+fn test_ensure_title_heading() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/titles/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    let postprocessor = ensure_title(TitleStrategy::Heading, false);
+    exporter.add_postprocessor(&postprocessor);
+    exporter.run().unwrap();
+
+    for (filename, expected) in [
+        ("my-first_note.md", "# my-first_note\n\nSome text.\n"),
+        ("With heading.md", "# Existing heading\n\nSome text.\n"),
+        (
+            "With frontmatter.md",
+            "---\ntags:\n- foo\n---\n\n# With frontmatter\n\n## Not a title\n\nSome text.\n",
+        ),
+    ] {
+        let actual = read_to_string(tmp_dir.path().join(filename)).unwrap();
+        assert_eq!(
+            expected, actual,
+            "{filename} does not have expected content"
+        );
+    }
+}
+
+#[test]
+fn test_postprocessors_vault_root() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let mut exporter = Exporter::new(
+        PathBuf::from("tests/testdata/input/start-at/"),
+        tmp_dir.path().to_path_buf(),
+    );
+    exporter.start_at(PathBuf::from("tests/testdata/input/start-at/subdir/"));
+
+    let paths: Mutex<Vec<PathBuf>> = Mutex::default();
+    let callback = |ctx: &mut Context, _mdevents: &mut MarkdownEvents<'_>| -> PostprocessorResult {
+        let relative_path = ctx.current_file().strip_prefix(ctx.vault_root()).unwrap();
+        paths.lock().unwrap().push(relative_path.to_path_buf());
+        PostprocessorResult::Continue
+    };
+    exporter.add_postprocessor(&callback);
+    exporter.run().unwrap();
+
+    let mut paths = paths.into_inner().unwrap();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("subdir/Note B.md"),
+            PathBuf::from("subdir/Note C.md"),
+        ]
+    );
+}
+
 #[test]
 fn test_softbreaks_to_hardbreaks() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
@@ -254,6 +981,29 @@ fn test_softbreaks_to_hardbreaks() {
     assert_eq!(expected, actual);
 }
 
+#[test]
+fn test_resolve_markdown_links() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = PathBuf::from("tests/testdata/input/resolve-markdown-links");
+    let mut exporter = Exporter::new(source.clone(), tmp_dir.path().to_path_buf());
+    let resolve_markdown_links =
+        resolve_markdown_links(vault_contents(&source, WalkOptions::default()).unwrap());
+    exporter.add_postprocessor(&resolve_markdown_links);
+    exporter.run().unwrap();
+
+    for filename in ["Note.md", "subdir/Nested Note.md"] {
+        let expected = read_to_string(
+            PathBuf::from("tests/testdata/expected/resolve-markdown-links").join(filename),
+        )
+        .unwrap();
+        let actual = read_to_string(tmp_dir.path().join(filename)).unwrap();
+        assert_eq!(
+            expected, actual,
+            "{filename} does not have expected content"
+        );
+    }
+}
+
 #[test]
 fn test_filter_by_tags() {
     let tmp_dir = TempDir::new().expect("failed to make tempdir");
@@ -294,4 +1044,75 @@ fn test_filter_by_tags() {
             filename
         );
     }
+    for skipped in ["string-private.md", "number.md"] {
+        assert!(
+            !tmp_dir.path().join(skipped).exists(),
+            "{} should have been skipped",
+            skipped
+        );
+    }
+}
+
+#[test]
+fn test_text_postprocessor() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let src = tmp_dir.path().join("source.md");
+    let dest = tmp_dir.path().join("dest.md");
+    write(
+        &src,
+        "---\ntitle: A note  \n---\n\nA hard break  \nfollowed by text.\n\n```\ncode with trailing spaces   \n```\n",
+    )
+    .unwrap();
+
+    let trim_trailing_whitespace = |_context: &mut Context, text: &mut String| {
+        *text = text
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n";
+        PostprocessorResult::Continue
+    };
+    let mut exporter = Exporter::new(src, dest.clone());
+    exporter.add_text_postprocessor(&trim_trailing_whitespace);
+    exporter.run().unwrap();
+
+    assert_eq!(
+        "---\ntitle: A note\n---\n\nA hard break\nfollowed by text.\n\n````\ncode with trailing spaces\n````\n",
+        read_to_string(dest).unwrap()
+    );
+}
+
+#[test]
+fn test_prefix_internal_links() {
+    let tmp_dir = TempDir::new().expect("failed to make tempdir");
+    let source = tmp_dir.path().join("source");
+    std::fs::create_dir_all(source.join("sub dir")).unwrap();
+    write(
+        source.join("sub dir/Note.md"),
+        "Links to [[Other]], [[Other#A Heading|a heading]], [[#Local]] and [the web](https://example.com).\n\n![[image.png]]\n\n# Local\n",
+    )
+    .unwrap();
+    write(source.join("Other.md"), "# A Heading\n").unwrap();
+    write(source.join("sub dir/image.png"), "").unwrap();
+
+    let prefix_links = prefix_internal_links("/notes/".to_owned());
+    let mut exports = [true, false].iter().map(|&with_postprocessor| {
+        let destination = tmp_dir.path().join(format!("dest-{with_postprocessor}"));
+        std::fs::create_dir_all(&destination).unwrap();
+        let mut exporter = Exporter::new(source.clone(), destination.clone());
+        match with_postprocessor {
+            true => exporter.add_postprocessor(&prefix_links),
+            false => exporter.link_base("/notes/".to_owned()),
+        };
+        exporter.run().unwrap();
+        read_to_string(destination.join("sub dir/Note.md")).unwrap()
+    });
+    let prefixed = exports.next().unwrap();
+    assert_eq!(
+        "Links to [Other](/notes/Other.md), [a heading](/notes/Other.md#a-heading), [Local](/notes/sub%20dir/Note.md#local) and [the web](https://example.com).\n\n![image.png](/notes/sub%20dir/image.png)\n\n# Local\n",
+        prefixed
+    );
+    // The postprocessor produces the same links as Exporter::link_base.
+    assert_eq!(prefixed, exports.next().unwrap());
 }